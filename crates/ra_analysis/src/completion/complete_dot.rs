@@ -1,53 +1,77 @@
-use ra_syntax::ast::AstNode;
 use hir::{Ty, Def};
 
 use crate::Cancelable;
 use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
 
-/// Complete dot accesses, i.e. fields or methods (currently only fields).
+/// Complete dot accesses, i.e. fields or methods.
 pub(super) fn complete_dot(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
     let (function, receiver) = match (&ctx.function, ctx.dot_receiver) {
         (Some(function), Some(receiver)) => (function, receiver),
         _ => return Ok(()),
     };
     let infer_result = function.infer(ctx.db)?;
-    let receiver_ty = if let Some(ty) = infer_result.type_of_node(receiver.syntax()) {
-        ty
-    } else {
-        return Ok(());
+    let syntax_mapping = function.body_syntax_mapping(ctx.db)?;
+    let expr = match syntax_mapping.node_expr(receiver) {
+        Some(expr) => expr,
+        None => return Ok(()),
     };
+    let receiver_ty = infer_result[expr].clone();
     if !ctx.is_method_call {
-        complete_fields(acc, ctx, receiver_ty)?;
+        complete_fields(acc, ctx, receiver_ty.clone())?;
     }
+    complete_methods(acc, ctx, function, receiver_ty)?;
     Ok(())
 }
 
 fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty) -> Cancelable<()> {
-    // TODO: autoderef etc.
-    match receiver {
-        Ty::Adt { def_id, .. } => {
-            match def_id.resolve(ctx.db)? {
-                Def::Struct(s) => {
-                    let variant_data = s.variant_data(ctx.db)?;
-                    for field in variant_data.fields() {
-                        CompletionItem::new(CompletionKind::Reference, field.name().to_string())
+    for receiver in receiver.autoderef(ctx.db, false) {
+        match receiver? {
+            Ty::Adt { def_id, .. } => {
+                match def_id.resolve(ctx.db)? {
+                    Def::Struct(s) => {
+                        let variant_data = s.variant_data(ctx.db)?;
+                        for field in variant_data.fields() {
+                            CompletionItem::new(
+                                CompletionKind::Reference,
+                                field.name().to_string(),
+                            )
                             .kind(CompletionItemKind::Field)
                             .add_to(acc);
+                        }
                     }
+                    // TODO unions
+                    _ => {}
                 }
-                // TODO unions
-                _ => {}
             }
-        }
-        Ty::Tuple(fields) => {
-            for (i, _ty) in fields.iter().enumerate() {
-                CompletionItem::new(CompletionKind::Reference, i.to_string())
-                    .kind(CompletionItemKind::Field)
-                    .add_to(acc);
+            Ty::Tuple(fields) => {
+                for (i, _ty) in fields.iter().enumerate() {
+                    CompletionItem::new(CompletionKind::Reference, i.to_string())
+                        .kind(CompletionItemKind::Field)
+                        .add_to(acc);
+                }
             }
+            _ => {}
+        };
+    }
+    Ok(())
+}
+
+fn complete_methods(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    function: &hir::Function,
+    receiver: Ty,
+) -> Cancelable<()> {
+    let module = function.module(ctx.db)?;
+    let generics = function.generic_params(ctx.db);
+    receiver.iterate_methods(ctx.db, &module, &generics, |_ty, func| {
+        if func.has_self_param(ctx.db) {
+            CompletionItem::new(CompletionKind::Reference, func.name(ctx.db).to_string())
+                .kind(CompletionItemKind::Method)
+                .add_to(acc);
         }
-        _ => {}
-    };
+        Ok(None::<()>)
+    })?;
     Ok(())
 }
 
@@ -72,6 +96,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_struct_field_completion_autoderef() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            fn foo(a: &&A) {
+               a.<|>
+            }
+            ",
+            r#"the_field"#,
+        );
+    }
+
+    #[test]
+    fn test_struct_field_completion_user_deref() {
+        check_ref_completion(
+            r"
+            trait Deref { type Target; }
+            struct A { the_field: u32 }
+            struct Wrapper { inner: A }
+            impl Deref for Wrapper { type Target = A; }
+            fn foo(w: Wrapper) {
+               w.<|>
+            }
+            ",
+            r#"inner
+               the_field"#,
+        );
+    }
+
     #[test]
     fn test_no_struct_field_completion_for_method_call() {
         check_ref_completion(
@@ -84,4 +138,166 @@ mod tests {
             r#""#,
         );
     }
+
+    #[test]
+    fn test_method_completion() {
+        check_ref_completion(
+            r"
+            struct A {}
+            impl A {
+                fn the_method(&self) {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"the_method"#,
+        );
+    }
+
+    #[test]
+    fn test_fields_and_methods_completion() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            impl A {
+                fn the_method(&self) {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"the_field
+               the_method"#,
+        );
+    }
+
+    #[test]
+    fn test_self_completion() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            impl A {
+                fn the_method(&self) {}
+                fn foo(&mut self) {
+                   self.<|>
+                }
+            }
+            ",
+            r#"the_field
+               the_method
+               foo"#,
+        );
+    }
+
+    #[test]
+    fn test_method_completion_autoderef() {
+        check_ref_completion(
+            r"
+            struct A {}
+            impl A {
+                fn the_method(&self) {}
+            }
+            fn foo(a: &A) {
+               a.<|>
+            }
+            ",
+            r#"the_method"#,
+        );
+    }
+
+    #[test]
+    fn test_no_non_self_method() {
+        check_ref_completion(
+            r"
+            struct A {}
+            impl A {
+                fn the_method() {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#""#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion() {
+        check_ref_completion(
+            r"
+            struct A {}
+            trait Trait { fn the_method(&self); }
+            impl Trait for A {}
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"the_method"#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion_requires_import() {
+        check_ref_completion(
+            r"
+            struct A {}
+            mod m {
+                pub trait Trait { fn the_method(&self); }
+                impl Trait for super::A {}
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#""#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion_for_bounded_param() {
+        check_ref_completion(
+            r"
+            trait Display { fn fmt(&self); }
+            trait ToString { fn to_string(&self); }
+            impl<T: Display> ToString for T {}
+            fn foo<T>(t: T) where T: Display {
+               t.<|>
+            }
+            ",
+            r#"fmt
+               to_string"#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion_for_dyn_trait() {
+        check_ref_completion(
+            r"
+            mod handlers { pub trait Handler { fn handle(&self); } }
+            trait Deref { type Target; }
+            struct Box<T> {}
+            impl<T> Deref for Box<T> { type Target = T; }
+            struct S { h: Box<dyn handlers::Handler> }
+            fn foo(s: S) {
+               s.h.<|>
+            }
+            ",
+            r#"handle"#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion_for_impl_trait() {
+        check_ref_completion(
+            r"
+            mod iter { pub trait Iterator { type Item; fn next(&mut self); } }
+            fn numbers() -> impl iter::Iterator<Item = u32> {}
+            fn foo() {
+               numbers().<|>
+            }
+            ",
+            r#"next"#,
+        );
+    }
 }
@@ -13,13 +13,16 @@ pub(super) fn complete_path(acc: &mut Completions, ctx: &CompletionContext) -> C
         None => return Ok(()),
     };
     match def_id.resolve(ctx.db)? {
-        hir::Def::Module(module) => {
-            let module_scope = module.scope(ctx.db)?;
-            module_scope.entries().for_each(|(name, res)| {
-                CompletionItem::new(CompletionKind::Reference, name.to_string())
-                    .from_resolution(ctx.db, res)
-                    .add_to(acc)
-            });
+        hir::Def::Module(target) => {
+            let module_scope = target.scope(ctx.db)?;
+            module_scope
+                .entries()
+                .filter(|(_name, res)| res.vis.is_visible_from(module))
+                .for_each(|(name, res)| {
+                    CompletionItem::new(CompletionKind::Reference, name.to_string())
+                        .from_resolution(ctx.db, res)
+                        .add_to(acc)
+                });
         }
         hir::Def::Enum(e) => e
             .variants(ctx.db)?
@@ -49,13 +52,36 @@ mod tests {
             use self::m::<|>;
 
             mod m {
-                struct Bar;
+                pub struct Bar;
             }
             ",
             "Bar",
         );
     }
 
+    #[test]
+    fn completes_only_visible_items() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod foo;
+            mod bar {
+                pub struct Pub;
+                pub(crate) struct PubCrate;
+                pub(super) struct PubSuper;
+                pub(in crate::bar) struct PubInBar;
+                struct Private;
+                pub mod baz {
+                    pub(super) struct PubSuperInBaz;
+                }
+            }
+            //- /foo.rs
+            use crate::bar::<|>
+            ",
+            "Pub;PubCrate;PubSuper;baz",
+        );
+    }
+
     #[test]
     fn completes_use_item_starting_with_crate() {
         check_reference_completion(
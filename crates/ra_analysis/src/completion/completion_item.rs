@@ -32,6 +32,11 @@ pub enum CompletionItemKind {
     EnumVariant,
     Binding,
     Field,
+    Method,
+    Trait,
+    TypeAlias,
+    Const,
+    Static,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -137,6 +142,26 @@ impl Builder {
                 types: Some(hir::Def::Enum(..)),
                 ..
             } => CompletionItemKind::Enum,
+            PerNs {
+                types: Some(hir::Def::EnumVariant(..)),
+                ..
+            } => CompletionItemKind::EnumVariant,
+            PerNs {
+                types: Some(hir::Def::Trait(..)),
+                ..
+            } => CompletionItemKind::Trait,
+            PerNs {
+                types: Some(hir::Def::Type(..)),
+                ..
+            } => CompletionItemKind::TypeAlias,
+            PerNs {
+                values: Some(hir::Def::Const(..)),
+                ..
+            } => CompletionItemKind::Const,
+            PerNs {
+                values: Some(hir::Def::Static(..)),
+                ..
+            } => CompletionItemKind::Static,
             PerNs {
                 values: Some(hir::Def::Function(..)),
                 ..
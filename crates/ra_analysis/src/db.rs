@@ -1,7 +1,7 @@
 use std::{fmt, sync::Arc};
 use salsa::{self, Database};
 use ra_db::{LocationIntener, BaseDatabase};
-use hir::{self, DefId, DefLoc};
+use hir::{self, DefId, DefLoc, MacroCallId, MacroCallLoc};
 
 use crate::{
     symbol_index,
@@ -16,12 +16,14 @@ pub(crate) struct RootDatabase {
 #[derive(Default)]
 struct IdMaps {
     defs: LocationIntener<DefLoc, DefId>,
+    macros: LocationIntener<MacroCallLoc, MacroCallId>,
 }
 
 impl fmt::Debug for IdMaps {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("IdMaps")
             .field("n_defs", &self.defs.len())
+            .field("n_macros", &self.macros.len())
             .finish()
     }
 }
@@ -65,6 +67,12 @@ impl AsRef<LocationIntener<DefLoc, DefId>> for RootDatabase {
     }
 }
 
+impl AsRef<LocationIntener<MacroCallLoc, MacroCallId>> for RootDatabase {
+    fn as_ref(&self) -> &LocationIntener<MacroCallLoc, MacroCallId> {
+        &self.id_maps.macros
+    }
+}
+
 salsa::database_storage! {
     pub(crate) struct RootDatabaseStorage for RootDatabase {
         impl ra_db::FilesDatabase {
@@ -85,10 +93,17 @@ salsa::database_storage! {
             fn library_symbols() for symbol_index::LibrarySymbolsQuery;
         }
         impl hir::db::HirDatabase {
+            fn hir_source_file() for hir::db::HirSourceFileQuery;
+            fn macro_def() for hir::db::MacroDefQuery;
+            fn expand_macro_invocation() for hir::db::ExpandMacroInvocationQuery;
             fn module_tree() for hir::db::ModuleTreeQuery;
             fn fn_scopes() for hir::db::FnScopesQuery;
+            fn fn_signature() for hir::db::FnSignatureQuery;
+            fn body_hir() for hir::db::BodyHirQuery;
+            fn body_syntax_mapping() for hir::db::BodySyntaxMappingQuery;
             fn file_items() for hir::db::SourceFileItemsQuery;
             fn file_item() for hir::db::FileItemQuery;
+            fn source_root_crates() for hir::db::SourceRootCratesQuery;
             fn input_module_items() for hir::db::InputModuleItemsQuery;
             fn item_map() for hir::db::ItemMapQuery;
             fn submodules() for hir::db::SubmodulesQuery;
@@ -97,6 +112,14 @@ salsa::database_storage! {
             fn type_for_field() for hir::db::TypeForFieldQuery;
             fn struct_data() for hir::db::StructDataQuery;
             fn enum_data() for hir::db::EnumDataQuery;
+            fn enum_variant_data() for hir::db::EnumVariantDataQuery;
+            fn type_alias_data() for hir::db::TypeAliasDataQuery;
+            fn const_data() for hir::db::ConstDataQuery;
+            fn implements() for hir::db::ImplementsQuery;
+            fn generic_params() for hir::db::GenericParamsQuery;
+            fn trait_data() for hir::db::TraitDataQuery;
+            fn impls_in_module() for hir::db::ImplsInModuleQuery;
+            fn impls_in_crate() for hir::db::ImplsInCrateQuery;
         }
     }
 }
@@ -3,26 +3,27 @@ use ra_syntax::{
     SyntaxNodeRef, AstNode,
     ast, algo::find_covering_node,
 };
+use hir::source_binder;
 
 use crate::{
-    TextRange, FileRange,
+    TextRange, FileRange, Cancelable,
     db::RootDatabase,
 };
 
-pub(crate) fn extend_selection(db: &RootDatabase, frange: FileRange) -> TextRange {
+pub(crate) fn extend_selection(db: &RootDatabase, frange: FileRange) -> Cancelable<TextRange> {
     let source_file = db.source_file(frange.file_id);
     if let Some(macro_call) = find_macro_call(source_file.syntax(), frange.range) {
-        if let Some(exp) = crate::macros::expand(db, frange.file_id, macro_call) {
+        if let Some(exp) = source_binder::expand_macro_call(db, frange.file_id, macro_call)? {
             if let Some(dst_range) = exp.map_range_forward(frange.range) {
                 if let Some(dst_range) = ra_editor::extend_selection(exp.source_file(), dst_range) {
                     if let Some(src_range) = exp.map_range_back(dst_range) {
-                        return src_range;
+                        return Ok(src_range);
                     }
                 }
             }
         }
     }
-    ra_editor::extend_selection(&source_file, frange.range).unwrap_or(frange.range)
+    Ok(ra_editor::extend_selection(&source_file, frange.range).unwrap_or(frange.range))
 }
 
 fn find_macro_call(node: SyntaxNodeRef, range: TextRange) -> Option<ast::MacroCall> {
@@ -40,12 +41,20 @@ mod tests {
     fn extend_selection_inside_macros() {
         let (analysis, frange) = single_file_with_range(
             "
+            macro_rules! ctry {
+                ($expr:expr) => {
+                    match $expr {
+                        None => return Ok(None),
+                        Some(it) => it,
+                    }
+                };
+            }
             fn main() {
                 ctry!(foo(|x| <|>x<|>));
             }
         ",
         );
-        let r = analysis.extend_selection(frange);
-        assert_eq_dbg("[51; 56)", &r);
+        let r = analysis.extend_selection(frange).unwrap();
+        assert_eq_dbg("[295; 300)", &r);
     }
 }
@@ -26,6 +26,7 @@ use crate::{
     CrateId, db, Diagnostic, FileId, FilePosition, FileRange, FileSystemEdit,
     Query, ReferenceResolution, RootChange, SourceChange, SourceFileEdit,
     symbol_index::{LibrarySymbolsQuery, SymbolIndex, SymbolsDatabase},
+    type_diagnostics,
 };
 
 #[derive(Debug, Default)]
@@ -231,7 +232,7 @@ impl AnalysisImpl {
                 position.file_id,
                 name_ref.syntax(),
             )? {
-                let scope = fn_descr.scopes(&*self.db);
+                let scope = fn_descr.scopes(&*self.db)?;
                 // First try to resolve the symbol locally
                 if let Some(entry) = scope.resolve_local_name(name_ref) {
                     rr.add_resolution(
@@ -288,7 +289,7 @@ impl AnalysisImpl {
         let mut ret = vec![(position.file_id, binding.syntax().range())];
         ret.extend(
             descr
-                .scopes(&*self.db)
+                .scopes(&*self.db)?
                 .find_all_refs(binding)
                 .into_iter()
                 .map(|ref_desc| (position.file_id, ref_desc.range)),
@@ -316,7 +317,7 @@ impl AnalysisImpl {
                 position.file_id,
                 name_ref.syntax(),
             )?);
-            let scope = descr.scopes(db);
+            let scope = descr.scopes(db)?;
             let resolved = ctry!(scope.resolve_local_name(name_ref));
             let resolved = resolved.ptr().resolve(source_file);
             let binding = ctry!(find_node_at_offset::<ast::BindPat>(
@@ -401,6 +402,7 @@ impl AnalysisImpl {
                 res.push(diag)
             }
         };
+        res.extend(type_diagnostics::type_diagnostics(&*self.db, file_id)?);
         Ok(res)
     }
 
@@ -500,7 +502,14 @@ impl AnalysisImpl {
             parent_fn
         )?);
         let infer = function.infer(&*self.db)?;
-        Ok(infer.type_of_node(node).map(|t| t.to_string()))
+        let syntax_mapping = function.body_syntax_mapping(&*self.db)?;
+        if let Some(expr) = ast::Expr::cast(node).and_then(|e| syntax_mapping.node_expr(e)) {
+            return Ok(Some(infer[expr].to_string()));
+        }
+        if let Some(pat) = ast::Pat::cast(node).and_then(|p| syntax_mapping.node_pat(p)) {
+            return Ok(Some(infer[pat].to_string()));
+        }
+        Ok(None)
     }
 
     fn index_resolve(&self, name_ref: ast::NameRef) -> Cancelable<Vec<(FileId, FileSymbol)>> {
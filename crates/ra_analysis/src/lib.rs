@@ -18,7 +18,7 @@ pub mod mock_analysis;
 
 mod extend_selection;
 mod syntax_highlighting;
-mod macros;
+mod type_diagnostics;
 
 use std::{fmt, sync::Arc};
 
@@ -42,7 +42,7 @@ pub use hir::FnSignatureInfo;
 
 pub use ra_db::{
     Canceled, Cancelable, FilePosition, FileRange,
-    CrateGraph, CrateId, SourceRootId, FileId
+    CrateGraph, CfgOptions, CrateId, SourceRootId, FileId
 };
 
 #[derive(Default)]
@@ -280,7 +280,7 @@ impl Analysis {
     pub fn file_line_index(&self, file_id: FileId) -> Arc<LineIndex> {
         self.imp.file_line_index(file_id)
     }
-    pub fn extend_selection(&self, frange: FileRange) -> TextRange {
+    pub fn extend_selection(&self, frange: FileRange) -> Cancelable<TextRange> {
         extend_selection::extend_selection(&self.imp.db, frange)
     }
     pub fn matching_brace(&self, file: &SourceFileNode, offset: TextUnit) -> Option<TextUnit> {
@@ -4,7 +4,10 @@ use relative_path::RelativePathBuf;
 use test_utils::{extract_offset, extract_range, parse_fixture, CURSOR_MARKER};
 use ra_db::mock::FileMap;
 
-use crate::{Analysis, AnalysisChange, AnalysisHost, FileId, FilePosition, FileRange, SourceRootId};
+use crate::{
+    Analysis, AnalysisChange, AnalysisHost, CfgOptions, CrateGraph, FileId, FilePosition,
+    FileRange, SourceRootId,
+};
 
 /// Mock analysis is used in test to bootstrap an AnalysisHost/Analysis
 /// from a set of in-memory files.
@@ -87,12 +90,25 @@ impl MockAnalysis {
         let source_root = SourceRootId(0);
         let mut change = AnalysisChange::new();
         change.add_root(source_root, true);
+        let mut crate_graph = CrateGraph::default();
+        let mut root_crate = None;
+        let mut std_crate = None;
         for (path, contents) in self.files.into_iter() {
             assert!(path.starts_with('/'));
             let path = RelativePathBuf::from_path(&path[1..]).unwrap();
             let file_id = file_map.add(path.clone());
+            if path == "lib.rs" || path == "main.rs" {
+                root_crate = Some(crate_graph.add_crate_root(file_id, CfgOptions::default()));
+            } else if path == "std/lib.rs" {
+                // a stand-in for the standard library, which the crate depends on
+                std_crate = Some(crate_graph.add_crate_root(file_id, CfgOptions::default()));
+            }
             change.add_file(source_root, file_id, path, Arc::new(contents));
         }
+        if let (Some(root_crate), Some(std_crate)) = (root_crate, std_crate) {
+            crate_graph.add_dep(root_crate, "std".into(), std_crate);
+        }
+        change.set_crate_graph(crate_graph);
         // change.set_file_resolver(Arc::new(file_map));
         host.apply_change(change);
         host
@@ -1,6 +1,7 @@
 use ra_syntax::{ast, AstNode,};
 use ra_editor::HighlightedRange;
 use ra_db::SyntaxDatabase;
+use hir::source_binder;
 
 use crate::{
     db::RootDatabase,
@@ -15,7 +16,7 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<Hi
         .descendants()
         .filter_map(ast::MacroCall::cast)
     {
-        if let Some(exp) = crate::macros::expand(db, file_id, macro_call) {
+        if let Some(exp) = source_binder::expand_macro_call(db, file_id, macro_call)? {
             let mapped_ranges = ra_editor::highlight(exp.source_file())
                 .into_iter()
                 .filter_map(|r| {
@@ -34,13 +35,21 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<Hi
 
 #[cfg(test)]
 mod tests {
-    use crate::mock_analysis::single_file;
+    use crate::mock_analysis::{single_file, MockAnalysis};
     use test_utils::assert_eq_dbg;
 
     #[test]
     fn highlights_code_inside_macros() {
         let (analysis, file_id) = single_file(
             "
+            macro_rules! ctry {
+                ($expr:expr) => {
+                    match $expr {
+                        None => return Ok(None),
+                        Some(it) => it,
+                    }
+                };
+            }
             fn main() {
                 ctry!({ let x = 92; x});
             }
@@ -48,15 +57,46 @@ mod tests {
         );
         let highlights = analysis.highlight(file_id).unwrap();
         assert_eq_dbg(
-            r#"[HighlightedRange { range: [13; 15), tag: "keyword" },
-                HighlightedRange { range: [16; 20), tag: "function" },
-                HighlightedRange { range: [41; 46), tag: "macro" },
-                HighlightedRange { range: [49; 52), tag: "keyword" },
-                HighlightedRange { range: [57; 59), tag: "literal" },
-                HighlightedRange { range: [49; 52), tag: "keyword" },
-                HighlightedRange { range: [53; 54), tag: "function" },
-                HighlightedRange { range: [57; 59), tag: "literal" },
-                HighlightedRange { range: [61; 62), tag: "text" }]"#,
+            r#"[HighlightedRange { range: [13; 30), tag: "macro" },
+                HighlightedRange { range: [87; 92), tag: "keyword" },
+                HighlightedRange { range: [133; 139), tag: "keyword" },
+                HighlightedRange { range: [257; 259), tag: "keyword" },
+                HighlightedRange { range: [260; 264), tag: "function" },
+                HighlightedRange { range: [285; 290), tag: "macro" },
+                HighlightedRange { range: [293; 296), tag: "keyword" },
+                HighlightedRange { range: [301; 303), tag: "literal" },
+                HighlightedRange { range: [293; 296), tag: "keyword" },
+                HighlightedRange { range: [297; 298), tag: "function" },
+                HighlightedRange { range: [301; 303), tag: "literal" },
+                HighlightedRange { range: [305; 306), tag: "text" }]"#,
+            &highlights,
+        )
+    }
+
+    #[test]
+    fn highlights_calls_of_macros_from_parent_modules() {
+        let mock = MockAnalysis::with_files(
+            "
+            //- /main.rs
+            mod foo;
+            macro_rules! id {
+                ($expr:expr) => { $expr };
+            }
+            //- /foo.rs
+            fn f() -> u32 {
+                id!(92)
+            }
+            ",
+        );
+        let file_id = mock.id_of("/foo.rs");
+        let highlights = mock.analysis().highlight(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[HighlightedRange { range: [0; 2), tag: "keyword" },
+                HighlightedRange { range: [3; 4), tag: "function" },
+                HighlightedRange { range: [10; 13), tag: "text" },
+                HighlightedRange { range: [20; 23), tag: "macro" },
+                HighlightedRange { range: [24; 26), tag: "literal" },
+                HighlightedRange { range: [24; 26), tag: "literal" }]"#,
             &highlights,
         )
     }
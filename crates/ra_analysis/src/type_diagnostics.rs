@@ -0,0 +1,465 @@
+use ra_db::SyntaxDatabase;
+use ra_editor::{LocalEdit, Severity};
+use ra_syntax::{
+    ast::{self, ArgListOwner},
+    AstNode, SyntaxKind::*, SyntaxNodeRef, TextRange,
+};
+use ra_text_edit::TextEditBuilder;
+use hir::{
+    source_binder, Function, InferenceDiagnostic, Mutability, Ty,
+};
+
+use crate::{
+    db::RootDatabase,
+    Cancelable, Diagnostic, FileId, SourceChange,
+};
+
+/// The type errors found by type inference in the functions of `file_id`.
+pub(crate) fn type_diagnostics(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<Diagnostic>> {
+    let source_file = db.source_file(file_id);
+    let mut res = Vec::new();
+    for fn_def in source_file
+        .syntax()
+        .descendants()
+        .filter_map(ast::FnDef::cast)
+    {
+        if let Some(function) = source_binder::function_from_source(db, file_id, fn_def)? {
+            fn_diagnostics(db, file_id, fn_def, function, &mut res)?;
+        }
+    }
+    Ok(res)
+}
+
+fn fn_diagnostics(
+    db: &RootDatabase,
+    file_id: FileId,
+    fn_def: ast::FnDef,
+    function: Function,
+    acc: &mut Vec<Diagnostic>,
+) -> Cancelable<()> {
+    let source_file = db.source_file(file_id);
+    let infer = function.infer(db)?;
+    let syntax_mapping = function.body_syntax_mapping(db)?;
+    // we don't resolve items declared inside of functions yet, so we can't
+    // tell whether paths are unresolved in functions that have some
+    let has_local_items = fn_def.body().map_or(false, |body| {
+        body.syntax()
+            .descendants()
+            .any(|node| ast::ModuleItem::cast(node).is_some())
+    });
+    for diagnostic in infer.diagnostics() {
+        let expr = match diagnostic {
+            InferenceDiagnostic::MismatchedType { expr, .. }
+            | InferenceDiagnostic::UnresolvedPath { expr }
+            | InferenceDiagnostic::WrongArgCount { expr, .. }
+            | InferenceDiagnostic::NoSuchField { expr, .. } => *expr,
+        };
+        let node = match syntax_mapping.expr_syntax(expr) {
+            Some(ptr) => ptr.resolve(&source_file),
+            None => continue,
+        };
+        let node = node.borrowed();
+        let (range, message, fix) = match diagnostic {
+            InferenceDiagnostic::MismatchedType {
+                expected, actual, ..
+            } => {
+                let message = format!(
+                    "mismatched types: expected `{}`, found `{}`",
+                    expected, actual
+                );
+                let fix = mismatch_fix(db, &function, node, expected, actual)?;
+                (mismatch_range(node), message, fix)
+            }
+            InferenceDiagnostic::UnresolvedPath { .. } => {
+                if has_local_items {
+                    continue;
+                }
+                let message = format!("unresolved path `{}`", node.text());
+                (node.range(), message, None)
+            }
+            InferenceDiagnostic::WrongArgCount {
+                expected, found, ..
+            } => {
+                let arg_list = ast::CallExpr::cast(node)
+                    .and_then(|call| call.arg_list())
+                    .or_else(|| ast::MethodCallExpr::cast(node).and_then(|call| call.arg_list()));
+                let range = arg_list.map_or(node.range(), |it| it.syntax().range());
+                let message = format!(
+                    "expected {} argument{}, found {}",
+                    expected,
+                    if *expected == 1 { "" } else { "s" },
+                    found
+                );
+                (range, message, None)
+            }
+            InferenceDiagnostic::NoSuchField { field, .. } => {
+                // point at the field's name, in a field access or a struct
+                // literal
+                let name_ref = ast::FieldExpr::cast(node)
+                    .and_then(|e| e.name_ref())
+                    .or_else(|| node.parent().and_then(ast::NamedField::cast)?.name_ref());
+                let range = name_ref.map_or(node.range(), |it| it.syntax().range());
+                (range, format!("no such field `{}`", field), None)
+            }
+        };
+        acc.push(Diagnostic {
+            range,
+            message,
+            severity: Severity::Error,
+            fix: fix.map(|fix| SourceChange::from_local_edit(file_id, fix)),
+        });
+    }
+    Ok(())
+}
+
+/// Where to report a mismatch for `node`: for a block, that's its closing
+/// brace, which is more helpful than highlighting the whole block.
+fn mismatch_range(node: SyntaxNodeRef) -> TextRange {
+    if node.kind() == BLOCK {
+        if let Some(r_curly) = node.last_child().filter(|it| it.kind() == R_CURLY) {
+            return r_curly.range();
+        }
+    }
+    node.range()
+}
+
+/// Suggests adding `&` if a reference to the value is expected, or `.clone()`
+/// if the value is expected but we have a reference to it and it's `Clone`.
+fn mismatch_fix(
+    db: &RootDatabase,
+    function: &Function,
+    node: SyntaxNodeRef,
+    expected: &Ty,
+    actual: &Ty,
+) -> Cancelable<Option<LocalEdit>> {
+    let text = node.text().to_string();
+    let text = if needs_parens(node) {
+        format!("({})", text)
+    } else {
+        text
+    };
+    let (label, replacement) = match (expected, actual) {
+        (Ty::Ref(inner, mutability), _) if **inner == *actual => match mutability {
+            Mutability::Shared => ("add `&`", format!("&{}", text)),
+            Mutability::Mut => ("add `&mut`", format!("&mut {}", text)),
+        },
+        (_, Ty::Ref(inner, _))
+            if **inner == *expected && expected.is_clone(db, &function.module(db)?)? =>
+        {
+            ("add `.clone()`", format!("{}.clone()", text))
+        }
+        _ => return Ok(None),
+    };
+    let mut edit = TextEditBuilder::new();
+    edit.replace(node.range(), replacement);
+    Ok(Some(LocalEdit {
+        label: label.to_string(),
+        edit: edit.finish(),
+        cursor_position: None,
+    }))
+}
+
+/// Whether `node` needs to be wrapped in parentheses before we can take a
+/// reference to it or call a method on it.
+fn needs_parens(node: SyntaxNodeRef) -> bool {
+    match node.kind() {
+        BIN_EXPR | CAST_EXPR | RANGE_EXPR | LAMBDA_EXPR | PREFIX_EXPR | REF_EXPR
+        | RETURN_EXPR | BREAK_EXPR => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::{single_file, MockAnalysis};
+    use test_utils::assert_eq_dbg;
+
+    #[test]
+    fn reports_mismatched_types() {
+        let mock = MockAnalysis::with_files(
+            "
+            //- /main.rs
+            fn takes_ref(x: &u32) {}
+            fn test(a: u32, b: &u32) -> u32 {
+                let c: bool = 1u8;
+                takes_ref(a);
+                b
+            }
+            struct S;
+            fn not_clone(s: &S) -> S {
+                s
+            }
+
+            //- /std/lib.rs
+            pub mod prelude { pub mod v1 { pub use crate::clone::Clone; } }
+            pub mod clone {
+                pub trait Clone {}
+                impl Clone for u32 {}
+            }
+            ",
+        );
+        let file_id = mock.id_of("/main.rs");
+        let diagnostics = mock.analysis().diagnostics(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[Diagnostic {
+                message: "mismatched types: expected `bool`, found `u8`",
+                range: [77; 80),
+                fix: None,
+                severity: Error },
+            Diagnostic {
+                message: "mismatched types: expected `&u32`, found `u32`",
+                range: [96; 97),
+                fix: Some(SourceChange {
+                    label: "add `&`",
+                    source_file_edits: [SourceFileEdit {
+                        file_id: FileId(1),
+                        edit: TextEdit { atoms: [AtomTextEdit {
+                            delete: [96; 97), insert: "&a" }] } }],
+                    file_system_edits: [],
+                    cursor_position: None }),
+                severity: Error },
+            Diagnostic {
+                message: "mismatched types: expected `u32`, found `&u32`",
+                range: [104; 105),
+                fix: Some(SourceChange {
+                    label: "add `.clone()`",
+                    source_file_edits: [SourceFileEdit {
+                        file_id: FileId(1),
+                        edit: TextEdit { atoms: [AtomTextEdit {
+                            delete: [104; 105), insert: "b.clone()" }] } }],
+                    file_system_edits: [],
+                    cursor_position: None }),
+                severity: Error },
+            Diagnostic {
+                message: "mismatched types: expected `S`, found `&S`",
+                range: [149; 150),
+                fix: None,
+                severity: Error }]"#,
+            &diagnostics,
+        );
+    }
+
+    #[test]
+    fn reports_missing_tail_expression() {
+        let (analysis, file_id) = single_file(
+            "
+            fn test() -> u32 {
+                1;
+            }
+            fn unexpanded_macro() -> u32 {
+                unimplemented!();
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[Diagnostic {
+                message: "mismatched types: expected `u32`, found `()`",
+                range: [63; 64),
+                fix: None,
+                severity: Error }]"#,
+            &diagnostics,
+        );
+    }
+
+    #[test]
+    fn reports_unresolved_paths() {
+        let mock = MockAnalysis::with_files(
+            "
+            //- /main.rs
+            mod foo { pub fn bar() {} }
+            enum E { A }
+            fn test<T>() {
+                let x = unknown;
+                foo::baz();
+                E::B;
+                u32::max_value();
+                T::default();
+                foo::bar();
+            }
+            fn local_items() {
+                fn local() {}
+                local();
+            }
+
+            //- /std/lib.rs
+            pub mod prelude { pub mod v1 {} }
+            ",
+        );
+        let file_id = mock.id_of("/main.rs");
+        let diagnostics = mock.analysis().diagnostics(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[Diagnostic {
+                message: "unresolved path `unknown`",
+                range: [68; 75),
+                fix: None,
+                severity: Error },
+            Diagnostic {
+                message: "unresolved path `foo::baz`",
+                range: [81; 89),
+                fix: None,
+                severity: Error }]"#,
+            &diagnostics,
+        );
+    }
+
+    #[test]
+    fn no_unresolved_path_diagnostics_for_incomplete_scopes() {
+        let mock = MockAnalysis::with_files(
+            "
+            //- /main.rs
+            mod foo {
+                pub use self::inner::*;
+                pub mod inner { pub fn baz() {} }
+            }
+            #[derive(Clone)]
+            enum E { A }
+            impl E { const C: u32 = 1; }
+            fn test(e: E) {
+                foo::baz();
+                E::C;
+                E::clone(&e);
+                from_prelude();
+            }
+
+            //- /std/lib.rs
+            pub mod prelude { pub mod v1 { pub use crate::other::*; } }
+            pub mod other { pub fn from_prelude() {} }
+            ",
+        );
+        let file_id = mock.id_of("/main.rs");
+        let diagnostics = mock.analysis().diagnostics(file_id).unwrap();
+        assert_eq_dbg(r#"[]"#, &diagnostics);
+    }
+
+    #[test]
+    fn no_unresolved_path_diagnostics_for_valid_code() {
+        let (analysis, file_id) = single_file(
+            "
+            const C: u32 = 1;
+            static S: u32 = 2;
+            enum E { A(u32), B }
+            struct P { x: u32 }
+            impl P {
+                fn new() -> P { Self::make(C) }
+                fn make(x: u32) -> P { P { x } }
+            }
+            fn test(e: E, arr: [u32; 2]) -> u32 {
+                for i in arr.iter() {}
+                if let E::A(a) = e {}
+                match e {
+                    E::A(b) if b == S => b,
+                    _ => crate::helper(),
+                }
+            }
+            fn helper() -> u32 {
+                let f = |c: u32| c;
+                f(P::new().x)
+            }
+            mod globs {
+                use super::*;
+                fn test() -> u32 { helper() }
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq_dbg(r#"[]"#, &diagnostics);
+    }
+
+    #[test]
+    fn no_unresolved_path_diagnostics_without_prelude() {
+        let (analysis, file_id) = single_file(
+            "
+            fn f() -> Vec<u32> { Vec::new() }
+            fn g() { drop(1); }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq_dbg(r#"[]"#, &diagnostics);
+    }
+
+    #[test]
+    fn reports_wrong_arg_count() {
+        let (analysis, file_id) = single_file(
+            "
+            struct S;
+            impl S { fn method(&self, x: u32) {} }
+            fn one(x: u32) {}
+            fn test(s: S) {
+                one(1, 2);
+                s.method();
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[Diagnostic {
+                message: "expected 1 argument, found 2",
+                range: [151; 157),
+                fix: None,
+                severity: Error },
+            Diagnostic {
+                message: "expected 1 argument, found 0",
+                range: [183; 185),
+                fix: None,
+                severity: Error }]"#,
+            &diagnostics,
+        );
+    }
+
+    #[test]
+    fn counts_self_param_in_path_calls_of_methods() {
+        let (analysis, file_id) = single_file(
+            "
+            struct S;
+            impl S { fn a(&self, b: u32) {} }
+            fn f(s: S) {
+                S::a(&s, 1);
+                s.a(1);
+                S::a(1);
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[Diagnostic {
+                message: "expected 2 arguments, found 1",
+                range: [167; 170),
+                fix: None,
+                severity: Error },
+            Diagnostic {
+                message: "mismatched types: expected `&S`, found `i32`",
+                range: [168; 169),
+                fix: None,
+                severity: Error }]"#,
+            &diagnostics,
+        );
+    }
+
+    #[test]
+    fn reports_unknown_fields() {
+        let (analysis, file_id) = single_file(
+            "
+            struct S { a: u32 }
+            fn test(s: &S) {
+                s.b;
+                S { a: 1, c: 2 };
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[Diagnostic {
+                message: "no such field `b`",
+                range: [80; 81),
+                fix: None,
+                severity: Error },
+            Diagnostic {
+                message: "no such field `c`",
+                range: [109; 110),
+                fix: None,
+                severity: Error }]"#,
+            &diagnostics,
+        );
+    }
+}
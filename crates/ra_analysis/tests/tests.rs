@@ -3,7 +3,7 @@ use test_utils::assert_eq_dbg;
 
 use ra_analysis::{
     mock_analysis::{analysis_and_position, single_file, single_file_with_position, MockAnalysis},
-    AnalysisChange, CrateGraph, FileId, FnSignatureInfo,
+    AnalysisChange, CfgOptions, CrateGraph, FileId, FnSignatureInfo,
 };
 
 fn get_signature(text: &str) -> (FnSignatureInfo, Option<usize>) {
@@ -95,6 +95,42 @@ fn test_unresolved_module_diagnostic_no_diag_for_inline_mode() {
     assert_eq_dbg(r#"[]"#, &diagnostics);
 }
 
+#[test]
+fn test_resolve_module_with_path_attr() {
+    let mock = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        #[path = \"unix/imp.rs\"]
+        mod imp;
+        #[path = \"platform\"]
+        mod sys {
+            mod inner {
+                #[path = \"deep.rs\"]
+                mod deep;
+            }
+        }
+        //- /unix/imp.rs
+        mod helper;
+        //- /unix/helper.rs
+        // empty
+        //- /platform/inner/deep.rs
+        // empty
+    ",
+    );
+    let lib_file = mock.id_of("/lib.rs");
+    let imp_file = mock.id_of("/unix/imp.rs");
+    let helper_file = mock.id_of("/unix/helper.rs");
+    let deep_file = mock.id_of("/platform/inner/deep.rs");
+    let analysis = mock.analysis();
+    assert_eq_dbg(r#"[]"#, &analysis.diagnostics(lib_file).unwrap());
+    assert_eq_dbg(r#"[]"#, &analysis.diagnostics(imp_file).unwrap());
+
+    let lib_crate = analysis.crate_for(lib_file).unwrap();
+    assert_eq!(lib_crate.len(), 1);
+    assert_eq!(analysis.crate_for(helper_file).unwrap(), lib_crate);
+    assert_eq!(analysis.crate_for(deep_file).unwrap(), lib_crate);
+}
+
 #[test]
 fn test_resolve_parent_module() {
     let (analysis, pos) = analysis_and_position(
@@ -135,19 +171,19 @@ fn test_resolve_parent_module_for_inline() {
 fn test_resolve_crate_root() {
     let mock = MockAnalysis::with_files(
         "
-        //- /lib.rs
+        //- /bar.rs
         mod foo;
         //- /foo.rs
         // emtpy <|>
     ",
     );
-    let root_file = mock.id_of("/lib.rs");
+    let root_file = mock.id_of("/bar.rs");
     let mod_file = mock.id_of("/foo.rs");
     let mut host = mock.analysis_host();
     assert!(host.analysis().crate_for(mod_file).unwrap().is_empty());
 
     let mut crate_graph = CrateGraph::default();
-    let crate_id = crate_graph.add_crate_root(root_file);
+    let crate_id = crate_graph.add_crate_root(root_file, CfgOptions::default());
     let mut change = AnalysisChange::new();
     change.set_crate_graph(crate_graph);
     host.apply_change(change);
@@ -39,7 +39,7 @@ pub struct SourceRoot {
 
 /// `CrateGraph` is a bit of information which turns a set of text files into a
 /// number of Rust crates. Each Crate is the `FileId` of it's root module, the
-/// set of cfg flags (not yet implemented) and the set of dependencies. Note
+/// set of cfg flags and the set of dependencies. Note
 /// that, due to cfg's, there might be several crates for a single `FileId`! As
 /// in the rust-lang proper, a crate does not have a name. Instead, names are
 /// specified on dependency edges. That is, a crate might be known under
@@ -59,13 +59,15 @@ pub struct CrateId(pub u32);
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CrateData {
     file_id: FileId,
+    cfg_options: CfgOptions,
     dependencies: Vec<Dependency>,
 }
 
 impl CrateData {
-    fn new(file_id: FileId) -> CrateData {
+    fn new(file_id: FileId, cfg_options: CfgOptions) -> CrateData {
         CrateData {
             file_id,
+            cfg_options,
             dependencies: Vec::new(),
         }
     }
@@ -87,10 +89,33 @@ impl Dependency {
     }
 }
 
+/// The set of cfg flags a crate is compiled with: atoms like `test`, and
+/// key-value pairs like `feature = "serde"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CfgOptions {
+    atoms: FxHashSet<SmolStr>,
+    key_values: FxHashSet<(SmolStr, SmolStr)>,
+}
+
+impl CfgOptions {
+    pub fn insert_atom(&mut self, name: SmolStr) {
+        self.atoms.insert(name);
+    }
+    pub fn insert_key_value(&mut self, key: SmolStr, value: SmolStr) {
+        self.key_values.insert((key, value));
+    }
+    pub fn is_atom_enabled(&self, name: &str) -> bool {
+        self.atoms.contains(&SmolStr::from(name))
+    }
+    pub fn is_key_value_enabled(&self, key: &str, value: &str) -> bool {
+        self.key_values.contains(&(key.into(), value.into()))
+    }
+}
+
 impl CrateGraph {
-    pub fn add_crate_root(&mut self, file_id: FileId) -> CrateId {
+    pub fn add_crate_root(&mut self, file_id: FileId, cfg_options: CfgOptions) -> CrateId {
         let crate_id = CrateId(self.arena.len() as u32);
-        let prev = self.arena.insert(crate_id, CrateData::new(file_id));
+        let prev = self.arena.insert(crate_id, CrateData::new(file_id, cfg_options));
         assert!(prev.is_none());
         crate_id
     }
@@ -104,9 +129,15 @@ impl CrateGraph {
     pub fn is_empty(&self) -> bool {
         self.arena.is_empty()
     }
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = CrateId> + 'a {
+        self.arena.keys().cloned()
+    }
     pub fn crate_root(&self, crate_id: CrateId) -> FileId {
         self.arena[&crate_id].file_id
     }
+    pub fn cfg_options(&self, crate_id: CrateId) -> &CfgOptions {
+        &self.arena[&crate_id].cfg_options
+    }
     pub fn crate_id_for_crate_root(&self, file_id: FileId) -> Option<CrateId> {
         let (&crate_id, _) = self
             .arena
@@ -141,15 +172,15 @@ impl CrateGraph {
 
 #[cfg(test)]
 mod tests {
-    use super::{CrateGraph, FxHashMap, FileId, SmolStr};
+    use super::{CrateGraph, CfgOptions, FxHashMap, FileId, SmolStr};
 
     #[test]
     #[should_panic]
     fn it_should_painc_because_of_cycle_dependencies() {
         let mut graph = CrateGraph::default();
-        let crate1 = graph.add_crate_root(FileId(1u32));
-        let crate2 = graph.add_crate_root(FileId(2u32));
-        let crate3 = graph.add_crate_root(FileId(3u32));
+        let crate1 = graph.add_crate_root(FileId(1u32), CfgOptions::default());
+        let crate2 = graph.add_crate_root(FileId(2u32), CfgOptions::default());
+        let crate3 = graph.add_crate_root(FileId(3u32), CfgOptions::default());
         graph.add_dep(crate1, SmolStr::new("crate2"), crate2);
         graph.add_dep(crate2, SmolStr::new("crate3"), crate3);
         graph.add_dep(crate3, SmolStr::new("crate1"), crate1);
@@ -160,12 +191,24 @@ mod tests {
         let mut graph = CrateGraph {
             arena: FxHashMap::default(),
         };
-        let crate1 = graph.add_crate_root(FileId(1u32));
-        let crate2 = graph.add_crate_root(FileId(2u32));
-        let crate3 = graph.add_crate_root(FileId(3u32));
+        let crate1 = graph.add_crate_root(FileId(1u32), CfgOptions::default());
+        let crate2 = graph.add_crate_root(FileId(2u32), CfgOptions::default());
+        let crate3 = graph.add_crate_root(FileId(3u32), CfgOptions::default());
         graph.add_dep(crate1, SmolStr::new("crate2"), crate2);
         graph.add_dep(crate2, SmolStr::new("crate3"), crate3);
     }
+
+    #[test]
+    fn cfg_options_lookup() {
+        let mut cfg = CfgOptions::default();
+        cfg.insert_atom("unix".into());
+        cfg.insert_key_value("target_os".into(), "linux".into());
+        assert!(cfg.is_atom_enabled("unix"));
+        assert!(!cfg.is_atom_enabled("windows"));
+        assert!(cfg.is_key_value_enabled("target_os", "linux"));
+        assert!(!cfg.is_key_value_enabled("target_os", "macos"));
+        assert!(!cfg.is_key_value_enabled("linux", "target_os"));
+    }
 }
 
 salsa::query_group! {
@@ -1,11 +1,12 @@
 use std::sync::Arc;
 
-use ra_syntax::ast::{self, NameOwner, StructFlavor};
+use ra_syntax::ast::{self, AstNode, NameOwner, StructFlavor};
 
 use crate::{
-    DefId, Cancelable, Name, AsName,
+    DefId, DefLoc, DefKind, Cancelable, Name, AsName, SourceItemId,
     db::HirDatabase,
     type_ref::TypeRef,
+    generics::GenericParams,
 };
 
 pub struct Struct {
@@ -32,6 +33,10 @@ impl Struct {
     pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
         Ok(db.struct_data(self.def_id)?.name.clone())
     }
+
+    pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
+        db.generic_params(self.def_id)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -74,26 +79,53 @@ impl Enum {
         Ok(db.enum_data(self.def_id)?.name.clone())
     }
 
-    pub fn variants(&self, db: &impl HirDatabase) -> Cancelable<Vec<(Name, Arc<VariantData>)>> {
-        Ok(db.enum_data(self.def_id)?.variants.clone())
+    pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
+        db.generic_params(self.def_id)
+    }
+
+    pub fn variants(&self, db: &impl HirDatabase) -> Cancelable<Vec<(Name, EnumVariant)>> {
+        Ok(db
+            .enum_data(self.def_id)?
+            .variants
+            .iter()
+            .map(|(name, def_id)| (name.clone(), EnumVariant::new(*def_id)))
+            .collect())
+    }
+
+    pub fn variant(&self, db: &impl HirDatabase, name: &Name) -> Cancelable<Option<EnumVariant>> {
+        Ok(db
+            .enum_data(self.def_id)?
+            .variants
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, def_id)| EnumVariant::new(*def_id)))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EnumData {
     name: Option<Name>,
-    variants: Vec<(Name, Arc<VariantData>)>,
+    variants: Vec<(Name, DefId)>,
 }
 
 impl EnumData {
-    pub(crate) fn new(enum_def: ast::EnumDef) -> Self {
+    pub(crate) fn new(db: &impl HirDatabase, def_loc: &DefLoc, enum_def: ast::EnumDef) -> Self {
         let name = enum_def.name().map(|n| n.as_name());
+        let file_items = db.file_items(def_loc.source_item_id.file_id);
         let variants = if let Some(evl) = enum_def.variant_list() {
             evl.variants()
                 .map(|v| {
+                    let variant_loc = DefLoc {
+                        kind: DefKind::EnumVariant,
+                        source_item_id: SourceItemId {
+                            file_id: def_loc.source_item_id.file_id,
+                            item_id: Some(file_items.id_of_unchecked(v.syntax())),
+                        },
+                        ..def_loc.clone()
+                    };
                     (
                         v.name().map(|n| n.as_name()).unwrap_or_else(Name::missing),
-                        Arc::new(VariantData::new(v.flavor())),
+                        variant_loc.id(db),
                     )
                 })
                 .collect()
@@ -104,6 +136,52 @@ impl EnumData {
     }
 }
 
+pub struct EnumVariant {
+    def_id: DefId,
+}
+
+impl EnumVariant {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        EnumVariant { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn parent_enum(&self, db: &impl HirDatabase) -> Cancelable<Enum> {
+        Ok(Enum::new(db.enum_variant_data(self.def_id)?.parent_enum))
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.enum_variant_data(self.def_id)?.name.clone())
+    }
+
+    pub fn variant_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<VariantData>> {
+        Ok(db.enum_variant_data(self.def_id)?.variant_data.clone())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumVariantData {
+    name: Option<Name>,
+    variant_data: Arc<VariantData>,
+    parent_enum: DefId,
+}
+
+impl EnumVariantData {
+    pub(crate) fn new(variant_def: ast::EnumVariant, parent_enum: DefId) -> EnumVariantData {
+        let name = variant_def.name().map(|n| n.as_name());
+        let variant_data = VariantData::new(variant_def.flavor());
+        let variant_data = Arc::new(variant_data);
+        EnumVariantData {
+            name,
+            variant_data,
+            parent_enum,
+        }
+    }
+}
+
 /// A single field of an enum variant or struct
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StructField {
@@ -0,0 +1,115 @@
+//! Evaluation of `#[cfg(...)]` attributes against the `CfgOptions` of a
+//! crate.
+
+use ra_db::CfgOptions;
+use ra_macros::tt;
+use ra_syntax::{SmolStr, SyntaxNodeRef, ast::{self, AstNode}};
+
+/// A parsed `cfg` predicate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CfgExpr {
+    Invalid,
+    Atom(SmolStr),
+    KeyValue { key: SmolStr, value: SmolStr },
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Collects the predicates of all `#[cfg]` attributes of an item, all of
+    /// which must hold. Returns `None` if there are no such attributes.
+    pub(crate) fn from_item(item: SyntaxNodeRef) -> Option<CfgExpr> {
+        let mut preds = item
+            .children()
+            .filter_map(ast::Attr::cast)
+            .filter_map(|attr| {
+                let (name, args) = attr.as_call()?;
+                if name != "cfg" {
+                    return None;
+                }
+                Some(parse_cfg(args))
+            })
+            .collect::<Vec<_>>();
+        match preds.len() {
+            0 => None,
+            1 => preds.pop(),
+            _ => Some(CfgExpr::All(preds)),
+        }
+    }
+
+    /// Evaluates the predicate. Returns `None` if it is malformed.
+    pub(crate) fn check(&self, options: &CfgOptions) -> Option<bool> {
+        let res = match self {
+            CfgExpr::Invalid => return None,
+            CfgExpr::Atom(name) => options.is_atom_enabled(name),
+            CfgExpr::KeyValue { key, value } => options.is_key_value_enabled(key, value),
+            CfgExpr::All(preds) => preds
+                .iter()
+                .try_fold(true, |acc, pred| Some(pred.check(options)? && acc))?,
+            CfgExpr::Any(preds) => preds
+                .iter()
+                .try_fold(false, |acc, pred| Some(pred.check(options)? || acc))?,
+            CfgExpr::Not(pred) => !pred.check(options)?,
+        };
+        Some(res)
+    }
+}
+
+/// Whether `item` is compiled in with `options`. Items with malformed `cfg`
+/// attributes are considered enabled.
+pub(crate) fn is_enabled(options: &CfgOptions, item: SyntaxNodeRef) -> bool {
+    match CfgExpr::from_item(item) {
+        Some(cfg) => cfg.check(options) != Some(false),
+        None => true,
+    }
+}
+
+fn parse_cfg(args: ast::TokenTree) -> CfgExpr {
+    let subtree = match ra_macros::ast_to_token_tree(args) {
+        Some((it, _)) => it,
+        None => return CfgExpr::Invalid,
+    };
+    let mut preds = parse_list(&subtree.token_trees);
+    if preds.len() == 1 {
+        preds.pop().unwrap()
+    } else {
+        CfgExpr::Invalid
+    }
+}
+
+/// Parses a comma separated list of predicates.
+fn parse_list(tokens: &[tt::TokenTree]) -> Vec<CfgExpr> {
+    tokens
+        .split(|it| match it {
+            tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) => punct.char == ',',
+            _ => false,
+        })
+        .filter(|it| !it.is_empty())
+        .map(parse_pred)
+        .collect()
+}
+
+fn parse_pred(tokens: &[tt::TokenTree]) -> CfgExpr {
+    use ra_macros::tt::{TokenTree::{Leaf, Subtree}, Leaf::{Ident, Punct, Literal}};
+
+    match tokens {
+        [Leaf(Ident(name))] => CfgExpr::Atom(name.text.clone()),
+        [Leaf(Ident(key)), Leaf(Punct(eq)), Leaf(Literal(value))] if eq.char == '=' => {
+            CfgExpr::KeyValue {
+                key: key.text.clone(),
+                value: value.text.trim_matches('"').into(),
+            }
+        }
+        [Leaf(Ident(op)), Subtree(args)] if args.delimiter == tt::Delimiter::Parenthesis => {
+            let mut preds = parse_list(&args.token_trees);
+            match op.text.as_str() {
+                "all" => CfgExpr::All(preds),
+                "any" => CfgExpr::Any(preds),
+                "not" if preds.len() == 1 => CfgExpr::Not(Box::new(preds.pop().unwrap())),
+                _ => CfgExpr::Invalid,
+            }
+        }
+        _ => CfgExpr::Invalid,
+    }
+}
@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use ra_syntax::ast::{self, NameOwner, BinOp, LiteralFlavor};
+
+use crate::{
+    Def, DefId, Cancelable, Name, AsName, Module, Path,
+    db::HirDatabase,
+    type_ref::TypeRef,
+};
+
+/// A constant item, e.g. `const MAX: usize = 92;`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Const {
+    def_id: DefId,
+}
+
+impl Const {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        Const { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn const_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<ConstData>> {
+        db.const_data(self.def_id)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.const_data(self.def_id)?.name.clone())
+    }
+}
+
+/// A static item, e.g. `static COUNTER: AtomicUsize = ...;`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Static {
+    def_id: DefId,
+}
+
+impl Static {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        Static { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn const_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<ConstData>> {
+        db.const_data(self.def_id)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.const_data(self.def_id)?.name.clone())
+    }
+}
+
+/// The signature of a const or static: its name, declared type and value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstData {
+    name: Option<Name>,
+    type_ref: TypeRef,
+    value: ConstExpr,
+}
+
+impl ConstData {
+    pub(crate) fn from_const(const_def: ast::ConstDef) -> ConstData {
+        let name = const_def.name().map(|n| n.as_name());
+        let type_ref = TypeRef::from_ast_opt(const_def.type_ref());
+        let value = ConstExpr::from_ast_opt(const_def.expr());
+        ConstData {
+            name,
+            type_ref,
+            value,
+        }
+    }
+
+    pub(crate) fn from_static(static_def: ast::StaticDef) -> ConstData {
+        let name = static_def.name().map(|n| n.as_name());
+        let type_ref = TypeRef::from_ast_opt(static_def.type_ref());
+        let value = ConstExpr::from_ast_opt(static_def.expr());
+        ConstData {
+            name,
+            type_ref,
+            value,
+        }
+    }
+
+    pub fn name(&self) -> Option<&Name> {
+        self.name.as_ref()
+    }
+
+    pub fn type_ref(&self) -> &TypeRef {
+        &self.type_ref
+    }
+
+    /// The initializer of the const or static.
+    pub fn value(&self) -> &ConstExpr {
+        &self.value
+    }
+}
+
+/// Constants referring to other constants are evaluated recursively; this
+/// limits how deep. Cycles like `const A: usize = B; const B: usize = A;` are
+/// detected separately.
+const CONST_EVAL_RECURSION_LIMIT: usize = 32;
+
+/// An expression that is evaluated at compile time, like the length of an
+/// array type or a const initializer. Only the few forms we know how to
+/// evaluate are lowered, everything else is `Missing`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConstExpr {
+    Missing,
+    /// An integer literal.
+    Literal(u64),
+    /// A path, which should resolve to a `const` item.
+    Path(Path),
+    BinaryOp {
+        lhs: Box<ConstExpr>,
+        op: BinOp,
+        rhs: Box<ConstExpr>,
+    },
+}
+
+impl ConstExpr {
+    pub(crate) fn from_ast(expr: ast::Expr) -> ConstExpr {
+        match expr {
+            ast::Expr::Literal(lit) => int_literal_value(lit)
+                .map(ConstExpr::Literal)
+                .unwrap_or(ConstExpr::Missing),
+            ast::Expr::PathExpr(e) => e
+                .path()
+                .and_then(Path::from_ast)
+                .map(ConstExpr::Path)
+                .unwrap_or(ConstExpr::Missing),
+            ast::Expr::ParenExpr(e) => ConstExpr::from_ast_opt(e.expr()),
+            ast::Expr::BinExpr(e) => match e.op() {
+                Some(op) => ConstExpr::BinaryOp {
+                    lhs: Box::new(ConstExpr::from_ast_opt(e.lhs())),
+                    op,
+                    rhs: Box::new(ConstExpr::from_ast_opt(e.rhs())),
+                },
+                None => ConstExpr::Missing,
+            },
+            _ => ConstExpr::Missing,
+        }
+    }
+
+    pub(crate) fn from_ast_opt(expr: Option<ast::Expr>) -> ConstExpr {
+        expr.map(ConstExpr::from_ast)
+            .unwrap_or(ConstExpr::Missing)
+    }
+
+    /// Evaluates the expression to an unsigned integer, resolving paths in
+    /// `module`. Returns `None` if we can't evaluate it, e.g. because it
+    /// overflows or uses something we don't support.
+    pub fn eval(&self, db: &impl HirDatabase, module: &Module) -> Cancelable<Option<u64>> {
+        let mut evaluator = Evaluator {
+            db,
+            stack: Vec::new(),
+            values: FxHashMap::default(),
+        };
+        evaluator.eval(self, module)
+    }
+}
+
+struct Evaluator<'a, DB> {
+    db: &'a DB,
+    /// The consts being evaluated. A const whose value depends on itself has
+    /// no value.
+    stack: Vec<DefId>,
+    /// The values of the consts evaluated so far, so that e.g. `A + A` only
+    /// evaluates `A` once.
+    values: FxHashMap<DefId, Option<u64>>,
+}
+
+impl<'a, DB> Evaluator<'a, DB>
+where
+    DB: HirDatabase,
+{
+    fn eval(&mut self, expr: &ConstExpr, module: &Module) -> Cancelable<Option<u64>> {
+        let value = match expr {
+            ConstExpr::Missing => None,
+            ConstExpr::Literal(value) => Some(*value),
+            ConstExpr::Path(path) => {
+                let def_id = match module.resolve_path(self.db, path)?.take_values() {
+                    Some(def_id) => def_id,
+                    None => return Ok(None),
+                };
+                match def_id.resolve(self.db)? {
+                    Def::Const(c) => self.eval_const(c)?,
+                    _ => None,
+                }
+            }
+            ConstExpr::BinaryOp { lhs, op, rhs } => {
+                let lhs = self.eval(lhs, module)?;
+                let rhs = self.eval(rhs, module)?;
+                match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) => eval_bin_op(lhs, *op, rhs),
+                    _ => None,
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    fn eval_const(&mut self, c: Const) -> Cancelable<Option<u64>> {
+        let def_id = c.def_id();
+        if let Some(&value) = self.values.get(&def_id) {
+            return Ok(value);
+        }
+        if self.stack.len() >= CONST_EVAL_RECURSION_LIMIT || self.stack.contains(&def_id) {
+            return Ok(None);
+        }
+        let data = c.const_data(self.db)?;
+        let module = def_id.module(self.db)?;
+        self.stack.push(def_id);
+        let value = self.eval(&data.value, &module);
+        self.stack.pop();
+        let value = value?;
+        self.values.insert(def_id, value);
+        Ok(value)
+    }
+}
+
+fn eval_bin_op(lhs: u64, op: BinOp, rhs: u64) -> Option<u64> {
+    match op {
+        BinOp::Addition => lhs.checked_add(rhs),
+        BinOp::Subtraction => lhs.checked_sub(rhs),
+        BinOp::Multiplication => lhs.checked_mul(rhs),
+        BinOp::Division => lhs.checked_div(rhs),
+        BinOp::Remainder => lhs.checked_rem(rhs),
+        BinOp::LeftShift if rhs < 64 => lhs.checked_shl(rhs as u32),
+        BinOp::RightShift if rhs < 64 => lhs.checked_shr(rhs as u32),
+        BinOp::BitwiseXor => Some(lhs ^ rhs),
+        BinOp::BitwiseOr => Some(lhs | rhs),
+        BinOp::BitwiseAnd => Some(lhs & rhs),
+        _ => None,
+    }
+}
+
+/// The value of an integer literal like `92`, `0xff_u8` or `1_000usize`.
+fn int_literal_value(lit: ast::Literal) -> Option<u64> {
+    let suffix = match lit.flavor()? {
+        LiteralFlavor::IntNumber { suffix } => suffix,
+        _ => return None,
+    };
+    let text = lit.token()?.leaf_text()?;
+    let text = &text[..text.len() - suffix.map_or(0, |s| s.len())];
+    let text = text.replace('_', "");
+    let (digits, radix) = match text.get(..2) {
+        Some("0x") => (&text[2..], 16),
+        Some("0o") => (&text[2..], 8),
+        Some("0b") => (&text[2..], 2),
+        _ => (&text[..], 10),
+    };
+    u64::from_str_radix(digits, radix).ok()
+}
@@ -1,29 +1,66 @@
 use std::sync::Arc;
 
-use ra_syntax::SyntaxNode;
-use ra_db::{SourceRootId, LocationIntener, SyntaxDatabase, FileId, Cancelable};
+use ra_syntax::{SyntaxNode, SourceFileNode};
+use ra_db::{SourceRootId, CrateId, LocationIntener, SyntaxDatabase, Cancelable};
 
 use crate::{
-    DefLoc, DefId, Name,
+    DefLoc, DefId, Name, HirFileId, MacroCallLoc, MacroCallId,
     SourceFileItems, SourceItemId,
     query_definitions,
-    FnScopes,
+    FnScopes, FnSignature,
     module::{ModuleId, ModuleTree, ModuleSource,
     nameres::{ItemMap, InputModuleItems}},
-    ty::{InferenceResult, Ty},
-    adt::{StructData, EnumData},
+    ty::{InferenceResult, Ty, traits::{TraitRef, Implementation}},
+    adt::{StructData, EnumData, EnumVariantData},
+    traits::TraitData,
+    type_alias::TypeAliasData,
+    consts::ConstData,
+    impl_block::ModuleImplBlocks,
+    ty::method_resolution::CrateImplBlocks,
+    generics::GenericParams,
 };
 
 salsa::query_group! {
 
 pub trait HirDatabase: SyntaxDatabase
     + AsRef<LocationIntener<DefLoc, DefId>>
+    + AsRef<LocationIntener<MacroCallLoc, MacroCallId>>
 {
-    fn fn_scopes(def_id: DefId) -> Arc<FnScopes> {
+    fn hir_source_file(file_id: HirFileId) -> SourceFileNode {
+        type HirSourceFileQuery;
+        use fn HirFileId::hir_source_file;
+    }
+
+    fn macro_def(source_item_id: SourceItemId) -> Option<Arc<crate::MacroDef>> {
+        type MacroDefQuery;
+        use fn crate::macros::macro_def;
+    }
+
+    fn expand_macro_invocation(invoc: MacroCallId) -> Option<Arc<crate::MacroExpansion>> {
+        type ExpandMacroInvocationQuery;
+        use fn crate::macros::expand_macro_invocation;
+    }
+
+    fn fn_scopes(def_id: DefId) -> Cancelable<Arc<FnScopes>> {
         type FnScopesQuery;
         use fn query_definitions::fn_scopes;
     }
 
+    fn fn_signature(def_id: DefId) -> Arc<FnSignature> {
+        type FnSignatureQuery;
+        use fn crate::function::fn_signature;
+    }
+
+    fn body_hir(def_id: DefId) -> Cancelable<Arc<crate::expr::Body>> {
+        type BodyHirQuery;
+        use fn crate::expr::body_hir;
+    }
+
+    fn body_syntax_mapping(def_id: DefId) -> Cancelable<Arc<crate::expr::BodySyntaxMapping>> {
+        type BodySyntaxMappingQuery;
+        use fn crate::expr::body_syntax_mapping;
+    }
+
     fn struct_data(def_id: DefId) -> Cancelable<Arc<StructData>> {
         type StructDataQuery;
         use fn query_definitions::struct_data;
@@ -34,6 +71,31 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::enum_data;
     }
 
+    fn enum_variant_data(def_id: DefId) -> Cancelable<Arc<EnumVariantData>> {
+        type EnumVariantDataQuery;
+        use fn query_definitions::enum_variant_data;
+    }
+
+    fn trait_data(def_id: DefId) -> Cancelable<Arc<TraitData>> {
+        type TraitDataQuery;
+        use fn query_definitions::trait_data;
+    }
+
+    fn type_alias_data(def_id: DefId) -> Cancelable<Arc<TypeAliasData>> {
+        type TypeAliasDataQuery;
+        use fn query_definitions::type_alias_data;
+    }
+
+    fn const_data(def_id: DefId) -> Cancelable<Arc<ConstData>> {
+        type ConstDataQuery;
+        use fn query_definitions::const_data;
+    }
+
+    fn generic_params(def_id: DefId) -> Arc<GenericParams> {
+        type GenericParamsQuery;
+        use fn query_definitions::generic_params;
+    }
+
     fn infer(def_id: DefId) -> Cancelable<Arc<InferenceResult>> {
         type InferQuery;
         use fn query_definitions::infer;
@@ -44,12 +106,17 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::type_for_def;
     }
 
-    fn type_for_field(def_id: DefId, field: Name) -> Cancelable<Ty> {
+    fn type_for_field(def_id: DefId, field: Name) -> Cancelable<Option<Ty>> {
         type TypeForFieldQuery;
         use fn query_definitions::type_for_field;
     }
 
-    fn file_items(file_id: FileId) -> Arc<SourceFileItems> {
+    fn implements(trait_ref: TraitRef) -> Cancelable<Option<Implementation>> {
+        type ImplementsQuery;
+        use fn crate::ty::traits::implements;
+    }
+
+    fn file_items(file_id: HirFileId) -> Arc<SourceFileItems> {
         type SourceFileItemsQuery;
         use fn query_definitions::file_items;
     }
@@ -59,23 +126,38 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::file_item;
     }
 
-    fn submodules(source: ModuleSource) -> Cancelable<Arc<Vec<crate::module::imp::Submodule>>> {
+    fn submodules(krate: CrateId, source: ModuleSource) -> Cancelable<Arc<Vec<crate::module::imp::Submodule>>> {
         type SubmodulesQuery;
         use fn query_definitions::submodules;
     }
 
-    fn input_module_items(source_root_id: SourceRootId, module_id: ModuleId) -> Cancelable<Arc<InputModuleItems>> {
+    fn source_root_crates(source_root_id: SourceRootId) -> Arc<Vec<CrateId>> {
+        type SourceRootCratesQuery;
+        use fn crate::krate::source_root_crates;
+    }
+
+    fn input_module_items(krate: CrateId, module_id: ModuleId) -> Cancelable<Arc<InputModuleItems>> {
         type InputModuleItemsQuery;
         use fn query_definitions::input_module_items;
     }
-    fn item_map(source_root_id: SourceRootId) -> Cancelable<Arc<ItemMap>> {
+    fn item_map(krate: CrateId) -> Cancelable<Arc<ItemMap>> {
         type ItemMapQuery;
         use fn query_definitions::item_map;
     }
-    fn module_tree(source_root_id: SourceRootId) -> Cancelable<Arc<ModuleTree>> {
+    fn module_tree(krate: CrateId) -> Cancelable<Arc<ModuleTree>> {
         type ModuleTreeQuery;
         use fn crate::module::imp::module_tree;
     }
+
+    fn impls_in_module(krate: CrateId, module_id: ModuleId) -> Cancelable<Arc<ModuleImplBlocks>> {
+        type ImplsInModuleQuery;
+        use fn crate::impl_block::impls_in_module;
+    }
+
+    fn impls_in_crate(krate: CrateId) -> Cancelable<Arc<CrateImplBlocks>> {
+        type ImplsInCrateQuery;
+        use fn crate::ty::method_resolution::impls_in_crate;
+    }
 }
 
 }
@@ -0,0 +1,1040 @@
+//! HIR for function bodies. The body of a function is lowered into arenas of
+//! `Expr`s and `Pat`s, and all further analysis (scopes, type inference) works
+//! on those instead of the syntax tree. A `BodySyntaxMapping` remembers which
+//! syntax node each expression and pattern came from.
+
+use std::ops::Index;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use ra_db::{LocalSyntaxPtr, Cancelable};
+use ra_syntax::{
+    SyntaxKind::{MACRO_CALL, R_CURLY},
+    ast::{self, AstNode, LoopBodyOwner, ArgListOwner, NameOwner},
+};
+
+use crate::{
+    Path, Name, AsName, DefId, Def, ConstExpr,
+    db::HirDatabase,
+    type_ref::{Mutability, TypeRef},
+    arena::{Arena, Id},
+};
+
+pub type ExprId = Id<Expr>;
+pub type PatId = Id<Pat>;
+
+/// The body of a function.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Body {
+    exprs: Arena<Expr>,
+    pats: Arena<Pat>,
+    /// The patterns for the function's arguments. While the argument types are
+    /// part of the function signature, the patterns are not (they don't change
+    /// the external type of the function).
+    ///
+    /// If the function has a `self` parameter, it comes first, as a binding
+    /// named `self`.
+    args: Vec<PatId>,
+    body_expr: ExprId,
+}
+
+/// A body together with the mapping from syntax nodes to HIR expression and
+/// pattern ids, and back. This is needed to go from e.g. a position in a file
+/// to the HIR expression containing it; but for type inference etc., we want
+/// to operate on a structure that is agnostic to the actual positions of
+/// expressions in the file, so that we don't recompute the type inference
+/// whenever some whitespace is typed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BodySyntaxMapping {
+    body: Arc<Body>,
+    expr_syntax_mapping: FxHashMap<LocalSyntaxPtr, ExprId>,
+    expr_syntax_mapping_back: FxHashMap<ExprId, LocalSyntaxPtr>,
+    pat_syntax_mapping: FxHashMap<LocalSyntaxPtr, PatId>,
+    pat_syntax_mapping_back: FxHashMap<PatId, LocalSyntaxPtr>,
+}
+
+impl Body {
+    pub fn args(&self) -> &[PatId] {
+        &self.args
+    }
+
+    pub fn body_expr(&self) -> ExprId {
+        self.body_expr
+    }
+}
+
+impl Index<ExprId> for Body {
+    type Output = Expr;
+
+    fn index(&self, expr: ExprId) -> &Expr {
+        &self.exprs[expr]
+    }
+}
+
+impl Index<PatId> for Body {
+    type Output = Pat;
+
+    fn index(&self, pat: PatId) -> &Pat {
+        &self.pats[pat]
+    }
+}
+
+impl BodySyntaxMapping {
+    pub fn expr_syntax(&self, expr: ExprId) -> Option<LocalSyntaxPtr> {
+        self.expr_syntax_mapping_back.get(&expr).cloned()
+    }
+    pub fn syntax_expr(&self, ptr: LocalSyntaxPtr) -> Option<ExprId> {
+        self.expr_syntax_mapping.get(&ptr).cloned()
+    }
+    pub fn node_expr(&self, node: ast::Expr) -> Option<ExprId> {
+        self.expr_syntax_mapping
+            .get(&LocalSyntaxPtr::new(node.syntax()))
+            .cloned()
+    }
+    pub fn pat_syntax(&self, pat: PatId) -> Option<LocalSyntaxPtr> {
+        self.pat_syntax_mapping_back.get(&pat).cloned()
+    }
+    pub fn syntax_pat(&self, ptr: LocalSyntaxPtr) -> Option<PatId> {
+        self.pat_syntax_mapping.get(&ptr).cloned()
+    }
+    pub fn node_pat(&self, node: ast::Pat) -> Option<PatId> {
+        self.pat_syntax_mapping
+            .get(&LocalSyntaxPtr::new(node.syntax()))
+            .cloned()
+    }
+
+    pub fn body(&self) -> &Arc<Body> {
+        &self.body
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// This is produced if syntax tree does not have a required expression piece.
+    Missing,
+    Path(Path),
+    If {
+        condition: ExprId,
+        then_branch: ExprId,
+        else_branch: Option<ExprId>,
+    },
+    Block {
+        statements: Vec<Statement>,
+        tail: Option<ExprId>,
+    },
+    /// An `unsafe { ... }` block; `body` is the block itself.
+    Unsafe {
+        body: ExprId,
+    },
+    Loop {
+        body: ExprId,
+        label: Option<Name>,
+    },
+    While {
+        condition: ExprId,
+        body: ExprId,
+        label: Option<Name>,
+    },
+    For {
+        iterable: ExprId,
+        pat: PatId,
+        body: ExprId,
+        label: Option<Name>,
+    },
+    Call {
+        callee: ExprId,
+        args: Vec<ExprId>,
+    },
+    MethodCall {
+        receiver: ExprId,
+        method_name: Name,
+        args: Vec<ExprId>,
+    },
+    Match {
+        expr: ExprId,
+        arms: Vec<MatchArm>,
+    },
+    Continue,
+    Break {
+        expr: Option<ExprId>,
+        /// The label of the loop to break out of; the innermost loop if `None`.
+        label: Option<Name>,
+    },
+    Return {
+        expr: Option<ExprId>,
+    },
+    StructLit {
+        path: Option<Path>,
+        fields: Vec<StructLitField>,
+        spread: Option<ExprId>,
+    },
+    Field {
+        expr: ExprId,
+        name: Name,
+    },
+    Try {
+        expr: ExprId,
+    },
+    Cast {
+        expr: ExprId,
+        type_ref: TypeRef,
+    },
+    Ref {
+        expr: ExprId,
+        mutability: Mutability,
+    },
+    UnaryOp {
+        expr: ExprId,
+        op: Option<UnaryOp>,
+    },
+    BinaryOp {
+        lhs: ExprId,
+        rhs: ExprId,
+    },
+    Index {
+        base: ExprId,
+        index: ExprId,
+    },
+    Lambda {
+        args: Vec<PatId>,
+        arg_types: Vec<Option<TypeRef>>,
+        body: ExprId,
+    },
+    Tuple {
+        exprs: Vec<ExprId>,
+    },
+    Array(Array),
+    Literal(Literal),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Array {
+    /// `[a, b, c]`
+    ElementList(Vec<ExprId>),
+    /// `[initializer; repeat]`. The repeat count is also kept as `len`, for
+    /// evaluating it at compile time.
+    Repeat {
+        initializer: ExprId,
+        repeat: ExprId,
+        len: ConstExpr,
+    },
+}
+
+pub type UnaryOp = ast::PrefixOp;
+pub type Literal = ast::LiteralFlavor;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchArm {
+    pub pats: Vec<PatId>,
+    pub guard: Option<ExprId>,
+    pub expr: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLitField {
+    pub name: Name,
+    pub expr: ExprId,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Statement {
+    Let {
+        pat: PatId,
+        type_ref: Option<TypeRef>,
+        initializer: Option<ExprId>,
+    },
+    Expr(ExprId),
+}
+
+impl Expr {
+    /// Calls `f` with the ids of all direct subexpressions of `self`.
+    pub fn walk_child_exprs(&self, mut f: impl FnMut(ExprId)) {
+        match self {
+            Expr::Missing | Expr::Path(_) | Expr::Continue | Expr::Literal(_) => {}
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                f(*condition);
+                f(*then_branch);
+                if let Some(else_branch) = else_branch {
+                    f(*else_branch);
+                }
+            }
+            Expr::Block { statements, tail } => {
+                for stmt in statements {
+                    match stmt {
+                        Statement::Let { initializer, .. } => {
+                            if let Some(expr) = initializer {
+                                f(*expr);
+                            }
+                        }
+                        Statement::Expr(e) => f(*e),
+                    }
+                }
+                if let Some(expr) = tail {
+                    f(*expr);
+                }
+            }
+            Expr::Unsafe { body } | Expr::Loop { body, .. } => f(*body),
+            Expr::While {
+                condition, body, ..
+            } => {
+                f(*condition);
+                f(*body);
+            }
+            Expr::For { iterable, body, .. } => {
+                f(*iterable);
+                f(*body);
+            }
+            Expr::Call { callee, args } => {
+                f(*callee);
+                for arg in args {
+                    f(*arg);
+                }
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                f(*receiver);
+                for arg in args {
+                    f(*arg);
+                }
+            }
+            Expr::Match { expr, arms } => {
+                f(*expr);
+                for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        f(guard);
+                    }
+                    f(arm.expr);
+                }
+            }
+            Expr::Break { expr, .. } | Expr::Return { expr } => {
+                if let Some(expr) = expr {
+                    f(*expr);
+                }
+            }
+            Expr::StructLit { fields, spread, .. } => {
+                for field in fields {
+                    f(field.expr);
+                }
+                if let Some(expr) = spread {
+                    f(*expr);
+                }
+            }
+            Expr::Lambda { body, .. } => f(*body),
+            Expr::BinaryOp { lhs, rhs } => {
+                f(*lhs);
+                f(*rhs);
+            }
+            Expr::Index { base, index } => {
+                f(*base);
+                f(*index);
+            }
+            Expr::Field { expr, .. }
+            | Expr::Try { expr }
+            | Expr::Cast { expr, .. }
+            | Expr::Ref { expr, .. }
+            | Expr::UnaryOp { expr, .. } => f(*expr),
+            Expr::Tuple { exprs } | Expr::Array(Array::ElementList(exprs)) => {
+                for expr in exprs {
+                    f(*expr);
+                }
+            }
+            Expr::Array(Array::Repeat {
+                initializer,
+                repeat,
+                ..
+            }) => {
+                f(*initializer);
+                f(*repeat);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingAnnotation {
+    /// No binding annotation given: this means that the final binding mode
+    /// will depend on whether we have skipped through a `&` reference when
+    /// matching. For example, the `x` in `Some(x)` will have binding mode
+    /// `None`; if you do `let Some(x) = &Some(22)`, it will ultimately be a
+    /// ref-binding. For now, we treat it like a by-value binding.
+    Unannotated,
+    /// Annotated with `mut x` -- could be either ref or not, similar to `None`.
+    Mutable,
+    /// Annotated as `ref`, like `ref x`
+    Ref,
+    /// Annotated as `ref mut x`.
+    RefMut,
+}
+
+impl BindingAnnotation {
+    fn new(is_mutable: bool, is_ref: bool) -> Self {
+        match (is_mutable, is_ref) {
+            (true, true) => BindingAnnotation::RefMut,
+            (false, true) => BindingAnnotation::Ref,
+            (true, false) => BindingAnnotation::Mutable,
+            (false, false) => BindingAnnotation::Unannotated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPat {
+    pub name: Name,
+    pub pat: PatId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pat {
+    Missing,
+    Wild,
+    Tuple {
+        args: Vec<PatId>,
+        /// The position of `..`, if any: the sub-patterns after it match the
+        /// last elements of the tuple.
+        ellipsis: Option<usize>,
+    },
+    Struct {
+        path: Option<Path>,
+        args: Vec<FieldPat>,
+    },
+    Slice {
+        args: Vec<PatId>,
+    },
+    Path(Path),
+    /// A literal pattern; the literal itself is lowered as an expression.
+    Lit(ExprId),
+    Bind {
+        name: Name,
+        mode: BindingAnnotation,
+        subpat: Option<PatId>,
+    },
+    TupleStruct {
+        path: Option<Path>,
+        args: Vec<PatId>,
+    },
+    Ref {
+        pat: PatId,
+        mutability: Mutability,
+    },
+}
+
+impl Pat {
+    /// Calls `f` with the ids of all direct subpatterns of `self`.
+    pub fn walk_child_pats(&self, mut f: impl FnMut(PatId)) {
+        match self {
+            Pat::Missing | Pat::Wild | Pat::Path(_) | Pat::Lit(_) => {}
+            Pat::Bind { subpat, .. } => {
+                if let Some(pat) = subpat {
+                    f(*pat);
+                }
+            }
+            Pat::Tuple { args, .. } | Pat::TupleStruct { args, .. } | Pat::Slice { args } => {
+                args.iter().for_each(|pat| f(*pat));
+            }
+            Pat::Ref { pat, .. } => f(*pat),
+            Pat::Struct { args, .. } => {
+                args.iter().for_each(|field_pat| f(field_pat.pat));
+            }
+        }
+    }
+}
+
+// Queries
+
+pub(crate) fn body_hir(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<Body>> {
+    Ok(Arc::clone(&body_syntax_mapping(db, def_id)?.body))
+}
+
+struct ExprCollector {
+    exprs: Arena<Expr>,
+    pats: Arena<Pat>,
+    expr_syntax_mapping: FxHashMap<LocalSyntaxPtr, ExprId>,
+    expr_syntax_mapping_back: FxHashMap<ExprId, LocalSyntaxPtr>,
+    pat_syntax_mapping: FxHashMap<LocalSyntaxPtr, PatId>,
+    pat_syntax_mapping_back: FxHashMap<PatId, LocalSyntaxPtr>,
+}
+
+impl ExprCollector {
+    fn new() -> Self {
+        ExprCollector {
+            exprs: Arena::default(),
+            pats: Arena::default(),
+            expr_syntax_mapping: FxHashMap::default(),
+            expr_syntax_mapping_back: FxHashMap::default(),
+            pat_syntax_mapping: FxHashMap::default(),
+            pat_syntax_mapping_back: FxHashMap::default(),
+        }
+    }
+
+    fn alloc_expr(&mut self, expr: Expr, syntax_ptr: LocalSyntaxPtr) -> ExprId {
+        let id = self.exprs.alloc(expr);
+        self.expr_syntax_mapping.insert(syntax_ptr, id);
+        self.expr_syntax_mapping_back.insert(id, syntax_ptr);
+        id
+    }
+
+    fn alloc_pat(&mut self, pat: Pat, syntax_ptr: LocalSyntaxPtr) -> PatId {
+        let id = self.pats.alloc(pat);
+        self.pat_syntax_mapping.insert(syntax_ptr, id);
+        self.pat_syntax_mapping_back.insert(id, syntax_ptr);
+        id
+    }
+
+    fn empty_block(&mut self) -> ExprId {
+        let block = Expr::Block {
+            statements: Vec::new(),
+            tail: None,
+        };
+        self.exprs.alloc(block)
+    }
+
+    fn collect_expr(&mut self, expr: ast::Expr) -> ExprId {
+        let syntax_ptr = LocalSyntaxPtr::new(expr.syntax());
+        match expr {
+            ast::Expr::IfExpr(e) => {
+                if let Some(pat) = e.condition().and_then(|c| c.pat()) {
+                    // if let -- desugar to match
+                    let pat = self.collect_pat(pat);
+                    let match_expr =
+                        self.collect_expr_opt(e.condition().expect("checked above").expr());
+                    let then_branch = self.collect_block_opt(e.then_branch());
+                    let else_branch = e
+                        .else_branch()
+                        .map(|b| self.collect_else_branch(b))
+                        .unwrap_or_else(|| self.empty_block());
+                    let placeholder_pat = self.pats.alloc(Pat::Wild);
+                    let arms = vec![
+                        MatchArm {
+                            pats: vec![pat],
+                            expr: then_branch,
+                            guard: None,
+                        },
+                        MatchArm {
+                            pats: vec![placeholder_pat],
+                            expr: else_branch,
+                            guard: None,
+                        },
+                    ];
+                    self.alloc_expr(
+                        Expr::Match {
+                            expr: match_expr,
+                            arms,
+                        },
+                        syntax_ptr,
+                    )
+                } else {
+                    let condition = self.collect_expr_opt(e.condition().and_then(|c| c.expr()));
+                    let then_branch = self.collect_block_opt(e.then_branch());
+                    let else_branch = e.else_branch().map(|b| self.collect_else_branch(b));
+                    self.alloc_expr(
+                        Expr::If {
+                            condition,
+                            then_branch,
+                            else_branch,
+                        },
+                        syntax_ptr,
+                    )
+                }
+            }
+            ast::Expr::BlockExpr(e) => {
+                let block = self.collect_block_opt(e.block());
+                if e.is_unsafe() {
+                    self.alloc_expr(Expr::Unsafe { body: block }, syntax_ptr)
+                } else {
+                    // make the block expression point to the block as well
+                    self.expr_syntax_mapping.insert(syntax_ptr, block);
+                    block
+                }
+            }
+            ast::Expr::LoopExpr(e) => {
+                let body = self.collect_block_opt(e.loop_body());
+                let label = loop_label(e);
+                self.alloc_expr(Expr::Loop { body, label }, syntax_ptr)
+            }
+            ast::Expr::WhileExpr(e) => {
+                if let Some(pat) = e.condition().and_then(|c| c.pat()) {
+                    // while let -- desugar to
+                    // `loop { match expr { pat => body, _ => break } }`
+                    let pat = self.collect_pat(pat);
+                    let match_expr =
+                        self.collect_expr_opt(e.condition().expect("checked above").expr());
+                    let body = self.collect_block_opt(e.loop_body());
+                    let break_expr = self.exprs.alloc(Expr::Break {
+                        expr: None,
+                        label: None,
+                    });
+                    let placeholder_pat = self.pats.alloc(Pat::Wild);
+                    let arms = vec![
+                        MatchArm {
+                            pats: vec![pat],
+                            expr: body,
+                            guard: None,
+                        },
+                        MatchArm {
+                            pats: vec![placeholder_pat],
+                            expr: break_expr,
+                            guard: None,
+                        },
+                    ];
+                    let match_expr = self.exprs.alloc(Expr::Match {
+                        expr: match_expr,
+                        arms,
+                    });
+                    let label = loop_label(e);
+                    self.alloc_expr(
+                        Expr::Loop {
+                            body: match_expr,
+                            label,
+                        },
+                        syntax_ptr,
+                    )
+                } else {
+                    let condition = self.collect_expr_opt(e.condition().and_then(|c| c.expr()));
+                    let body = self.collect_block_opt(e.loop_body());
+                    let label = loop_label(e);
+                    self.alloc_expr(
+                        Expr::While {
+                            condition,
+                            body,
+                            label,
+                        },
+                        syntax_ptr,
+                    )
+                }
+            }
+            ast::Expr::ForExpr(e) => {
+                let iterable = self.collect_expr_opt(e.iterable());
+                let pat = self.collect_pat_opt(e.pat());
+                let body = self.collect_block_opt(e.loop_body());
+                let label = loop_label(e);
+                self.alloc_expr(
+                    Expr::For {
+                        iterable,
+                        pat,
+                        body,
+                        label,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::CallExpr(e) => {
+                let callee = self.collect_expr_opt(e.expr());
+                let args = if let Some(arg_list) = e.arg_list() {
+                    arg_list.args().map(|e| self.collect_expr(e)).collect()
+                } else {
+                    Vec::new()
+                };
+                self.alloc_expr(Expr::Call { callee, args }, syntax_ptr)
+            }
+            ast::Expr::MethodCallExpr(e) => {
+                let receiver = self.collect_expr_opt(e.expr());
+                let args = if let Some(arg_list) = e.arg_list() {
+                    arg_list.args().map(|e| self.collect_expr(e)).collect()
+                } else {
+                    Vec::new()
+                };
+                let method_name = e
+                    .name_ref()
+                    .map(|nr| nr.as_name())
+                    .unwrap_or_else(Name::missing);
+                self.alloc_expr(
+                    Expr::MethodCall {
+                        receiver,
+                        method_name,
+                        args,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::MatchExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let arms = if let Some(match_arm_list) = e.match_arm_list() {
+                    match_arm_list
+                        .arms()
+                        .map(|arm| MatchArm {
+                            pats: arm.pats().map(|p| self.collect_pat(p)).collect(),
+                            guard: arm
+                                .guard()
+                                .and_then(|guard| guard.expr())
+                                .map(|e| self.collect_expr(e)),
+                            expr: self.collect_expr_opt(arm.expr()),
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                self.alloc_expr(Expr::Match { expr, arms }, syntax_ptr)
+            }
+            ast::Expr::PathExpr(e) => {
+                let path = e
+                    .path()
+                    .and_then(Path::from_ast)
+                    .map(Expr::Path)
+                    .unwrap_or(Expr::Missing);
+                self.alloc_expr(path, syntax_ptr)
+            }
+            ast::Expr::ContinueExpr(_e) => {
+                // TODO: labels
+                self.alloc_expr(Expr::Continue, syntax_ptr)
+            }
+            ast::Expr::BreakExpr(e) => {
+                let expr = e.expr().map(|e| self.collect_expr(e));
+                let label = e.lifetime().map(|it| Name::new(it.text()));
+                self.alloc_expr(Expr::Break { expr, label }, syntax_ptr)
+            }
+            ast::Expr::ParenExpr(e) => {
+                let inner = self.collect_expr_opt(e.expr());
+                // make the paren expr point to the inner expression as well
+                self.expr_syntax_mapping.insert(syntax_ptr, inner);
+                inner
+            }
+            ast::Expr::ReturnExpr(e) => {
+                let expr = e.expr().map(|e| self.collect_expr(e));
+                self.alloc_expr(Expr::Return { expr }, syntax_ptr)
+            }
+            ast::Expr::StructLit(e) => {
+                let path = e.path().and_then(Path::from_ast);
+                let mut fields = Vec::new();
+                let mut spread = None;
+                if let Some(nfl) = e.named_field_list() {
+                    for field in nfl.fields() {
+                        let name = field
+                            .name_ref()
+                            .map(|nr| nr.as_name())
+                            .unwrap_or_else(Name::missing);
+                        let expr = if let Some(e) = field.expr() {
+                            self.collect_expr(e)
+                        } else if let Some(nr) = field.name_ref() {
+                            // field shorthand
+                            let path = Expr::Path(Path::from_name_ref(nr));
+                            self.alloc_expr(path, LocalSyntaxPtr::new(nr.syntax()))
+                        } else {
+                            self.exprs.alloc(Expr::Missing)
+                        };
+                        fields.push(StructLitField { name, expr });
+                    }
+                    spread = nfl.spread().map(|s| self.collect_expr(s));
+                }
+                self.alloc_expr(
+                    Expr::StructLit {
+                        path,
+                        fields,
+                        spread,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::FieldExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let name = e
+                    .name_ref()
+                    .map(|nr| nr.as_name())
+                    .unwrap_or_else(Name::missing);
+                self.alloc_expr(Expr::Field { expr, name }, syntax_ptr)
+            }
+            ast::Expr::TryExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                self.alloc_expr(Expr::Try { expr }, syntax_ptr)
+            }
+            ast::Expr::CastExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let type_ref = TypeRef::from_ast_opt(e.type_ref());
+                self.alloc_expr(Expr::Cast { expr, type_ref }, syntax_ptr)
+            }
+            ast::Expr::RefExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let mutability = Mutability::from_mutable(e.is_mut());
+                self.alloc_expr(Expr::Ref { expr, mutability }, syntax_ptr)
+            }
+            ast::Expr::PrefixExpr(e) => {
+                let expr = self.collect_expr_opt(e.expr());
+                let op = e.op();
+                self.alloc_expr(Expr::UnaryOp { expr, op }, syntax_ptr)
+            }
+            ast::Expr::LambdaExpr(e) => {
+                let mut args = Vec::new();
+                let mut arg_types = Vec::new();
+                if let Some(pl) = e.param_list() {
+                    for param in pl.params() {
+                        let pat = self.collect_pat_opt(param.pat());
+                        let type_ref = param.type_ref().map(TypeRef::from_ast);
+                        args.push(pat);
+                        arg_types.push(type_ref);
+                    }
+                }
+                let body = self.collect_expr_opt(e.body());
+                self.alloc_expr(
+                    Expr::Lambda {
+                        args,
+                        arg_types,
+                        body,
+                    },
+                    syntax_ptr,
+                )
+            }
+            ast::Expr::BinExpr(e) => {
+                let lhs = self.collect_expr_opt(e.lhs());
+                let rhs = self.collect_expr_opt(e.rhs());
+                self.alloc_expr(Expr::BinaryOp { lhs, rhs }, syntax_ptr)
+            }
+            ast::Expr::IndexExpr(e) => {
+                let base = self.collect_expr_opt(e.base());
+                let index = self.collect_expr_opt(e.index());
+                self.alloc_expr(Expr::Index { base, index }, syntax_ptr)
+            }
+            ast::Expr::TupleExpr(e) => {
+                let exprs = e.exprs().map(|expr| self.collect_expr(expr)).collect();
+                self.alloc_expr(Expr::Tuple { exprs }, syntax_ptr)
+            }
+            ast::Expr::ArrayExpr(e) => match e.kind() {
+                ast::ArrayExprKind::ElementList(exprs) => {
+                    let exprs = exprs.map(|expr| self.collect_expr(expr)).collect();
+                    self.alloc_expr(Expr::Array(Array::ElementList(exprs)), syntax_ptr)
+                }
+                ast::ArrayExprKind::Repeat {
+                    initializer,
+                    repeat,
+                } => {
+                    let len = ConstExpr::from_ast_opt(repeat);
+                    let initializer = self.collect_expr_opt(initializer);
+                    let repeat = self.collect_expr_opt(repeat);
+                    let array = Array::Repeat {
+                        initializer,
+                        repeat,
+                        len,
+                    };
+                    self.alloc_expr(Expr::Array(array), syntax_ptr)
+                }
+            },
+            ast::Expr::Literal(e) => {
+                let lit = e.flavor().map_or(Expr::Missing, Expr::Literal);
+                self.alloc_expr(lit, syntax_ptr)
+            }
+
+            // TODO implement HIR for these:
+            ast::Expr::Label(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
+            ast::Expr::RangeExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
+
+            // These only occur as parts of match and struct literal expressions
+            // and are handled there.
+            ast::Expr::MatchArmList(_)
+            | ast::Expr::MatchArm(_)
+            | ast::Expr::NamedFieldList(_)
+            | ast::Expr::NamedField(_) => self.alloc_expr(Expr::Missing, syntax_ptr),
+        }
+    }
+
+    fn collect_expr_opt(&mut self, expr: Option<ast::Expr>) -> ExprId {
+        if let Some(expr) = expr {
+            self.collect_expr(expr)
+        } else {
+            self.exprs.alloc(Expr::Missing)
+        }
+    }
+
+    fn collect_else_branch(&mut self, else_branch: ast::ElseBranchFlavor) -> ExprId {
+        match else_branch {
+            ast::ElseBranchFlavor::Block(block) => self.collect_block(block),
+            ast::ElseBranchFlavor::IfExpr(if_expr) => self.collect_expr(ast::Expr::IfExpr(if_expr)),
+        }
+    }
+
+    fn collect_block(&mut self, block: ast::Block) -> ExprId {
+        let statements = block
+            .statements()
+            .map(|s| match s {
+                ast::Stmt::LetStmt(stmt) => {
+                    let pat = self.collect_pat_opt(stmt.pat());
+                    let type_ref = stmt.type_ref().map(TypeRef::from_ast);
+                    let initializer = stmt.initializer().map(|e| self.collect_expr(e));
+                    Statement::Let {
+                        pat,
+                        type_ref,
+                        initializer,
+                    }
+                }
+                ast::Stmt::ExprStmt(stmt) => Statement::Expr(self.collect_expr_opt(stmt.expr())),
+            })
+            .collect();
+        let tail = match block.expr() {
+            Some(e) => Some(self.collect_expr(e)),
+            None => {
+                // we don't expand macros yet, but a trailing macro call like
+                // `format!(..)` is still the tail expression of the block
+                let last_child = block
+                    .syntax()
+                    .children()
+                    .filter(|it| !it.kind().is_trivia() && it.kind() != R_CURLY)
+                    .last();
+                match last_child {
+                    Some(node) if node.kind() == MACRO_CALL => {
+                        Some(self.alloc_expr(Expr::Missing, LocalSyntaxPtr::new(node)))
+                    }
+                    _ => None,
+                }
+            }
+        };
+        self.alloc_expr(
+            Expr::Block { statements, tail },
+            LocalSyntaxPtr::new(block.syntax()),
+        )
+    }
+
+    fn collect_block_opt(&mut self, block: Option<ast::Block>) -> ExprId {
+        if let Some(block) = block {
+            self.collect_block(block)
+        } else {
+            self.exprs.alloc(Expr::Missing)
+        }
+    }
+
+    fn collect_pat(&mut self, pat: ast::Pat) -> PatId {
+        let syntax_ptr = LocalSyntaxPtr::new(pat.syntax());
+        let pattern = match pat {
+            ast::Pat::BindPat(bp) => {
+                let name = bp
+                    .name()
+                    .map(|nr| nr.as_name())
+                    .unwrap_or_else(Name::missing);
+                let mode = BindingAnnotation::new(bp.is_mutable(), bp.is_ref());
+                let subpat = bp.pat().map(|subpat| self.collect_pat(subpat));
+                Pat::Bind { name, mode, subpat }
+            }
+            ast::Pat::TupleStructPat(p) => {
+                let path = p.path().and_then(Path::from_ast);
+                let args = p.args().map(|p| self.collect_pat(p)).collect();
+                Pat::TupleStruct { path, args }
+            }
+            ast::Pat::RefPat(p) => {
+                let pat = self.collect_pat_opt(p.pat());
+                let mutability = Mutability::from_mutable(p.is_mut());
+                Pat::Ref { pat, mutability }
+            }
+            ast::Pat::PathPat(p) => p
+                .path()
+                .and_then(Path::from_ast)
+                .map(Pat::Path)
+                .unwrap_or(Pat::Missing),
+            ast::Pat::TuplePat(p) => {
+                let args = p.args().map(|p| self.collect_pat(p)).collect();
+                Pat::Tuple {
+                    args,
+                    ellipsis: p.ellipsis_pos(),
+                }
+            }
+            ast::Pat::PlaceholderPat(_) => Pat::Wild,
+            ast::Pat::StructPat(p) => {
+                let path = p.path().and_then(Path::from_ast);
+                let mut args = Vec::new();
+                if let Some(field_pat_list) = p.field_pat_list() {
+                    for field_pat in field_pat_list.field_pats() {
+                        let name = field_pat
+                            .name()
+                            .map(|n| n.as_name())
+                            .unwrap_or_else(Name::missing);
+                        let pat = self.collect_pat_opt(field_pat.pat());
+                        args.push(FieldPat { name, pat });
+                    }
+                    for bind_pat in field_pat_list.bind_pats() {
+                        // field shorthand, e.g. `S { a }`
+                        let name = bind_pat
+                            .name()
+                            .map(|n| n.as_name())
+                            .unwrap_or_else(Name::missing);
+                        let pat = self.collect_pat(ast::Pat::BindPat(bind_pat));
+                        args.push(FieldPat { name, pat });
+                    }
+                }
+                Pat::Struct { path, args }
+            }
+            ast::Pat::SlicePat(p) => {
+                let args = p.args().map(|p| self.collect_pat(p)).collect();
+                Pat::Slice { args }
+            }
+            ast::Pat::LiteralPat(p) => match p.literal() {
+                Some(lit) => Pat::Lit(self.collect_expr(ast::Expr::Literal(lit))),
+                None => Pat::Missing,
+            },
+
+            // TODO: implement
+            ast::Pat::RangePat(_) => Pat::Missing,
+            // only occurs as part of a struct pattern
+            ast::Pat::FieldPatList(_) => Pat::Missing,
+        };
+        self.alloc_pat(pattern, syntax_ptr)
+    }
+
+    fn collect_pat_opt(&mut self, pat: Option<ast::Pat>) -> PatId {
+        if let Some(pat) = pat {
+            self.collect_pat(pat)
+        } else {
+            self.pats.alloc(Pat::Missing)
+        }
+    }
+
+    fn into_body_syntax_mapping(self, args: Vec<PatId>, body_expr: ExprId) -> BodySyntaxMapping {
+        let body = Body {
+            exprs: self.exprs,
+            pats: self.pats,
+            args,
+            body_expr,
+        };
+        BodySyntaxMapping {
+            body: Arc::new(body),
+            expr_syntax_mapping: self.expr_syntax_mapping,
+            expr_syntax_mapping_back: self.expr_syntax_mapping_back,
+            pat_syntax_mapping: self.pat_syntax_mapping,
+            pat_syntax_mapping_back: self.pat_syntax_mapping_back,
+        }
+    }
+}
+
+fn loop_label<'a>(e: impl LoopBodyOwner<'a>) -> Option<Name> {
+    let lifetime = e.label()?.lifetime()?;
+    Some(Name::new(lifetime.text()))
+}
+
+pub(crate) fn collect_fn_body_syntax(node: ast::FnDef) -> BodySyntaxMapping {
+    let mut collector = ExprCollector::new();
+
+    let mut args = Vec::new();
+    if let Some(param_list) = node.param_list() {
+        if let Some(self_param) = param_list.self_param() {
+            let self_param = LocalSyntaxPtr::new(self_param.syntax());
+            let arg = collector.alloc_pat(
+                Pat::Bind {
+                    name: Name::self_param(),
+                    mode: BindingAnnotation::Unannotated,
+                    subpat: None,
+                },
+                self_param,
+            );
+            args.push(arg);
+        }
+
+        for param in param_list.params() {
+            args.push(collector.collect_pat_opt(param.pat()));
+        }
+    }
+
+    let body = collector.collect_block_opt(node.body());
+    collector.into_body_syntax_mapping(args, body)
+}
+
+pub(crate) fn body_syntax_mapping(
+    db: &impl HirDatabase,
+    def_id: DefId,
+) -> Cancelable<Arc<BodySyntaxMapping>> {
+    let def = def_id.resolve(db)?;
+
+    let body_syntax_mapping = match def {
+        Def::Function(f) => collect_fn_body_syntax(f.syntax(db).borrowed()),
+        // TODO: consts, etc.
+        _ => panic!("Trying to get body for item type without body"),
+    };
+
+    Ok(Arc::new(body_syntax_mapping))
+}
@@ -8,14 +8,19 @@ use std::{
 use ra_db::Cancelable;
 use ra_syntax::{
     TextRange, TextUnit,
-    ast::{self, AstNode, DocCommentsOwner, NameOwner},
+    ast::{self, AstNode, DocCommentsOwner, NameOwner, SelfParamFlavor},
 };
 
-use crate::{DefId, DefKind, HirDatabase, ty::InferenceResult, Module};
+use crate::{
+    DefId, DefKind, HirDatabase, ty::InferenceResult, Module, GenericParams, Name, AsName,
+    ImplBlock,
+    expr::{Body, BodySyntaxMapping},
+    type_ref::{TypeRef, Mutability},
+};
 
-pub use self::scope::FnScopes;
+pub use self::scope::{FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Function {
     def_id: DefId,
 }
@@ -25,6 +30,20 @@ impl Function {
         Function { def_id }
     }
 
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Name {
+        self.signature(db).name().clone()
+    }
+
+    /// Whether the function takes a `self` parameter, i.e. can be called with
+    /// method call syntax.
+    pub fn has_self_param(&self, db: &impl HirDatabase) -> bool {
+        self.signature(db).has_self_param()
+    }
+
     pub fn syntax(&self, db: &impl HirDatabase) -> ast::FnDefNode {
         let def_loc = self.def_id.loc(db);
         assert!(def_loc.kind == DefKind::Function);
@@ -32,8 +51,21 @@ impl Function {
         ast::FnDef::cast(syntax.borrowed()).unwrap().owned()
     }
 
-    pub fn scopes(&self, db: &impl HirDatabase) -> Arc<FnScopes> {
-        db.fn_scopes(self.def_id)
+    pub fn body_syntax_mapping(&self, db: &impl HirDatabase) -> Cancelable<Arc<BodySyntaxMapping>> {
+        db.body_syntax_mapping(self.def_id)
+    }
+
+    pub fn scopes(&self, db: &impl HirDatabase) -> Cancelable<ScopesWithSyntaxMapping> {
+        let scopes = db.fn_scopes(self.def_id)?;
+        let syntax_mapping = db.body_syntax_mapping(self.def_id)?;
+        Ok(ScopesWithSyntaxMapping {
+            scopes,
+            syntax_mapping,
+        })
+    }
+
+    pub fn signature(&self, db: &impl HirDatabase) -> Arc<FnSignature> {
+        db.fn_signature(self.def_id)
     }
 
     pub fn signature_info(&self, db: &impl HirDatabase) -> Option<FnSignatureInfo> {
@@ -45,9 +77,109 @@ impl Function {
         db.infer(self.def_id)
     }
 
+    pub fn body(&self, db: &impl HirDatabase) -> Cancelable<Arc<Body>> {
+        db.body_hir(self.def_id)
+    }
+
     pub fn module(&self, db: &impl HirDatabase) -> Cancelable<Module> {
         self.def_id.module(db)
     }
+
+    pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
+        db.generic_params(self.def_id)
+    }
+
+    /// The containing impl block, if this is a method.
+    pub fn impl_block(&self, db: &impl HirDatabase) -> Cancelable<Option<ImplBlock>> {
+        let loc = self.def_id.loc(db);
+        let module_impls = db.impls_in_module(loc.krate, loc.module_id)?;
+        Ok(ImplBlock::containing(module_impls, self.def_id))
+    }
+}
+
+/// The signature of a function, as declared: the types are not resolved yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnSignature {
+    name: Name,
+    /// The types of the parameters, not including `self`.
+    params: Vec<TypeRef>,
+    ret_type: TypeRef,
+    /// The declared type of the `self` parameter, e.g. `&Self` for `&self`.
+    self_param: Option<TypeRef>,
+    is_unsafe: bool,
+}
+
+impl FnSignature {
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[TypeRef] {
+        &self.params
+    }
+
+    pub fn ret_type(&self) -> &TypeRef {
+        &self.ret_type
+    }
+
+    /// Whether the function takes a `self` parameter, i.e. can be called with
+    /// method call syntax.
+    pub fn has_self_param(&self) -> bool {
+        self.self_param.is_some()
+    }
+
+    /// The type of the `self` parameter, as declared, i.e. still mentioning
+    /// `Self`.
+    pub fn self_param(&self) -> Option<&TypeRef> {
+        self.self_param.as_ref()
+    }
+
+    pub fn is_unsafe(&self) -> bool {
+        self.is_unsafe
+    }
+}
+
+pub(crate) fn fn_signature(db: &impl HirDatabase, def_id: DefId) -> Arc<FnSignature> {
+    let func = Function::new(def_id);
+    let syntax = func.syntax(db);
+    let node = syntax.borrowed();
+    let name = node
+        .name()
+        .map(|n| n.as_name())
+        .unwrap_or_else(Name::missing);
+    let mut params = Vec::new();
+    let mut self_param = None;
+    if let Some(param_list) = node.param_list() {
+        self_param = param_list.self_param().map(self_param_type);
+        for param in param_list.params() {
+            params.push(TypeRef::from_ast_opt(param.type_ref()));
+        }
+    }
+    let ret_type = if let Some(type_ref) = node.ret_type().and_then(|rt| rt.type_ref()) {
+        TypeRef::from_ast(type_ref)
+    } else {
+        TypeRef::unit()
+    };
+    let sig = FnSignature {
+        name,
+        params,
+        ret_type,
+        self_param,
+        is_unsafe: node.is_unsafe(),
+    };
+    Arc::new(sig)
+}
+
+fn self_param_type(self_param: ast::SelfParam) -> TypeRef {
+    if let Some(type_ref) = self_param.type_ref() {
+        return TypeRef::from_ast(type_ref);
+    }
+    let self_type = TypeRef::Path(Name::self_type().into());
+    match self_param.flavor() {
+        SelfParamFlavor::Owned => self_type,
+        SelfParamFlavor::Ref => TypeRef::Reference(Box::new(self_type), Mutability::Shared),
+        SelfParamFlavor::MutRef => TypeRef::Reference(Box::new(self_type), Mutability::Mut),
+    }
 }
 
 #[derive(Debug, Clone)]
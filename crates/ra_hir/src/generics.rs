@@ -0,0 +1,130 @@
+//! Many kinds of items or constructs can have generic parameters: functions,
+//! structs, impls, traits, etc. This module provides a common HIR for these
+//! generic parameters. See also the `Generics` type and the `generics_of` query
+//! in rustc.
+
+use std::sync::Arc;
+
+use ra_syntax::ast::{self, NameOwner};
+
+use crate::{Name, AsName, Path, type_ref::TypeRef};
+
+/// Data about a generic parameter (to a function, struct, impl, ...).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GenericParam {
+    pub(crate) idx: u32,
+    pub(crate) name: Name,
+}
+
+/// A trait bound on a type, from either a where clause or the bounds of a
+/// type parameter: `T: Clone` in `fn f<T: Clone>()` or `where Vec<T>: Debug`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TypePredicate {
+    pub(crate) type_ref: TypeRef,
+    pub(crate) trait_path: Path,
+}
+
+/// Data about the generic parameters of a function, struct, impl, etc.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GenericParams {
+    /// The generic parameters of the impl block containing a method. They are
+    /// in scope in the method and come first in its substitutions.
+    pub(crate) parent_params: Option<Arc<GenericParams>>,
+    pub(crate) params: Vec<GenericParam>,
+    pub(crate) predicates: Vec<TypePredicate>,
+}
+
+impl GenericParams {
+    pub(crate) fn new(
+        parent_params: Option<Arc<GenericParams>>,
+        type_param_list: Option<ast::TypeParamList>,
+        where_clause: Option<ast::WhereClause>,
+    ) -> GenericParams {
+        let mut generics = GenericParams {
+            parent_params,
+            ..GenericParams::default()
+        };
+        let start = generics.count_parent_params();
+        if let Some(type_param_list) = type_param_list {
+            for (idx, type_param) in type_param_list.type_params().enumerate() {
+                let name = type_param
+                    .name()
+                    .map(|n| n.as_name())
+                    .unwrap_or_else(Name::missing);
+                let type_ref = TypeRef::Path(name.clone().into());
+                generics.add_predicates(&type_ref, type_param.bounds());
+                let param = GenericParam {
+                    idx: (start + idx) as u32,
+                    name,
+                };
+                generics.params.push(param);
+            }
+        }
+        if let Some(where_clause) = where_clause {
+            for pred in where_clause.predicates() {
+                let type_ref = TypeRef::from_ast_opt(pred.type_ref());
+                generics.add_predicates(&type_ref, pred.bounds());
+            }
+        }
+        generics
+    }
+
+    fn add_predicates(&mut self, type_ref: &TypeRef, bounds: Vec<ast::PathType>) {
+        for bound in bounds {
+            if let Some(trait_path) = bound.path().and_then(Path::from_ast) {
+                self.predicates.push(TypePredicate {
+                    type_ref: type_ref.clone(),
+                    trait_path,
+                });
+            }
+        }
+    }
+
+    pub(crate) fn find_by_name(&self, name: &Name) -> Option<&GenericParam> {
+        self.params
+            .iter()
+            .find(|p| &p.name == name)
+            .or_else(|| self.parent_params.as_ref()?.find_by_name(name))
+    }
+
+    pub(crate) fn count_parent_params(&self) -> usize {
+        self.parent_params
+            .as_ref()
+            .map_or(0, |p| p.count_params_including_parent())
+    }
+
+    pub(crate) fn count_params_including_parent(&self) -> usize {
+        self.count_parent_params() + self.params.len()
+    }
+
+    /// The parameters of the parent followed by our own, in the order of their
+    /// indices.
+    pub(crate) fn params_including_parent(&self) -> Vec<&GenericParam> {
+        let mut params = match &self.parent_params {
+            Some(parent) => parent.params_including_parent(),
+            None => Vec::new(),
+        };
+        params.extend(self.params.iter());
+        params
+    }
+
+    /// The predicates of the parent followed by our own.
+    pub(crate) fn predicates_including_parent(&self) -> Vec<&TypePredicate> {
+        let mut predicates = match &self.parent_params {
+            Some(parent) => parent.predicates_including_parent(),
+            None => Vec::new(),
+        };
+        predicates.extend(self.predicates.iter());
+        predicates
+    }
+
+    pub fn params(&self) -> &[GenericParam] {
+        &self.params
+    }
+}
+
+impl GenericParam {
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+}
@@ -0,0 +1,227 @@
+//! HIR for impl blocks. Impl blocks don't have names, so they don't get a
+//! `DefId`; instead, they are collected per module by the `impls_in_module`
+//! query and identified by their index in the resulting `ModuleImplBlocks`.
+
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use ra_syntax::ast::{self, AstNode, ModuleItemOwner, NameOwner, TypeParamsOwner};
+use ra_db::CrateId;
+
+use crate::{
+    DefId, DefLoc, DefKind, SourceItemId, HirFileId, SourceFileItems,
+    Function, HirDatabase, Cancelable, Module, Name, AsName,
+    type_ref::TypeRef,
+    generics::GenericParams,
+    cfg,
+    arena::{Arena, Id},
+    module::{ModuleSourceNode, ModuleId},
+};
+
+/// A single impl block, e.g. `impl Foo { ... }` or `impl Bar for Foo { ... }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImplBlock {
+    module_impl_blocks: Arc<ModuleImplBlocks>,
+    impl_id: ImplId,
+}
+
+impl ImplBlock {
+    pub(crate) fn from_id(module_impl_blocks: Arc<ModuleImplBlocks>, impl_id: ImplId) -> ImplBlock {
+        ImplBlock {
+            module_impl_blocks,
+            impl_id,
+        }
+    }
+
+    /// Returns the impl block containing the item `def_id`, if there is one.
+    pub(crate) fn containing(
+        module_impl_blocks: Arc<ModuleImplBlocks>,
+        def_id: DefId,
+    ) -> Option<ImplBlock> {
+        let impl_id = *module_impl_blocks.impls_by_def.get(&def_id)?;
+        Some(ImplBlock {
+            module_impl_blocks,
+            impl_id,
+        })
+    }
+
+    fn impl_data(&self) -> &ImplData {
+        &self.module_impl_blocks.impls[self.impl_id]
+    }
+
+    /// The module containing the impl block; the types of the impl need to be
+    /// resolved in it.
+    pub fn module(&self, db: &impl HirDatabase) -> Cancelable<Module> {
+        Module::new(
+            db,
+            self.module_impl_blocks.krate,
+            self.module_impl_blocks.module_id,
+        )
+    }
+
+    pub fn generic_params(&self) -> &GenericParams {
+        &self.impl_data().generics
+    }
+
+    /// The trait being implemented, `None` for inherent impls.
+    pub fn target_trait(&self) -> Option<&TypeRef> {
+        self.impl_data().target_trait.as_ref()
+    }
+
+    /// The type the impl is for, i.e. `Foo` in `impl Bar for Foo`.
+    pub fn target_type(&self) -> &TypeRef {
+        &self.impl_data().target_type
+    }
+
+    pub fn items(&self) -> &[ImplItem] {
+        &self.impl_data().items
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImplData {
+    generics: GenericParams,
+    target_trait: Option<TypeRef>,
+    target_type: TypeRef,
+    items: Vec<ImplItem>,
+}
+
+impl ImplData {
+    fn from_ast(
+        db: &impl HirDatabase,
+        file_id: HirFileId,
+        file_items: &SourceFileItems,
+        krate: CrateId,
+        module_id: ModuleId,
+        node: ast::ImplItem,
+    ) -> Self {
+        let generics = GenericParams::new(None, node.type_param_list(), node.where_clause());
+        let target_trait = node.target_trait().map(TypeRef::from_ast);
+        let target_type = TypeRef::from_ast_opt(node.target_type());
+        let items = if let Some(item_list) = node.item_list() {
+            item_list
+                .items()
+                .filter_map(|item| match item {
+                    ast::ModuleItem::FnDef(fn_def) => {
+                        let item_id = file_items.id_of(file_id, fn_def.syntax());
+                        let def_loc = DefLoc {
+                            kind: DefKind::Function,
+                            krate,
+                            module_id,
+                            source_item_id: SourceItemId {
+                                file_id,
+                                item_id: Some(item_id),
+                            },
+                        };
+                        Some(ImplItem::Method(Function::new(def_loc.id(db))))
+                    }
+                    ast::ModuleItem::TypeDef(type_def) => {
+                        let name = type_def.name()?.as_name();
+                        let type_ref = TypeRef::from_ast_opt(type_def.type_ref());
+                        Some(ImplItem::Type { name, type_ref })
+                    }
+                    // TODO: associated consts
+                    _ => None,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        ImplData {
+            generics,
+            target_trait,
+            target_type,
+            items,
+        }
+    }
+}
+
+/// An item inside an impl block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImplItem {
+    Method(Function),
+    /// An associated type, e.g. `type Target = Foo;`.
+    Type { name: Name, type_ref: TypeRef },
+    // TODO: Const
+}
+
+impl ImplItem {
+    pub fn def_id(&self) -> Option<DefId> {
+        match self {
+            ImplItem::Method(f) => Some(f.def_id()),
+            ImplItem::Type { .. } => None,
+        }
+    }
+}
+
+pub(crate) type ImplId = Id<ImplData>;
+
+/// The collection of impl blocks of a single module. Also remembers which impl
+/// block each item belongs to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ModuleImplBlocks {
+    krate: CrateId,
+    module_id: ModuleId,
+    impls: Arena<ImplData>,
+    impls_by_def: FxHashMap<DefId, ImplId>,
+}
+
+impl ModuleImplBlocks {
+    fn new(krate: CrateId, module_id: ModuleId) -> Self {
+        ModuleImplBlocks {
+            krate,
+            module_id,
+            impls: Arena::default(),
+            impls_by_def: FxHashMap::default(),
+        }
+    }
+
+    fn collect(&mut self, db: &impl HirDatabase) -> Cancelable<()> {
+        let (krate, module_id) = (self.krate, self.module_id);
+        let module_tree = db.module_tree(krate)?;
+        let source = module_id.source(&module_tree);
+        let file_id: HirFileId = source.file_id().into();
+        let file_items = db.file_items(file_id);
+
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(krate);
+        let node = source.resolve(db);
+        let items: Vec<ast::ModuleItem> = match &node {
+            ModuleSourceNode::SourceFile(it) => it.borrowed().items().collect(),
+            ModuleSourceNode::Module(it) => it
+                .borrowed()
+                .item_list()
+                .into_iter()
+                .flat_map(|it| it.items())
+                .collect(),
+        };
+        for item in items {
+            let impl_item = match item {
+                ast::ModuleItem::ImplItem(it) if cfg::is_enabled(cfg_options, it.syntax()) => it,
+                _ => continue,
+            };
+            let impl_data =
+                ImplData::from_ast(db, file_id, &file_items, krate, module_id, impl_item);
+            let id = self.impls.alloc(impl_data);
+            for def_id in self.impls[id].items.iter().filter_map(ImplItem::def_id) {
+                self.impls_by_def.insert(def_id, id);
+            }
+        }
+        Ok(())
+    }
+
+    /// The ids of all impl blocks of the module, in source order.
+    pub(crate) fn impl_ids<'a>(&'a self) -> impl Iterator<Item = ImplId> + 'a {
+        self.impls.iter().map(|(impl_id, _)| impl_id)
+    }
+}
+
+pub(crate) fn impls_in_module(
+    db: &impl HirDatabase,
+    krate: CrateId,
+    module_id: ModuleId,
+) -> Cancelable<Arc<ModuleImplBlocks>> {
+    let mut result = ModuleImplBlocks::new(krate, module_id);
+    result.collect(db)?;
+    Ok(Arc::new(result))
+}
@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 pub use ra_db::CrateId;
+use ra_db::SourceRootId;
 
 use crate::{HirDatabase, Module, Cancelable, Name, AsName};
 
@@ -20,6 +23,9 @@ impl Crate {
     pub(crate) fn new(crate_id: CrateId) -> Crate {
         Crate { crate_id }
     }
+    pub(crate) fn crate_id(&self) -> CrateId {
+        self.crate_id
+    }
     pub fn dependencies(&self, db: &impl HirDatabase) -> Vec<CrateDependency> {
         let crate_graph = db.crate_graph();
         crate_graph
@@ -32,16 +38,23 @@ impl Crate {
             .collect()
     }
     pub fn root_module(&self, db: &impl HirDatabase) -> Cancelable<Option<Module>> {
-        let crate_graph = db.crate_graph();
-        let file_id = crate_graph.crate_root(self.crate_id);
-        let source_root_id = db.file_source_root(file_id);
-        let module_tree = db.module_tree(source_root_id)?;
-        // FIXME: teach module tree about crate roots instead of guessing
-        let (module_id, _) = ctry!(module_tree
-            .modules_with_sources()
-            .find(|(_, src)| src.file_id() == file_id));
-
-        let module = Module::new(db, source_root_id, module_id)?;
+        let module_tree = db.module_tree(self.crate_id)?;
+        let module = Module::new(db, self.crate_id, module_tree.root())?;
         Ok(Some(module))
     }
 }
+
+/// Crates whose root file lives in the given source root. Several crates can
+/// share a source root, like the lib and the bins of a Cargo package.
+pub(crate) fn source_root_crates(
+    db: &impl HirDatabase,
+    source_root_id: SourceRootId,
+) -> Arc<Vec<CrateId>> {
+    let crate_graph = db.crate_graph();
+    let mut res = crate_graph
+        .iter()
+        .filter(|&krate| db.file_source_root(crate_graph.crate_root(krate)) == source_root_id)
+        .collect::<Vec<_>>();
+    res.sort();
+    Arc::new(res)
+}
@@ -27,13 +27,21 @@ mod krate;
 mod module;
 mod function;
 mod adt;
+mod traits;
+mod type_alias;
+mod consts;
+mod impl_block;
 mod type_ref;
+mod generics;
 mod ty;
+mod expr;
+mod macros;
+mod cfg;
 
 use std::ops::Index;
 
-use ra_syntax::{SyntaxNodeRef, SyntaxNode, SyntaxKind};
-use ra_db::{LocationIntener, SourceRootId, FileId, Cancelable};
+use ra_syntax::{SyntaxNodeRef, SyntaxNode, SyntaxKind, SourceFileNode};
+use ra_db::{LocationIntener, CrateId, FileId, Cancelable};
 
 use crate::{
     db::HirDatabase,
@@ -45,12 +53,24 @@ pub use self::{
     path::{Path, PathKind},
     name::Name,
     krate::Crate,
-    module::{Module, ModuleId, Problem, nameres::{ItemMap, PerNs, Namespace}, ModuleScope, Resolution},
-    function::{Function, FnScopes},
-    adt::{Struct, Enum},
-    ty::Ty,
+    module::{
+        Module, ModuleId, Problem, nameres::{ItemMap, PerNs, Namespace},
+        ModuleScope, Resolution, Visibility,
+    },
+    function::{Function, FnSignature, FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax},
+    adt::{Struct, Enum, EnumVariant},
+    traits::{Trait, TraitData, TraitItem},
+    type_alias::{Type, TypeAliasData},
+    consts::{Const, Static, ConstData, ConstExpr},
+    impl_block::{ImplBlock, ImplItem},
+    generics::{GenericParams, GenericParam, TypePredicate},
+    ty::{Ty, InferenceResult, InferenceDiagnostic},
+    type_ref::Mutability,
 };
 
+pub use self::expr::{Body, BodySyntaxMapping, ExprId, PatId};
+pub use self::macros::{MacroDef, MacroExpansion, MacroCallId, MacroCallLoc};
+
 pub use self::function::FnSignatureInfo;
 
 /// Def's are a core concept of hir. A `Def` is an Item (function, module, etc)
@@ -65,7 +85,11 @@ pub(crate) enum DefKind {
     Function,
     Struct,
     Enum,
-    Item,
+    Trait,
+    EnumVariant,
+    Type,
+    Const,
+    Static,
 
     StructCtor,
 }
@@ -73,7 +97,7 @@ pub(crate) enum DefKind {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct DefLoc {
     pub(crate) kind: DefKind,
-    source_root_id: SourceRootId,
+    krate: CrateId,
     module_id: ModuleId,
     source_item_id: SourceItemId,
 }
@@ -85,11 +109,10 @@ impl DefKind {
             SyntaxKind::MODULE => PerNs::types(DefKind::Module),
             SyntaxKind::STRUCT_DEF => PerNs::both(DefKind::Struct, DefKind::StructCtor),
             SyntaxKind::ENUM_DEF => PerNs::types(DefKind::Enum),
-            // These define items, but don't have their own DefKinds yet:
-            SyntaxKind::TRAIT_DEF => PerNs::types(DefKind::Item),
-            SyntaxKind::TYPE_DEF => PerNs::types(DefKind::Item),
-            SyntaxKind::CONST_DEF => PerNs::values(DefKind::Item),
-            SyntaxKind::STATIC_DEF => PerNs::values(DefKind::Item),
+            SyntaxKind::TRAIT_DEF => PerNs::types(DefKind::Trait),
+            SyntaxKind::TYPE_DEF => PerNs::types(DefKind::Type),
+            SyntaxKind::CONST_DEF => PerNs::values(DefKind::Const),
+            SyntaxKind::STATIC_DEF => PerNs::values(DefKind::Static),
             _ => PerNs::none(),
         }
     }
@@ -112,6 +135,11 @@ pub enum Def {
     Function(Function),
     Struct(Struct),
     Enum(Enum),
+    EnumVariant(EnumVariant),
+    Trait(Trait),
+    Type(Type),
+    Const(Const),
+    Static(Static),
     Item,
 }
 
@@ -120,7 +148,7 @@ impl DefId {
         let loc = self.loc(db);
         let res = match loc.kind {
             DefKind::Module => {
-                let module = Module::new(db, loc.source_root_id, loc.module_id)?;
+                let module = Module::new(db, loc.krate, loc.module_id)?;
                 Def::Module(module)
             }
             DefKind::Function => {
@@ -135,8 +163,27 @@ impl DefId {
                 let enum_def = Enum::new(self);
                 Def::Enum(enum_def)
             }
+            DefKind::Trait => {
+                let trait_def = Trait::new(self);
+                Def::Trait(trait_def)
+            }
+            DefKind::EnumVariant => {
+                let enum_variant = EnumVariant::new(self);
+                Def::EnumVariant(enum_variant)
+            }
+            DefKind::Type => {
+                let type_def = Type::new(self);
+                Def::Type(type_def)
+            }
+            DefKind::Const => {
+                let const_def = Const::new(self);
+                Def::Const(const_def)
+            }
+            DefKind::Static => {
+                let static_def = Static::new(self);
+                Def::Static(static_def)
+            }
             DefKind::StructCtor => Def::Item,
-            DefKind::Item => Def::Item,
         };
         Ok(res)
     }
@@ -144,7 +191,7 @@ impl DefId {
     /// For a module, returns that module; for any other def, returns the containing module.
     pub fn module(self, db: &impl HirDatabase) -> Cancelable<Module> {
         let loc = self.loc(db);
-        Module::new(db, loc.source_root_id, loc.module_id)
+        Module::new(db, loc.krate, loc.module_id)
     }
 }
 
@@ -152,9 +199,43 @@ impl DefId {
 /// it's OK to use it as a salsa key/value.
 pub(crate) type SourceFileItemId = Id<SyntaxNode>;
 
+/// `HirFileId` is either a real file, or the result of a macro expansion.
+/// Items produced by macros live in the latter kind of file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HirFileId {
+    File(FileId),
+    Macro(MacroCallId),
+}
+
+impl HirFileId {
+    /// For macro expansions, returns the file which contains the macro call.
+    pub fn original_file(self, db: &impl HirDatabase) -> FileId {
+        match self {
+            HirFileId::File(file_id) => file_id,
+            HirFileId::Macro(id) => id.loc(db).source_item_id.file_id.original_file(db),
+        }
+    }
+
+    pub(crate) fn hir_source_file(db: &impl HirDatabase, file_id: HirFileId) -> SourceFileNode {
+        match file_id {
+            HirFileId::File(file_id) => db.source_file(file_id),
+            HirFileId::Macro(id) => match db.expand_macro_invocation(id) {
+                Some(expansion) => expansion.source_file().clone(),
+                None => SourceFileNode::parse(""),
+            },
+        }
+    }
+}
+
+impl From<FileId> for HirFileId {
+    fn from(file_id: FileId) -> HirFileId {
+        HirFileId::File(file_id)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SourceItemId {
-    file_id: FileId,
+    file_id: HirFileId,
     /// None for the whole file.
     item_id: Option<SourceFileItemId>,
 }
@@ -162,12 +243,12 @@ pub struct SourceItemId {
 /// Maps item's `SyntaxNode`s to `SourceFileItemId` and back.
 #[derive(Debug, PartialEq, Eq)]
 pub struct SourceFileItems {
-    file_id: FileId,
+    file_id: HirFileId,
     arena: Arena<SyntaxNode>,
 }
 
 impl SourceFileItems {
-    fn new(file_id: FileId) -> SourceFileItems {
+    fn new(file_id: HirFileId) -> SourceFileItems {
         SourceFileItems {
             file_id,
             arena: Arena::default(),
@@ -177,7 +258,7 @@ impl SourceFileItems {
     fn alloc(&mut self, item: SyntaxNode) -> SourceFileItemId {
         self.arena.alloc(item)
     }
-    pub fn id_of(&self, file_id: FileId, item: SyntaxNodeRef) -> SourceFileItemId {
+    pub fn id_of(&self, file_id: HirFileId, item: SyntaxNodeRef) -> SourceFileItemId {
         assert_eq!(
             self.file_id, file_id,
             "SourceFileItems: wrong file, expected {:?}, got {:?}",
@@ -0,0 +1,185 @@
+/// Machinery for `macro_rules!` expansion.
+///
+/// A `MacroDef` is a parsed `macro_rules!` definition. Expanding a call with
+/// it produces a `MacroExpansion`: the reparsed result of the expansion and a
+/// mapping between the tokens of the call and of the expansion.
+use std::sync::Arc;
+
+use ra_syntax::{
+    ast, AstNode, SourceFileNode, TextRange, TextUnit,
+    SyntaxKind::{SOURCE_FILE, ITEM_LIST},
+};
+use ra_db::{LocationIntener, CrateId};
+use ra_macros::MacroRules;
+
+use crate::{
+    HirDatabase, SourceItemId, HirFileId,
+    name::Name,
+    module::ModuleId,
+};
+
+/// Macro expansions deeper than this are not performed, to guard against
+/// infinitely recursive macros.
+pub(crate) const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// At most this many macro calls are expanded while collecting the items of a
+/// module, and as many again by the name resolution of the whole crate. The
+/// depth limit alone doesn't stop macros which expand to several calls of
+/// themselves from growing exponentially.
+pub(crate) const MAX_EXPANSIONS: usize = 1024;
+
+/// `MacroCallId` identifies a particular macro invocation, like
+/// `println!("Hello, {}", world)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacroCallId(u32);
+ra_db::impl_numeric_id!(MacroCallId);
+
+/// The location of a macro call, together with the `macro_rules!` definition
+/// it was resolved to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MacroCallLoc {
+    pub(crate) krate: CrateId,
+    pub(crate) module_id: ModuleId,
+    pub(crate) source_item_id: SourceItemId,
+    pub(crate) def: SourceItemId,
+}
+
+impl MacroCallId {
+    pub(crate) fn loc(
+        self,
+        db: &impl AsRef<LocationIntener<MacroCallLoc, MacroCallId>>,
+    ) -> MacroCallLoc {
+        db.as_ref().id2loc(self)
+    }
+}
+
+impl MacroCallLoc {
+    pub(crate) fn id(
+        &self,
+        db: &impl AsRef<LocationIntener<MacroCallLoc, MacroCallId>>,
+    ) -> MacroCallId {
+        db.as_ref().loc2id(&self)
+    }
+}
+
+pub(crate) fn macro_def(db: &impl HirDatabase, def: SourceItemId) -> Option<Arc<MacroDef>> {
+    let syntax = db.file_item(def);
+    let macro_call = ast::MacroCall::cast(syntax.borrowed())?;
+    MacroDef::from_ast(macro_call).map(Arc::new)
+}
+
+pub(crate) fn expand_macro_invocation(
+    db: &impl HirDatabase,
+    invoc: MacroCallId,
+) -> Option<Arc<MacroExpansion>> {
+    let loc = invoc.loc(db);
+    let def = db.macro_def(loc.def)?;
+    let syntax = db.file_item(loc.source_item_id);
+    let macro_call = ast::MacroCall::cast(syntax.borrowed())?;
+    def.expand(macro_call).map(Arc::new)
+}
+
+/// The number of macro expansions `file_id` is nested in.
+pub(crate) fn expansion_depth(db: &impl HirDatabase, mut file_id: HirFileId) -> usize {
+    let mut depth = 0;
+    while let HirFileId::Macro(id) = file_id {
+        depth += 1;
+        file_id = id.loc(db).source_item_id.file_id;
+    }
+    depth
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MacroDef {
+    name: Name,
+    rules: MacroRules,
+}
+
+impl MacroDef {
+    /// Parses a `macro_rules! name { ... }` item.
+    pub fn from_ast(def: ast::MacroCall) -> Option<MacroDef> {
+        let name = Name::new(def.macro_rules_name()?.leaf_text()?.clone());
+        let (tt, _) = ra_macros::ast_to_token_tree(def.token_tree()?)?;
+        let rules = MacroRules::parse(&tt)?;
+        Some(MacroDef { name, rules })
+    }
+
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    pub fn expand(&self, call: ast::MacroCall) -> Option<MacroExpansion> {
+        let (input, token_map) = ra_macros::ast_to_token_tree(call.token_tree()?)?;
+        let expanded = self.rules.expand(&input)?;
+        let (text, ranges) = ra_macros::token_tree_to_text(&expanded);
+
+        // Macros in item position expand to items, everything else is parsed
+        // in the context of a function body.
+        let in_item_position = match call.syntax().parent() {
+            None => true,
+            Some(parent) => parent.kind() == SOURCE_FILE || parent.kind() == ITEM_LIST,
+        };
+        let (prefix, suffix) = if in_item_position {
+            ("", "")
+        } else {
+            ("fn __expansion() { ", " }")
+        };
+        let source_file = SourceFileNode::parse(&format!("{}{}{}", prefix, text, suffix));
+        let shift = TextUnit::of_str(prefix);
+        let ranges_map = ranges
+            .into_iter()
+            .filter_map(|(id, range)| Some((token_map.range_of(id)?, range + shift)))
+            .collect();
+        Some(MacroExpansion {
+            source_file,
+            ranges_map,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MacroExpansion {
+    source_file: SourceFileNode,
+    /// Pairs of (range in the call, range in the expansion) of the tokens
+    /// the expansion copied from the call.
+    ranges_map: Vec<(TextRange, TextRange)>,
+}
+
+impl MacroExpansion {
+    pub fn source_file(&self) -> &SourceFileNode {
+        &self.source_file
+    }
+
+    pub fn map_range_back(&self, tgt_range: TextRange) -> Option<TextRange> {
+        let pairs = self.ranges_map.iter().map(|&(s, t)| (t, s));
+        map_range(pairs, tgt_range)
+    }
+
+    pub fn map_range_forward(&self, src_range: TextRange) -> Option<TextRange> {
+        map_range(self.ranges_map.iter().cloned(), src_range)
+    }
+}
+
+/// Maps `range` through `(from, to)` pairs of token ranges. The range must
+/// either lie inside a single token or start and end at token boundaries.
+fn map_range(
+    pairs: impl Iterator<Item = (TextRange, TextRange)> + Clone,
+    range: TextRange,
+) -> Option<TextRange> {
+    for (from, to) in pairs.clone() {
+        if range.is_subrange(&from) {
+            let offset = range.start() - from.start();
+            return Some(TextRange::offset_len(to.start() + offset, range.len()));
+        }
+    }
+    let start = pairs
+        .clone()
+        .find(|(from, _)| from.start() == range.start())?
+        .1
+        .start();
+    let end = pairs.clone().find(|(from, _)| from.end() == range.end())?.1.end();
+    if start > end {
+        return None;
+    }
+    Some(TextRange::from_to(start, end))
+}
@@ -2,11 +2,14 @@ use std::sync::Arc;
 
 use parking_lot::Mutex;
 use salsa::{self, Database};
-use ra_db::{LocationIntener, BaseDatabase, FilePosition, FileId, CrateGraph, SourceRoot, SourceRootId};
-use relative_path::RelativePathBuf;
+use ra_db::{
+    LocationIntener, BaseDatabase, FilePosition, FileId, CrateGraph, CfgOptions, SourceRoot,
+    SourceRootId,
+};
+use relative_path::{RelativePath, RelativePathBuf};
 use test_utils::{parse_fixture, CURSOR_MARKER, extract_offset};
 
-use crate::{db, DefId, DefLoc};
+use crate::{db, DefId, DefLoc, MacroCallId, MacroCallLoc};
 
 pub const WORKSPACE: SourceRootId = SourceRootId(0);
 
@@ -30,6 +33,11 @@ impl MockDatabase {
         let file_id = db.add_file(&mut source_root, "/main.rs", text);
         db.query_mut(ra_db::SourceRootQuery)
             .set(WORKSPACE, Arc::new(source_root.clone()));
+
+        let mut crate_graph = CrateGraph::default();
+        crate_graph.add_crate_root(file_id, CfgOptions::default());
+        db.set_crate_graph(crate_graph);
+
         (db, source_root, file_id)
     }
 
@@ -63,6 +71,16 @@ impl MockDatabase {
         }
         db.query_mut(ra_db::SourceRootQuery)
             .set(WORKSPACE, Arc::new(source_root.clone()));
+
+        // `/lib.rs` and `/main.rs` are treated as roots of separate crates
+        let mut crate_graph = CrateGraph::default();
+        for path in ["lib.rs", "main.rs"].iter() {
+            if let Some(&file_id) = source_root.files.get(RelativePath::new(path)) {
+                crate_graph.add_crate_root(file_id, CfgOptions::default());
+            }
+        }
+        db.set_crate_graph(crate_graph);
+
         (db, source_root, position)
     }
 
@@ -95,6 +113,7 @@ impl MockDatabase {
 #[derive(Debug, Default)]
 struct IdMaps {
     defs: LocationIntener<DefLoc, DefId>,
+    macros: LocationIntener<MacroCallLoc, MacroCallId>,
 }
 
 impl salsa::Database for MockDatabase {
@@ -145,6 +164,12 @@ impl AsRef<LocationIntener<DefLoc, DefId>> for MockDatabase {
     }
 }
 
+impl AsRef<LocationIntener<MacroCallLoc, MacroCallId>> for MockDatabase {
+    fn as_ref(&self) -> &LocationIntener<MacroCallLoc, MacroCallId> {
+        &self.id_maps.macros
+    }
+}
+
 impl MockDatabase {
     pub(crate) fn log(&self, f: impl FnOnce()) -> Vec<salsa::Event<MockDatabase>> {
         *self.events.lock() = Some(Vec::new());
@@ -183,10 +208,17 @@ salsa::database_storage! {
             fn file_lines() for ra_db::FileLinesQuery;
         }
         impl db::HirDatabase {
+            fn hir_source_file() for db::HirSourceFileQuery;
+            fn macro_def() for db::MacroDefQuery;
+            fn expand_macro_invocation() for db::ExpandMacroInvocationQuery;
             fn module_tree() for db::ModuleTreeQuery;
             fn fn_scopes() for db::FnScopesQuery;
+            fn fn_signature() for db::FnSignatureQuery;
+            fn body_hir() for db::BodyHirQuery;
+            fn body_syntax_mapping() for db::BodySyntaxMappingQuery;
             fn file_items() for db::SourceFileItemsQuery;
             fn file_item() for db::FileItemQuery;
+            fn source_root_crates() for db::SourceRootCratesQuery;
             fn input_module_items() for db::InputModuleItemsQuery;
             fn item_map() for db::ItemMapQuery;
             fn submodules() for db::SubmodulesQuery;
@@ -195,6 +227,14 @@ salsa::database_storage! {
             fn type_for_field() for db::TypeForFieldQuery;
             fn struct_data() for db::StructDataQuery;
             fn enum_data() for db::EnumDataQuery;
+            fn enum_variant_data() for db::EnumVariantDataQuery;
+            fn type_alias_data() for db::TypeAliasDataQuery;
+            fn const_data() for db::ConstDataQuery;
+            fn implements() for db::ImplementsQuery;
+            fn generic_params() for db::GenericParamsQuery;
+            fn trait_data() for db::TraitDataQuery;
+            fn impls_in_module() for db::ImplsInModuleQuery;
+            fn impls_in_crate() for db::ImplsInCrateQuery;
         }
     }
 }
@@ -9,36 +9,36 @@ use ra_syntax::{
     ast::{self, AstNode, NameOwner},
     SyntaxNode,
 };
-use ra_db::{SourceRootId, FileId, Cancelable};
+use ra_db::{CrateId, FileId, Cancelable};
 use relative_path::RelativePathBuf;
 
 use crate::{
     Def, DefKind, DefLoc, DefId,
-    Name, Path, PathKind, HirDatabase, SourceItemId, SourceFileItemId, Crate,
+    Name, Path, PathKind, HirDatabase, SourceItemId, HirFileId, SourceFileItemId, Crate, Trait,
     arena::{Arena, Id},
 };
 
-pub use self::nameres::{ModuleScope, Resolution, Namespace, PerNs};
+pub use self::nameres::{ModuleScope, Resolution, Namespace, PerNs, Visibility};
 
 /// `Module` is API entry point to get all the information
 /// about a particular module.
 #[derive(Debug, Clone)]
 pub struct Module {
     tree: Arc<ModuleTree>,
-    pub(crate) source_root_id: SourceRootId,
+    pub(crate) krate: CrateId,
     pub(crate) module_id: ModuleId,
 }
 
 impl Module {
     pub(super) fn new(
         db: &impl HirDatabase,
-        source_root_id: SourceRootId,
+        krate: CrateId,
         module_id: ModuleId,
     ) -> Cancelable<Module> {
-        let module_tree = db.module_tree(source_root_id)?;
+        let module_tree = db.module_tree(krate)?;
         let res = Module {
             tree: module_tree,
-            source_root_id,
+            krate,
             module_id,
         };
         Ok(res)
@@ -67,12 +67,8 @@ impl Module {
     }
 
     /// Returns the crate this module is part of.
-    pub fn krate(&self, db: &impl HirDatabase) -> Option<Crate> {
-        let root_id = self.module_id.crate_root(&self.tree);
-        let file_id = root_id.source(&self.tree).file_id();
-        let crate_graph = db.crate_graph();
-        let crate_id = crate_graph.crate_id_for_crate_root(file_id)?;
-        Some(Crate::new(crate_id))
+    pub fn krate(&self) -> Crate {
+        Crate::new(self.krate)
     }
 
     /// The root of the tree this module is part of
@@ -93,7 +89,7 @@ impl Module {
     pub fn def_id(&self, db: &impl HirDatabase) -> DefId {
         let def_loc = DefLoc {
             kind: DefKind::Module,
-            source_root_id: self.source_root_id,
+            krate: self.krate,
             module_id: self.module_id,
             source_item_id: self.module_id.source(&self.tree).0,
         };
@@ -111,29 +107,89 @@ impl Module {
 
     /// Returns a `ModuleScope`: a set of items, visible in this module.
     pub fn scope(&self, db: &impl HirDatabase) -> Cancelable<ModuleScope> {
-        let item_map = db.item_map(self.source_root_id)?;
+        let item_map = db.item_map(self.krate)?;
         let res = item_map.per_module[&self.module_id].clone();
         Ok(res)
     }
 
+    /// Whether the scope of this module may be incomplete because of glob
+    /// imports, which we don't resolve yet.
+    pub(crate) fn has_glob_imports(&self, db: &impl HirDatabase) -> Cancelable<bool> {
+        let items = db.input_module_items(self.krate, self.module_id)?;
+        Ok(items.has_glob_imports())
+    }
+
+    /// Whether the prelude of the standard library is in scope and
+    /// completely resolved, see `ItemMap::has_complete_prelude`.
+    pub(crate) fn has_complete_prelude(&self, db: &impl HirDatabase) -> Cancelable<bool> {
+        Ok(db.item_map(self.krate)?.has_complete_prelude)
+    }
+
+    /// Returns the traits whose methods can be called in this module: those
+    /// defined in or imported into it, including the ones from the prelude.
+    pub fn traits_in_scope(&self, db: &impl HirDatabase) -> Cancelable<Vec<Trait>> {
+        let scope = self.scope(db)?;
+        let mut res = Vec::new();
+        for (_, resolution) in scope.entries() {
+            if let Some(def_id) = resolution.def_id.types {
+                if let Def::Trait(t) = def_id.resolve(db)? {
+                    res.push(t);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Finds the `macro_rules!` definition a macro call of `path` in this
+    /// module refers to, see `ItemMap::find_macro`.
+    pub(crate) fn resolve_macro(
+        &self,
+        db: &impl HirDatabase,
+        path: &Path,
+    ) -> Cancelable<Option<SourceItemId>> {
+        let item_map = db.item_map(self.krate)?;
+        Ok(item_map.find_macro(&self.tree, self.module_id, path))
+    }
+
     pub fn resolve_path(&self, db: &impl HirDatabase, path: &Path) -> Cancelable<PerNs<DefId>> {
-        let mut curr_per_ns = PerNs::types(
-            match path.kind {
-                PathKind::Crate => self.crate_root(),
-                PathKind::Self_ | PathKind::Plain => self.clone(),
-                PathKind::Super => {
-                    if let Some(p) = self.parent() {
-                        p
-                    } else {
-                        return Ok(PerNs::none());
-                    }
+        self.resolve_path_from(db, self, path)
+    }
+
+    /// Resolves `path` relative to this module, skipping the items which are
+    /// not visible from `from`.
+    pub(crate) fn resolve_path_from(
+        &self,
+        db: &impl HirDatabase,
+        from: &Module,
+        path: &Path,
+    ) -> Cancelable<PerNs<DefId>> {
+        let mut segments = &path.segments[..];
+        let start = match path.kind {
+            PathKind::Crate => self.crate_root().def_id(db),
+            PathKind::Self_ | PathKind::Plain => self.def_id(db),
+            PathKind::Super => {
+                if let Some(p) = self.parent() {
+                    p.def_id(db)
+                } else {
+                    return Ok(PerNs::none());
+                }
+            }
+            PathKind::Abs => {
+                let (krate, rest) = match segments.split_first() {
+                    Some(it) => it,
+                    None => return Ok(PerNs::none()),
+                };
+                segments = rest;
+                let item_map = db.item_map(self.krate)?;
+                match item_map.extern_prelude.get(&krate.name) {
+                    Some(&def_id) => def_id,
+                    None => return Ok(PerNs::none()),
                 }
             }
-            .def_id(db),
-        );
+        };
+        let mut curr_per_ns = PerNs::types(start);
 
-        let segments = &path.segments;
-        for name in segments.iter() {
+        for segment in segments.iter() {
             let curr = if let Some(r) = curr_per_ns.as_ref().take(Namespace::Types) {
                 r
             } else {
@@ -141,14 +197,20 @@ impl Module {
             };
             let module = match curr.resolve(db)? {
                 Def::Module(it) => it,
-                // TODO here would be the place to handle enum variants...
+                Def::Enum(e) => {
+                    // enum variants live in both namespaces
+                    curr_per_ns = match e.variant(db, &segment.name)? {
+                        Some(variant) => PerNs::both(variant.def_id(), variant.def_id()),
+                        None => return Ok(PerNs::none()),
+                    };
+                    continue;
+                }
                 _ => return Ok(PerNs::none()),
             };
             let scope = module.scope(db)?;
-            curr_per_ns = if let Some(r) = scope.get(&name) {
-                r.def_id
-            } else {
-                return Ok(PerNs::none());
+            curr_per_ns = match scope.get(&segment.name) {
+                Some(r) if r.vis.is_visible_from(from) => r.def_id,
+                _ => return Ok(PerNs::none()),
             };
         }
         Ok(curr_per_ns)
@@ -182,6 +244,12 @@ impl ModuleTree {
     ) -> impl Iterator<Item = (ModuleId, ModuleSource)> + 'a {
         self.mods.iter().map(|(id, m)| (id, m.source))
     }
+
+    /// The module for the crate root. It is always the first one to be
+    /// allocated.
+    pub(crate) fn root(&self) -> ModuleId {
+        self.modules().next().unwrap()
+    }
 }
 
 /// `ModuleSource` is the syntax tree element that produced this module:
@@ -221,6 +289,10 @@ impl ModuleId {
         let link = self.parent_link(tree)?;
         Some(tree.links[link].owner)
     }
+    /// Whether `module` is this module or one of its descendants.
+    fn is_ancestor_of(self, tree: &ModuleTree, module: ModuleId) -> bool {
+        generate(Some(module), move |it| it.parent(tree)).any(|it| it == self)
+    }
     fn crate_root(self, tree: &ModuleTree) -> ModuleId {
         generate(Some(self), move |it| it.parent(tree))
             .last()
@@ -287,7 +359,10 @@ pub struct ModuleData {
 impl ModuleSource {
     // precondition: item_id **must** point to module
     fn new(file_id: FileId, item_id: Option<SourceFileItemId>) -> ModuleSource {
-        let source_item_id = SourceItemId { file_id, item_id };
+        let source_item_id = SourceItemId {
+            file_id: file_id.into(),
+            item_id,
+        };
         ModuleSource(source_item_id)
     }
 
@@ -301,13 +376,16 @@ impl ModuleSource {
         m: ast::Module,
     ) -> ModuleSource {
         assert!(!m.has_semi());
-        let file_items = db.file_items(file_id);
-        let item_id = file_items.id_of(file_id, m.syntax());
+        let file_items = db.file_items(file_id.into());
+        let item_id = file_items.id_of(file_id.into(), m.syntax());
         ModuleSource::new(file_id, Some(item_id))
     }
 
     pub fn file_id(self) -> FileId {
-        self.0.file_id
+        match self.0.file_id {
+            HirFileId::File(file_id) => file_id,
+            HirFileId::Macro(_) => panic!("modules defined by macros are not supported"),
+        }
     }
 
     pub(crate) fn resolve(self, db: &impl HirDatabase) -> ModuleSourceNode {
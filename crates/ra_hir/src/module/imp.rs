@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use ra_syntax::ast::{self, NameOwner};
-use relative_path::RelativePathBuf;
-use rustc_hash::{FxHashMap, FxHashSet};
+use ra_syntax::{SmolStr, ast::{self, NameOwner}};
+use relative_path::{RelativePath, RelativePathBuf};
+use rustc_hash::FxHashSet;
 use arrayvec::ArrayVec;
-use ra_db::{SourceRoot, SourceRootId, Cancelable, FileId};
+use ra_db::{CrateId, Cancelable, FileId};
 
 use crate::{
     HirDatabase, Name, AsName,
@@ -16,16 +16,51 @@ use super::{
 };
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
-pub enum Submodule {
-    Declaration(Name),
-    Definition(Name, ModuleSource),
+pub struct Submodule {
+    pub(crate) name: Name,
+    /// The value of the `#[path = "..."]` attribute.
+    pub(crate) path: Option<SmolStr>,
+    /// The module itself for inline modules, `None` for `mod foo;`.
+    pub(crate) source: Option<ModuleSource>,
 }
 
-impl Submodule {
-    fn name(&self) -> &Name {
-        match self {
-            Submodule::Declaration(name) => name,
-            Submodule::Definition(name, _) => name,
+/// The directories the files of the submodules of a module live in.
+#[derive(Debug, Clone)]
+struct ModuleDir {
+    /// Where `mod foo;` and inline modules look for their files.
+    dir: RelativePathBuf,
+    /// Where `#[path] mod foo;` looks for its file.
+    attr_dir: RelativePathBuf,
+}
+
+impl ModuleDir {
+    /// Crate roots, `mod.rs` files and files loaded with `#[path]` own their
+    /// directory: the submodules of `foo.rs` live in `foo/` instead.
+    fn for_file(db: &impl HirDatabase, file_id: FileId, is_dir_owner: bool) -> ModuleDir {
+        let path = db.file_relative_path(file_id);
+        let attr_dir = path
+            .parent()
+            .map(RelativePath::to_relative_path_buf)
+            .unwrap_or_default();
+        let mod_name = path.file_stem().unwrap_or("unknown");
+        let is_dir_owner =
+            is_dir_owner || mod_name == "mod" || mod_name == "lib" || mod_name == "main";
+        let dir = if is_dir_owner {
+            attr_dir.clone()
+        } else {
+            attr_dir.join(mod_name)
+        };
+        ModuleDir { dir, attr_dir }
+    }
+
+    fn for_inline(&self, submodule: &Submodule) -> ModuleDir {
+        let dir = match &submodule.path {
+            Some(path) => self.dir.join(path.as_str()).normalize(),
+            None => self.dir.join(submodule.name.to_string()),
+        };
+        ModuleDir {
+            attr_dir: dir.clone(),
+            dir,
         }
     }
 }
@@ -46,51 +81,32 @@ pub(crate) fn modules<'a>(
 
 pub(crate) fn module_tree(
     db: &impl HirDatabase,
-    source_root: SourceRootId,
+    krate: CrateId,
 ) -> Cancelable<Arc<ModuleTree>> {
     db.check_canceled()?;
-    let res = create_module_tree(db, source_root)?;
+    let res = create_module_tree(db, krate)?;
     Ok(Arc::new(res))
 }
 
-fn create_module_tree<'a>(
-    db: &impl HirDatabase,
-    source_root: SourceRootId,
-) -> Cancelable<ModuleTree> {
+fn create_module_tree(db: &impl HirDatabase, krate: CrateId) -> Cancelable<ModuleTree> {
     let mut tree = ModuleTree::default();
-
-    let mut roots = FxHashMap::default();
     let mut visited = FxHashSet::default();
 
-    let source_root = db.source_root(source_root);
-    for &file_id in source_root.files.values() {
-        let source = ModuleSource::new_file(file_id);
-        if visited.contains(&source) {
-            continue; // TODO: use explicit crate_roots here
-        }
-        assert!(!roots.contains_key(&file_id));
-        let module_id = build_subtree(
-            db,
-            &source_root,
-            &mut tree,
-            &mut visited,
-            &mut roots,
-            None,
-            source,
-        )?;
-        roots.insert(file_id, module_id);
-    }
+    let file_id = db.crate_graph().crate_root(krate);
+    let source = ModuleSource::new_file(file_id);
+    let dir = ModuleDir::for_file(db, file_id, true);
+    build_subtree(db, krate, &mut tree, &mut visited, None, source, &dir)?;
     Ok(tree)
 }
 
 fn build_subtree(
     db: &impl HirDatabase,
-    source_root: &SourceRoot,
+    krate: CrateId,
     tree: &mut ModuleTree,
     visited: &mut FxHashSet<ModuleSource>,
-    roots: &mut FxHashMap<FileId, ModuleId>,
     parent: Option<LinkId>,
     source: ModuleSource,
+    dir: &ModuleDir,
 ) -> Cancelable<ModuleId> {
     visited.insert(source);
     let id = tree.push_mod(ModuleData {
@@ -98,47 +114,35 @@ fn build_subtree(
         parent,
         children: Vec::new(),
     });
-    for sub in db.submodules(source)?.iter() {
+    for sub in db.submodules(krate, source)?.iter() {
         let link = tree.push_link(LinkData {
-            name: sub.name().clone(),
+            name: sub.name.clone(),
             owner: id,
             points_to: Vec::new(),
             problem: None,
         });
 
-        let (points_to, problem) = match sub {
-            Submodule::Declaration(name) => {
-                let (points_to, problem) = resolve_submodule(db, source, &name);
+        let (points_to, problem) = match sub.source {
+            None => {
+                let (points_to, problem) = resolve_submodule(db, source, dir, sub);
                 let points_to = points_to
                     .into_iter()
-                    .map(|file_id| match roots.remove(&file_id) {
-                        Some(module_id) => {
-                            tree.mods[module_id].parent = Some(link);
-                            Ok(module_id)
+                    .filter_map(|file_id| {
+                        let source = ModuleSource::new_file(file_id);
+                        // a module file can't be its own (transitive) submodule
+                        if visited.contains(&source) {
+                            return None;
                         }
-                        None => build_subtree(
-                            db,
-                            source_root,
-                            tree,
-                            visited,
-                            roots,
-                            Some(link),
-                            ModuleSource::new_file(file_id),
-                        ),
+                        let dir = ModuleDir::for_file(db, file_id, sub.path.is_some());
+                        Some(build_subtree(db, krate, tree, visited, Some(link), source, &dir))
                     })
                     .collect::<Cancelable<Vec<_>>>()?;
                 (points_to, problem)
             }
-            Submodule::Definition(_name, submodule_source) => {
-                let points_to = build_subtree(
-                    db,
-                    source_root,
-                    tree,
-                    visited,
-                    roots,
-                    Some(link),
-                    *submodule_source,
-                )?;
+            Some(submodule_source) => {
+                let dir = dir.for_inline(sub);
+                let points_to =
+                    build_subtree(db, krate, tree, visited, Some(link), submodule_source, &dir)?;
                 (vec![points_to], None)
             }
         };
@@ -152,36 +156,28 @@ fn build_subtree(
 fn resolve_submodule(
     db: &impl HirDatabase,
     source: ModuleSource,
-    name: &Name,
+    dir: &ModuleDir,
+    submodule: &Submodule,
 ) -> (Vec<FileId>, Option<Problem>) {
-    // FIXME: handle submodules of inline modules properly
-    let file_id = source.file_id();
-    let source_root_id = db.file_source_root(file_id);
-    let path = db.file_relative_path(file_id);
-    let root = RelativePathBuf::default();
-    let dir_path = path.parent().unwrap_or(&root);
-    let mod_name = path.file_stem().unwrap_or("unknown");
-    let is_dir_owner = mod_name == "mod" || mod_name == "lib" || mod_name == "main";
-
-    let file_mod = dir_path.join(format!("{}.rs", name));
-    let dir_mod = dir_path.join(format!("{}/mod.rs", name));
-    let file_dir_mod = dir_path.join(format!("{}/{}.rs", mod_name, name));
+    let source_root_id = db.file_source_root(source.file_id());
     let mut candidates = ArrayVec::<[_; 2]>::new();
-    if is_dir_owner {
-        candidates.push(file_mod.clone());
-        candidates.push(dir_mod);
-    } else {
-        candidates.push(file_dir_mod.clone());
-    };
+    match &submodule.path {
+        Some(path) => candidates.push(dir.attr_dir.join(path.as_str()).normalize()),
+        None => {
+            let name = &submodule.name;
+            candidates.push(dir.dir.join(format!("{}.rs", name)));
+            candidates.push(dir.dir.join(format!("{}/mod.rs", name)));
+        }
+    }
     let sr = db.source_root(source_root_id);
     let points_to = candidates
-        .into_iter()
-        .filter_map(|path| sr.files.get(&path))
-        .map(|&it| it)
+        .iter()
+        .filter_map(|path| sr.files.get(path))
+        .cloned()
         .collect::<Vec<_>>();
     let problem = if points_to.is_empty() {
         Some(Problem::UnresolvedModule {
-            candidate: if is_dir_owner { file_mod } else { file_dir_mod },
+            candidate: candidates[0].clone(),
         })
     } else {
         None
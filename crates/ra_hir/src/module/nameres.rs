@@ -14,37 +14,83 @@
 //! modifications (that is, typing inside a function shold not change IMIs),
 //! such that the results of name resolution can be preserved unless the module
 //! structure itself is modified.
+//!
+//! Module-level macro calls are expanded while computing `InputModuleItems`,
+//! if the macro is defined earlier in the same module. Other calls are
+//! expanded by the resolver, which alternates between resolving imports and
+//! expanding macros until nothing changes.
 use std::sync::Arc;
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use ra_syntax::{
-    TextRange,
+    TextRange, SyntaxNodeRef,
     SyntaxKind::{self, *},
-    ast::{self, AstNode}
+    ast::{self, AstNode, AttrsOwner, NameOwner, VisibilityOwner}
 };
-use ra_db::SourceRootId;
 
 use crate::{
-    Cancelable, FileId,
+    Cancelable,
     DefId, DefLoc, DefKind,
-    SourceItemId, SourceFileItemId, SourceFileItems,
+    SourceItemId, SourceFileItemId, SourceFileItems, HirFileId,
     Path, PathKind,
-    HirDatabase, Crate,
+    HirDatabase, Crate, CrateId, Enum,
     Name, AsName,
+    MacroCallLoc,
+    cfg,
+    macros::{expansion_depth, MAX_EXPANSION_DEPTH, MAX_EXPANSIONS},
     module::{Module, ModuleId, ModuleTree},
+    path::PathSegment,
 };
 
 /// Item map is the result of the name resolution. Item map contains, for each
 /// module, the set of visible items.
-// FIXME: currenty we compute item map per source-root. We should do it per crate instead.
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct ItemMap {
     pub per_module: FxHashMap<ModuleId, ModuleScope>,
+    /// Root modules of the crates which `::foo` paths can refer to: the
+    /// dependencies and the `extern crate` items of the crate root.
+    pub(crate) extern_prelude: FxHashMap<Name, DefId>,
+    /// Whether the prelude of `std` or `core` was found and has no glob
+    /// imports. Otherwise, we can't tell whether a name which isn't in scope
+    /// is unresolved.
+    pub(crate) has_complete_prelude: bool,
+    /// Exported macros of the crates imported with `#[macro_use]`.
+    pub(crate) macro_use: FxHashMap<Name, SourceItemId>,
+}
+
+impl ItemMap {
+    /// Finds the `macro_rules!` definition for a macro call in `module_id`.
+    /// Macros defined in the module itself or in any of its ancestors are
+    /// visible, regardless of the textual order, as well as the macros
+    /// imported with `#[macro_use] extern crate`.
+    pub(crate) fn find_macro(
+        &self,
+        module_tree: &ModuleTree,
+        module_id: ModuleId,
+        path: &Path,
+    ) -> Option<SourceItemId> {
+        let name = path.as_ident()?;
+        let mut module = Some(module_id);
+        while let Some(module_id) = module {
+            let def = self
+                .per_module
+                .get(&module_id)
+                .and_then(|scope| scope.macros.get(name));
+            if let Some(&def) = def {
+                return Some(def);
+            }
+            module = module_id.parent(module_tree);
+        }
+        self.macro_use.get(name).cloned()
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct ModuleScope {
     items: FxHashMap<Name, Resolution>,
+    /// The `macro_rules!` definitions of the module, including the ones from
+    /// macro expansions; the last one wins if a name is defined twice.
+    macros: FxHashMap<Name, SourceItemId>,
 }
 
 impl ModuleScope {
@@ -66,38 +112,129 @@ impl ModuleScope {
 pub struct InputModuleItems {
     items: Vec<ModuleItem>,
     imports: Vec<Import>,
+    /// `macro_rules!` definitions, in textual order.
+    macro_defs: Vec<MacroDefItem>,
+    /// Macro calls which could not be expanded using the definitions from
+    /// this module.
+    macro_calls: Vec<MacroCallItem>,
+    extern_crates: Vec<ExternCrateItem>,
+    /// The number of macro calls expanded so far, see `MAX_EXPANSIONS`.
+    expansions: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 struct ModuleItem {
-    id: SourceFileItemId,
+    id: SourceItemId,
     name: Name,
     kind: SyntaxKind,
-    vis: Vis,
+    vis: RawVisibility,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct MacroDefItem {
+    id: SourceItemId,
+    name: Name,
+    /// Whether the macro is `#[macro_export]`ed, and thus available to the
+    /// `#[macro_use] extern crate` items of other crates.
+    exported: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
-enum Vis {
-    // Priv,
-    Other,
+struct MacroCallItem {
+    id: SourceItemId,
+    path: Path,
+}
+
+/// `extern crate name as alias;`
+#[derive(Debug, PartialEq, Eq)]
+struct ExternCrateItem {
+    name: Name,
+    alias: Name,
+    vis: RawVisibility,
+    macro_use: bool,
+}
+
+/// Visibility as written in the source. Private items are `pub(self)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RawVisibility {
+    Public,
+    /// Visible in the module the path points to, and in its descendants.
+    Restricted(Path),
+}
+
+impl RawVisibility {
+    fn private() -> RawVisibility {
+        RawVisibility::restricted(PathKind::Self_)
+    }
+
+    fn restricted(kind: PathKind) -> RawVisibility {
+        RawVisibility::Restricted(Path {
+            kind,
+            segments: Vec::new(),
+        })
+    }
+
+    fn from_ast(vis: Option<ast::Visibility>) -> RawVisibility {
+        let vis = match vis {
+            Some(it) => it,
+            None => return RawVisibility::private(),
+        };
+        match vis.kind() {
+            ast::VisibilityKind::Pub => RawVisibility::Public,
+            ast::VisibilityKind::PubCrate => RawVisibility::restricted(PathKind::Crate),
+            ast::VisibilityKind::PubSuper => RawVisibility::restricted(PathKind::Super),
+            ast::VisibilityKind::PubSelf => RawVisibility::private(),
+            ast::VisibilityKind::PubIn(path) => match Path::from_ast(path) {
+                Some(path) => RawVisibility::Restricted(path),
+                None => RawVisibility::private(),
+            },
+        }
+    }
+}
+
+/// Visibility of a name in a module's scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    /// Visible in `module_id` of `krate` and in its descendants.
+    Module { krate: CrateId, module_id: ModuleId },
+}
+
+impl Visibility {
+    /// Whether a name with this visibility can be referred to from `module`.
+    pub fn is_visible_from(&self, module: &Module) -> bool {
+        self.is_visible_in(module.krate, &module.tree, module.module_id)
+    }
+
+    fn is_visible_in(&self, from_krate: CrateId, tree: &ModuleTree, from: ModuleId) -> bool {
+        match *self {
+            Visibility::Public => true,
+            Visibility::Module { krate, module_id } => {
+                krate == from_krate && module_id.is_ancestor_of(tree, from)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Import {
     path: Path,
     kind: ImportKind,
+    vis: RawVisibility,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NamedImport {
+    pub file_id: HirFileId,
     pub file_item_id: SourceFileItemId,
     pub relative_range: TextRange,
 }
 
 impl NamedImport {
-    pub fn range(&self, db: &impl HirDatabase, file_id: FileId) -> TextRange {
+    /// The range of the import in `self.file_id`.
+    pub fn range(&self, db: &impl HirDatabase) -> TextRange {
         let source_item_id = SourceItemId {
-            file_id,
+            file_id: self.file_id,
             item_id: Some(self.file_item_id),
         };
         let syntax = db.file_item(source_item_id);
@@ -120,6 +257,7 @@ pub struct Resolution {
     pub def_id: PerNs<DefId>,
     /// ident by whitch this is imported into local scope.
     pub import: Option<NamedImport>,
+    pub vis: Visibility,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -209,17 +347,55 @@ impl<T> PerNs<T> {
 }
 
 impl InputModuleItems {
+    /// Whether there are glob imports like `use foo::*;`, which we don't
+    /// resolve yet.
+    pub(crate) fn has_glob_imports(&self) -> bool {
+        self.imports
+            .iter()
+            .any(|import| import.kind == ImportKind::Glob)
+    }
+
+    /// Collects the items of `module_id` from `nodes`, the children of the
+    /// module's source file or item list, or of a macro expansion.
     pub(crate) fn new<'a>(
-        file_items: &SourceFileItems,
-        items: impl Iterator<Item = ast::ModuleItem<'a>>,
+        db: &impl HirDatabase,
+        krate: CrateId,
+        module_id: ModuleId,
+        file_id: HirFileId,
+        nodes: impl Iterator<Item = SyntaxNodeRef<'a>>,
     ) -> InputModuleItems {
         let mut res = InputModuleItems::default();
-        for item in items {
-            res.add_item(file_items, item);
-        }
+        res.add_nodes(db, krate, module_id, file_id, nodes);
         res
     }
 
+    fn add_nodes<'a>(
+        &mut self,
+        db: &impl HirDatabase,
+        krate: CrateId,
+        module_id: ModuleId,
+        file_id: HirFileId,
+        nodes: impl Iterator<Item = SyntaxNodeRef<'a>>,
+    ) {
+        let file_items = db.file_items(file_id);
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(krate);
+        for node in nodes {
+            if !cfg::is_enabled(cfg_options, node) {
+                continue;
+            }
+            if let Some(item) = ast::ModuleItem::cast(node) {
+                self.add_item(&file_items, item);
+            } else if let Some(macro_call) = ast::MacroCall::cast(node) {
+                let id = SourceItemId {
+                    file_id,
+                    item_id: Some(file_items.id_of_unchecked(macro_call.syntax())),
+                };
+                self.add_macro_call(db, krate, module_id, id, macro_call);
+            }
+        }
+    }
+
     fn add_item(&mut self, file_items: &SourceFileItems, item: ast::ModuleItem) -> Option<()> {
         match item {
             ast::ModuleItem::StructDef(it) => self.items.push(ModuleItem::new(file_items, it)?),
@@ -231,8 +407,15 @@ impl InputModuleItems {
                 // impls don't define items
             }
             ast::ModuleItem::UseItem(it) => self.add_use_item(file_items, it),
-            ast::ModuleItem::ExternCrateItem(_) => {
-                // TODO
+            ast::ModuleItem::ExternCrateItem(it) => {
+                let name = it.name()?.as_name();
+                let alias = it.alias().and_then(|it| it.name()).map(|it| it.as_name());
+                self.extern_crates.push(ExternCrateItem {
+                    alias: alias.unwrap_or_else(|| name.clone()),
+                    name,
+                    vis: RawVisibility::from_ast(it.visibility()),
+                    macro_use: it.has_atom_attr("macro_use"),
+                })
             }
             ast::ModuleItem::ConstDef(it) => self.items.push(ModuleItem::new(file_items, it)?),
             ast::ModuleItem::StaticDef(it) => self.items.push(ModuleItem::new(file_items, it)?),
@@ -242,27 +425,84 @@ impl InputModuleItems {
     }
 
     fn add_use_item(&mut self, file_items: &SourceFileItems, item: ast::UseItem) {
+        let file_id = file_items.file_id;
         let file_item_id = file_items.id_of_unchecked(item.syntax());
         let start_offset = item.syntax().range().start();
+        let vis = RawVisibility::from_ast(item.visibility());
         Path::expand_use_item(item, |path, range| {
             let kind = match range {
                 None => ImportKind::Glob,
                 Some(range) => ImportKind::Named(NamedImport {
+                    file_id,
                     file_item_id,
                     relative_range: range - start_offset,
                 }),
             };
-            self.imports.push(Import { kind, path })
+            self.imports.push(Import {
+                kind,
+                path,
+                vis: vis.clone(),
+            })
         })
     }
+
+    fn add_macro_call(
+        &mut self,
+        db: &impl HirDatabase,
+        krate: CrateId,
+        module_id: ModuleId,
+        id: SourceItemId,
+        macro_call: ast::MacroCall,
+    ) -> Option<()> {
+        if let Some(name) = macro_call.macro_rules_name() {
+            let name = Name::new(name.leaf_text()?.clone());
+            let exported = macro_call.has_atom_attr("macro_export");
+            self.macro_defs.push(MacroDefItem { id, name, exported });
+            return Some(());
+        }
+        let path = Path::from_ast(macro_call.path()?)?;
+        let def = path
+            .as_ident()
+            .and_then(|name| self.macro_defs.iter().rev().find(|it| it.name == *name));
+        let def = match def {
+            Some(def)
+                if expansion_depth(db, id.file_id) < MAX_EXPANSION_DEPTH
+                    && self.expansions < MAX_EXPANSIONS =>
+            {
+                def.id
+            }
+            _ => {
+                self.macro_calls.push(MacroCallItem { id, path });
+                return Some(());
+            }
+        };
+        self.expansions += 1;
+        let loc = MacroCallLoc {
+            krate,
+            module_id,
+            source_item_id: id,
+            def,
+        };
+        let file_id = HirFileId::Macro(loc.id(db));
+        let source_file = db.hir_source_file(file_id);
+        let nodes = source_file.syntax().children();
+        self.add_nodes(db, krate, module_id, file_id, nodes);
+        Some(())
+    }
 }
 
 impl ModuleItem {
-    fn new<'a>(file_items: &SourceFileItems, item: impl ast::NameOwner<'a>) -> Option<ModuleItem> {
+    fn new<'a>(
+        file_items: &SourceFileItems,
+        item: impl ast::NameOwner<'a> + ast::VisibilityOwner<'a>,
+    ) -> Option<ModuleItem> {
         let name = item.name()?.as_name();
         let kind = item.syntax().kind();
-        let vis = Vis::Other;
-        let id = file_items.id_of_unchecked(item.syntax());
+        let vis = RawVisibility::from_ast(item.visibility());
+        let id = SourceItemId {
+            file_id: file_items.file_id,
+            item_id: Some(file_items.id_of_unchecked(item.syntax())),
+        };
         let res = ModuleItem {
             id,
             name,
@@ -276,9 +516,20 @@ impl ModuleItem {
 pub(crate) struct Resolver<'a, DB> {
     db: &'a DB,
     input: &'a FxHashMap<ModuleId, Arc<InputModuleItems>>,
-    source_root: SourceRootId,
+    /// Items produced by the macro calls which the resolver expanded.
+    expanded: FxHashMap<ModuleId, Vec<InputModuleItems>>,
+    expanded_calls: FxHashSet<SourceItemId>,
+    /// The number of macro calls expanded by the resolver, including the
+    /// calls in their expansions, see `MAX_EXPANSIONS`.
+    expansions: usize,
+    /// Root modules of the dependencies, by the name the crate uses for them.
+    dependencies: FxHashMap<Name, DefId>,
+    krate: CrateId,
     module_tree: Arc<ModuleTree>,
     result: ItemMap,
+    /// Whether the last round of import resolution and macro expansion has
+    /// changed anything.
+    changed: bool,
 }
 
 impl<'a, DB> Resolver<'a, DB>
@@ -288,67 +539,239 @@ where
     pub(crate) fn new(
         db: &'a DB,
         input: &'a FxHashMap<ModuleId, Arc<InputModuleItems>>,
-        source_root: SourceRootId,
+        krate: CrateId,
         module_tree: Arc<ModuleTree>,
     ) -> Resolver<'a, DB> {
         Resolver {
             db,
             input,
-            source_root,
+            expanded: FxHashMap::default(),
+            expanded_calls: FxHashSet::default(),
+            expansions: 0,
+            dependencies: FxHashMap::default(),
+            krate,
             module_tree,
             result: ItemMap::default(),
+            changed: false,
         }
     }
 
     pub(crate) fn resolve(mut self) -> Cancelable<ItemMap> {
+        self.collect_extern_crates()?;
+        let prelude = match self.find_prelude()? {
+            Some(module) => {
+                self.result.has_complete_prelude = !module.has_glob_imports(self.db)?;
+                Some(module.scope(self.db)?)
+            }
+            None => None,
+        };
         for (&module_id, items) in self.input.iter() {
-            self.populate_module(module_id, items)?;
+            self.populate_module(module_id, items, prelude.as_ref())?;
+        }
+
+        loop {
+            self.changed = false;
+            for &module_id in self.input.keys() {
+                self.db.check_canceled()?;
+                self.resolve_imports(module_id)?;
+            }
+            self.expand_macros()?;
+            if !self.changed {
+                break;
+            }
         }
+        Ok(self.result)
+    }
 
+    fn module_inputs(&self, module_id: ModuleId) -> impl Iterator<Item = &InputModuleItems> {
+        let input = self.input.get(&module_id).map(|it| &**it);
+        let expanded = self.expanded.get(&module_id).into_iter().flat_map(|it| it.iter());
+        input.into_iter().chain(expanded)
+    }
+
+    fn expand_macros(&mut self) -> Cancelable<()> {
+        let mut calls = Vec::new();
         for &module_id in self.input.keys() {
+            for call in self.module_inputs(module_id).flat_map(|it| it.macro_calls.iter()) {
+                if self.expanded_calls.contains(&call.id) {
+                    continue;
+                }
+                let def = self
+                    .result
+                    .find_macro(&self.module_tree, module_id, &call.path);
+                if let Some(def) = def {
+                    calls.push((module_id, call.id, def));
+                }
+            }
+        }
+        for (module_id, call_id, def) in calls {
             self.db.check_canceled()?;
-            self.resolve_imports(module_id)?;
+            self.expanded_calls.insert(call_id);
+            self.changed = true;
+            if expansion_depth(self.db, call_id.file_id) >= MAX_EXPANSION_DEPTH
+                || self.expansions >= MAX_EXPANSIONS
+            {
+                continue;
+            }
+            self.expansions += 1;
+            let loc = MacroCallLoc {
+                krate: self.krate,
+                module_id,
+                source_item_id: call_id,
+                def,
+            };
+            let file_id = HirFileId::Macro(loc.id(self.db));
+            let source_file = self.db.hir_source_file(file_id);
+            let nodes = source_file.syntax().children();
+            // Calls in the expansion count towards the resolver's budget.
+            let mut items = InputModuleItems {
+                expansions: self.expansions,
+                ..InputModuleItems::default()
+            };
+            items.add_nodes(self.db, self.krate, module_id, file_id, nodes);
+            self.expansions = items.expansions;
+
+            let mut module_items = self.result.per_module.remove(&module_id).unwrap_or_default();
+            self.add_input_items(&mut module_items, module_id, &items);
+            self.result.per_module.insert(module_id, module_items);
+            self.expanded.entry(module_id).or_default().push(items);
         }
-        Ok(self.result)
+        Ok(())
+    }
+
+
+    fn collect_extern_crates(&mut self) -> Cancelable<()> {
+        let mut macro_use_crates = Vec::new();
+        for dep in Crate::new(self.krate).dependencies(self.db) {
+            if let Some(module) = dep.krate.root_module(self.db)? {
+                self.dependencies.insert(dep.name.clone(), module.def_id(self.db));
+            }
+            let is_macro_use = self
+                .input
+                .values()
+                .flat_map(|it| it.extern_crates.iter())
+                .any(|it| it.macro_use && it.name == dep.name);
+            if is_macro_use {
+                macro_use_crates.push(dep.krate.crate_id());
+            }
+        }
+        self.result.extern_prelude = self.dependencies.clone();
+        let root = self.module_tree.root();
+        if let Some(input) = self.input.get(&root) {
+            for extern_crate in input.extern_crates.iter() {
+                if let Some(&def_id) = self.dependencies.get(&extern_crate.name) {
+                    self.result
+                        .extern_prelude
+                        .insert(extern_crate.alias.clone(), def_id);
+                }
+            }
+        }
+        for krate in macro_use_crates {
+            let module_tree = self.db.module_tree(krate)?;
+            for module_id in module_tree.modules() {
+                let input = self.db.input_module_items(krate, module_id)?;
+                for def in input.macro_defs.iter().filter(|it| it.exported) {
+                    self.result.macro_use.insert(def.name.clone(), def.id);
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn populate_module(&mut self, module_id: ModuleId, input: &InputModuleItems) -> Cancelable<()> {
-        let file_id = module_id.source(&self.module_tree).file_id();
+    /// Finds the `prelude::v1` module of `std` or, for `no_std` crates, of
+    /// `core`.
+    fn find_prelude(&self) -> Cancelable<Option<Module>> {
+        let deps = Crate::new(self.krate).dependencies(self.db);
+        let dep = ["std", "core"]
+            .iter()
+            .find_map(|&name| deps.iter().find(|dep| dep.name == Name::new(name.into())));
+        let root = match dep {
+            Some(dep) => dep.krate.root_module(self.db)?,
+            None => return Ok(None),
+        };
+        let prelude = root
+            .and_then(|it| it.child(&Name::new("prelude".into())))
+            .and_then(|it| it.child(&Name::new("v1".into())));
+        Ok(prelude)
+    }
 
+    fn populate_module(
+        &mut self,
+        module_id: ModuleId,
+        input: &InputModuleItems,
+        prelude: Option<&ModuleScope>,
+    ) -> Cancelable<()> {
         let mut module_items = ModuleScope::default();
+        let private = Visibility::Module {
+            krate: self.krate,
+            module_id,
+        };
+
+        // Populate std prelude, which is shadowed by everything else
+        for (name, res) in prelude.into_iter().flat_map(|it| it.entries()) {
+            if res.vis == Visibility::Public && !res.def_id.is_none() {
+                self.add_module_item(&mut module_items, name.clone(), res.def_id, private);
+            }
+        }
 
         // Populate extern crates prelude
-        {
-            let root_id = module_id.crate_root(&self.module_tree);
-            let file_id = root_id.source(&self.module_tree).file_id();
-            let crate_graph = self.db.crate_graph();
-            if let Some(crate_id) = crate_graph.crate_id_for_crate_root(file_id) {
-                let krate = Crate::new(crate_id);
-                for dep in krate.dependencies(self.db) {
-                    if let Some(module) = dep.krate.root_module(self.db)? {
-                        let def_id = module.def_id(self.db);
-                        self.add_module_item(
-                            &mut module_items,
-                            dep.name.clone(),
-                            PerNs::types(def_id),
-                        );
-                    }
-                }
+        for (name, &def_id) in self.result.extern_prelude.iter() {
+            let def_id = PerNs::types(def_id);
+            self.add_module_item(&mut module_items, name.clone(), def_id, private);
+        }
+        self.add_input_items(&mut module_items, module_id, input);
+
+        // Populate modules
+        for (name, child_id) in module_id.children(&self.module_tree) {
+            let def_loc = DefLoc {
+                kind: DefKind::Module,
+                krate: self.krate,
+                module_id: child_id,
+                source_item_id: child_id.source(&self.module_tree).0,
             };
+            let def_id = PerNs::types(def_loc.id(self.db));
+            let vis = input
+                .items
+                .iter()
+                .find(|it| it.kind == MODULE && it.name == name)
+                .map_or(private, |it| self.resolve_visibility(module_id, &it.vis));
+            self.add_module_item(&mut module_items, name, def_id, vis);
         }
+
+        self.result.per_module.insert(module_id, module_items);
+        Ok(())
+    }
+
+    fn add_input_items(
+        &self,
+        module_items: &mut ModuleScope,
+        module_id: ModuleId,
+        input: &InputModuleItems,
+    ) {
         for import in input.imports.iter() {
-            if let Some(name) = import.path.segments.iter().last() {
-                if let ImportKind::Named(import) = import.kind {
+            if let Some(segment) = import.path.segments.iter().last() {
+                if let ImportKind::Named(ptr) = import.kind {
                     module_items.items.insert(
-                        name.clone(),
+                        segment.name.clone(),
                         Resolution {
                             def_id: PerNs::none(),
-                            import: Some(import),
+                            import: Some(ptr),
+                            vis: self.resolve_visibility(module_id, &import.vis),
                         },
                     );
                 }
             }
         }
+        for def in input.macro_defs.iter() {
+            module_items.macros.insert(def.name.clone(), def.id);
+        }
+        for extern_crate in input.extern_crates.iter() {
+            if let Some(&def_id) = self.dependencies.get(&extern_crate.name) {
+                let vis = self.resolve_visibility(module_id, &extern_crate.vis);
+                let def_id = PerNs::types(def_id);
+                self.add_module_item(module_items, extern_crate.alias.clone(), def_id, vis);
+            }
+        }
         // Populate explicitly declared items, except modules
         for item in input.items.iter() {
             if item.kind == MODULE {
@@ -360,48 +783,65 @@ where
             let def_id = kind.map(|k| {
                 let def_loc = DefLoc {
                     kind: k,
-                    source_root_id: self.source_root,
+                    krate: self.krate,
                     module_id,
-                    source_item_id: SourceItemId {
-                        file_id,
-                        item_id: Some(item.id),
-                    },
+                    source_item_id: item.id,
                 };
                 def_loc.id(self.db)
             });
             let resolution = Resolution {
                 def_id,
                 import: None,
+                vis: self.resolve_visibility(module_id, &item.vis),
             };
             module_items.items.insert(item.name.clone(), resolution);
         }
-
-        // Populate modules
-        for (name, module_id) in module_id.children(&self.module_tree) {
-            let def_loc = DefLoc {
-                kind: DefKind::Module,
-                source_root_id: self.source_root,
-                module_id,
-                source_item_id: module_id.source(&self.module_tree).0,
-            };
-            let def_id = def_loc.id(self.db);
-            self.add_module_item(&mut module_items, name, PerNs::types(def_id));
-        }
-
-        self.result.per_module.insert(module_id, module_items);
-        Ok(())
     }
 
-    fn add_module_item(&self, module_items: &mut ModuleScope, name: Name, def_id: PerNs<DefId>) {
+    fn add_module_item(
+        &self,
+        module_items: &mut ModuleScope,
+        name: Name,
+        def_id: PerNs<DefId>,
+        vis: Visibility,
+    ) {
         let resolution = Resolution {
             def_id,
             import: None,
+            vis,
         };
         module_items.items.insert(name, resolution);
     }
 
+    /// Resolves the module `vis` restricts an item of `module_id` to. Paths
+    /// that do not point to a module make the item private.
+    fn resolve_visibility(&self, module_id: ModuleId, vis: &RawVisibility) -> Visibility {
+        let path = match vis {
+            RawVisibility::Public => return Visibility::Public,
+            RawVisibility::Restricted(path) => path,
+        };
+        let tree = &*self.module_tree;
+        let start = match path.kind {
+            PathKind::Self_ => Some(module_id),
+            PathKind::Super => module_id.parent(tree),
+            PathKind::Crate | PathKind::Plain | PathKind::Abs => Some(module_id.crate_root(tree)),
+        };
+        let target = path
+            .segments
+            .iter()
+            .fold(start, |module, segment| module?.child(tree, &segment.name));
+        Visibility::Module {
+            krate: self.krate,
+            module_id: target.unwrap_or(module_id),
+        }
+    }
+
     fn resolve_imports(&mut self, module_id: ModuleId) -> Cancelable<()> {
-        for import in self.input[&module_id].imports.iter() {
+        let imports: Vec<Import> = self
+            .module_inputs(module_id)
+            .flat_map(|it| it.imports.iter().cloned())
+            .collect();
+        for import in imports.iter() {
             self.resolve_import(module_id, import)?;
         }
         Ok(())
@@ -423,13 +863,31 @@ where
                 }
             }
             PathKind::Crate => module_id.crate_root(&self.module_tree),
+            PathKind::Abs => {
+                let (krate, segments) = match import.path.segments.split_first() {
+                    Some(it) => it,
+                    None => return Ok(()),
+                };
+                let loc = match self.result.extern_prelude.get(&krate.name) {
+                    Some(def_id) => def_id.loc(self.db),
+                    None => return Ok(()),
+                };
+                let module = Module::new(self.db, loc.krate, loc.module_id)?;
+                return self.resolve_import_in(module_id, module, segments, import, ptr);
+            }
         };
 
-        for (i, name) in import.path.segments.iter().enumerate() {
+        let vis = self.resolve_visibility(module_id, &import.vis);
+        for (i, segment) in import.path.segments.iter().enumerate() {
             let is_last = i == import.path.segments.len() - 1;
 
-            let def_id = match self.result.per_module[&curr].items.get(name) {
-                Some(res) if !res.def_id.is_none() => res.def_id,
+            let def_id = match self.result.per_module[&curr].items.get(&segment.name) {
+                Some(res)
+                    if !res.def_id.is_none()
+                        && res.vis.is_visible_in(self.krate, &self.module_tree, module_id) =>
+                {
+                    res.def_id
+                }
                 _ => return Ok(()),
             };
 
@@ -443,48 +901,85 @@ where
                     DefLoc {
                         kind: DefKind::Module,
                         module_id: target_module_id,
-                        source_root_id,
+                        krate,
                         ..
                     } => {
-                        if source_root_id == self.source_root {
+                        if krate == self.krate {
                             target_module_id
                         } else {
-                            let module = Module::new(self.db, source_root_id, target_module_id)?;
-                            let path = Path {
-                                segments: import.path.segments[i + 1..].iter().cloned().collect(),
-                                kind: PathKind::Crate,
-                            };
-                            let def_id = module.resolve_path(self.db, &path)?;
-                            if !def_id.is_none() {
-                                self.update(module_id, |items| {
-                                    let res = Resolution {
-                                        def_id: def_id,
-                                        import: Some(ptr),
-                                    };
-                                    items.items.insert(name.clone(), res);
-                                })
-                            }
+                            let module = Module::new(self.db, krate, target_module_id)?;
+                            let segments = &import.path.segments[i + 1..];
+                            return self.resolve_import_in(module_id, module, segments, import, ptr);
+                        }
+                    }
+                    DefLoc {
+                        kind: DefKind::Enum,
+                        ..
+                    } => {
+                        // an enum can only be followed by one of its variants
+                        let segment = &import.path.segments[i + 1];
+                        if i + 2 != import.path.segments.len() {
                             return Ok(());
                         }
+                        let variant = Enum::new(type_def_id).variant(self.db, &segment.name)?;
+                        if let Some(variant) = variant {
+                            let res = Resolution {
+                                def_id: PerNs::both(variant.def_id(), variant.def_id()),
+                                import: Some(ptr),
+                                vis,
+                            };
+                            self.update(module_id, segment.name.clone(), res);
+                        }
+                        return Ok(());
                     }
                     _ => return Ok(()),
                 }
             } else {
-                self.update(module_id, |items| {
-                    let res = Resolution {
-                        def_id: def_id,
-                        import: Some(ptr),
-                    };
-                    items.items.insert(name.clone(), res);
-                })
+                let res = Resolution {
+                    def_id: def_id,
+                    import: Some(ptr),
+                    vis,
+                };
+                self.update(module_id, segment.name.clone(), res);
             }
         }
         Ok(())
     }
 
-    fn update(&mut self, module_id: ModuleId, f: impl FnOnce(&mut ModuleScope)) {
+    /// Resolves the rest of an import, `segments`, in `module` of another
+    /// crate, whose item map is already known.
+    fn resolve_import_in(
+        &mut self,
+        module_id: ModuleId,
+        module: Module,
+        segments: &[PathSegment],
+        import: &Import,
+        ptr: NamedImport,
+    ) -> Cancelable<()> {
+        let from = Module::new(self.db, self.krate, module_id)?;
+        let path = Path {
+            segments: segments.to_vec(),
+            kind: PathKind::Self_,
+        };
+        let def_id = module.resolve_path_from(self.db, &from, &path)?;
+        if !def_id.is_none() {
+            let name = import.path.segments.last().unwrap().name.clone();
+            let res = Resolution {
+                def_id,
+                import: Some(ptr),
+                vis: self.resolve_visibility(module_id, &import.vis),
+            };
+            self.update(module_id, name, res);
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, module_id: ModuleId, name: Name, res: Resolution) {
         let module_items = self.result.per_module.get_mut(&module_id).unwrap();
-        f(module_items)
+        if module_items.items.get(&name) != Some(&res) {
+            module_items.items.insert(name, res);
+            self.changed = true;
+        }
     }
 }
 
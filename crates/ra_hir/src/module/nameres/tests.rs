@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use salsa::Database;
-use ra_db::{FilesDatabase, CrateGraph};
+use ra_db::{CrateGraph, CfgOptions};
 use relative_path::RelativePath;
 use test_utils::assert_eq_text;
 
@@ -13,12 +13,11 @@ use crate::{
 
 fn item_map(fixture: &str) -> (Arc<hir::ItemMap>, hir::ModuleId) {
     let (db, pos) = MockDatabase::with_position(fixture);
-    let source_root = db.file_source_root(pos.file_id);
     let module = hir::source_binder::module_from_position(&db, pos)
         .unwrap()
         .unwrap();
     let module_id = module.module_id;
-    (db.item_map(source_root).unwrap(), module_id)
+    (db.item_map(module.krate).unwrap(), module_id)
 }
 
 fn check_module_item_map(map: &hir::ItemMap, module_id: hir::ModuleId, expected: &str) {
@@ -101,6 +100,149 @@ fn item_map_using_self() {
     );
 }
 
+#[test]
+fn item_map_imports_enum_variants() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::E::{self, V1, V2};
+        use crate::foo::E::Nope;
+        <|>
+
+        //- /foo.rs
+        pub enum E { V1, V2 { x: u32 } }
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            E: t
+            Nope: _
+            V1: t v
+            V2: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_contains_items_from_macros() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+
+        macro_rules! define_id {
+            ($name:ident) => {
+                pub struct $name(u32);
+            };
+            ($($name:ident),*) => {
+                $(define_id!($name);)*
+            };
+        }
+
+        define_id!(FileId, CrateId);
+        use crate::foo::{Baz, Quux};
+        <|>
+
+        //- /foo.rs
+        macro_rules! items {
+            () => {
+                pub struct Baz;
+                pub struct Quux;
+            }
+        }
+        items!();
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            CrateId: t v
+            FileId: t v
+            Quux: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_expands_macros_from_parent_modules() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use crate::foo::bar::S;
+
+        macro_rules! define_struct {
+            ($name:ident) => { pub struct $name; }
+        }
+        <|>
+
+        //- /foo/mod.rs
+        pub mod bar;
+
+        //- /foo/bar.rs
+        define_struct!(S);
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            S: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_recursive_macro_terminates() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        macro_rules! forever {
+            () => { struct S; forever!(); }
+        }
+        forever!();
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            S: t v
+        ",
+    );
+}
+
+#[test]
+fn item_map_self_duplicating_macro_terminates() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        macro_rules! dup {
+            () => { dup!(); dup!(); }
+        }
+        dup!();
+        struct S;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            S: t v
+        ",
+    );
+}
+
 #[test]
 fn item_map_across_crates() {
     let (mut db, sr) = MockDatabase::with_files(
@@ -116,18 +258,17 @@ fn item_map_across_crates() {
     let lib_id = sr.files[RelativePath::new("/lib.rs")];
 
     let mut crate_graph = CrateGraph::default();
-    let main_crate = crate_graph.add_crate_root(main_id);
-    let lib_crate = crate_graph.add_crate_root(lib_id);
+    let main_crate = crate_graph.add_crate_root(main_id, CfgOptions::default());
+    let lib_crate = crate_graph.add_crate_root(lib_id, CfgOptions::default());
     crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
 
     db.set_crate_graph(crate_graph);
 
-    let source_root = db.file_source_root(main_id);
     let module = hir::source_binder::module_from_file_id(&db, main_id)
         .unwrap()
         .unwrap();
     let module_id = module.module_id;
-    let item_map = db.item_map(source_root).unwrap();
+    let item_map = db.item_map(main_crate).unwrap();
 
     check_module_item_map(
         &item_map,
@@ -139,6 +280,324 @@ fn item_map_across_crates() {
     );
 }
 
+#[test]
+fn item_map_resolves_extern_crates() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        extern crate test_crate as tc;
+        #[macro_use]
+        extern crate other_crate;
+        mod foo;
+        use tc::Baz;
+        make_struct!(Made);
+
+        //- /foo.rs
+        use ::test_crate::Quux;
+        use ::tc::Baz;
+        use ::main_crate::Nope;
+
+        //- /lib.rs
+        pub struct Baz;
+        pub struct Quux;
+
+        //- /other/lib.rs
+        #[macro_export]
+        macro_rules! make_struct {
+            ($name:ident) => { pub struct $name; }
+        }
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let foo_id = sr.files[RelativePath::new("/foo.rs")];
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+    let other_id = sr.files[RelativePath::new("/other/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id, CfgOptions::default());
+    let lib_crate = crate_graph.add_crate_root(lib_id, CfgOptions::default());
+    let other_crate = crate_graph.add_crate_root(other_id, CfgOptions::default());
+    crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
+    crate_graph.add_dep(main_crate, "other_crate".into(), other_crate);
+
+    db.set_crate_graph(crate_graph);
+
+    let item_map = db.item_map(main_crate).unwrap();
+    let main = hir::source_binder::module_from_file_id(&db, main_id)
+        .unwrap()
+        .unwrap();
+    check_module_item_map(
+        &item_map,
+        main.module_id,
+        "
+            Baz: t v
+            Made: t v
+            foo: t
+            other_crate: t
+            tc: t
+            test_crate: t
+        ",
+    );
+    let foo = hir::source_binder::module_from_file_id(&db, foo_id)
+        .unwrap()
+        .unwrap();
+    check_module_item_map(
+        &item_map,
+        foo.module_id,
+        "
+            Baz: t v
+            Nope: _
+            Quux: t v
+            other_crate: t
+            tc: t
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_injects_std_prelude() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        mod foo;
+        enum drop {}
+
+        //- /foo.rs
+
+        //- /std/lib.rs
+        pub mod prelude {
+            pub mod v1 {
+                pub use crate::mem::drop;
+                pub use crate::vec::Vec;
+                use crate::vec::NotInPrelude;
+            }
+        }
+        pub mod mem {
+            pub fn drop() {}
+        }
+        pub mod vec {
+            pub struct Vec;
+            pub struct NotInPrelude;
+        }
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let foo_id = sr.files[RelativePath::new("/foo.rs")];
+    let std_id = sr.files[RelativePath::new("/std/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id, CfgOptions::default());
+    let std_crate = crate_graph.add_crate_root(std_id, CfgOptions::default());
+    crate_graph.add_dep(main_crate, "std".into(), std_crate);
+
+    db.set_crate_graph(crate_graph);
+
+    let item_map = db.item_map(main_crate).unwrap();
+    let main = hir::source_binder::module_from_file_id(&db, main_id)
+        .unwrap()
+        .unwrap();
+    check_module_item_map(
+        &item_map,
+        main.module_id,
+        "
+            Vec: t v
+            drop: t
+            foo: t
+            std: t
+        ",
+    );
+    let foo = hir::source_binder::module_from_file_id(&db, foo_id)
+        .unwrap()
+        .unwrap();
+    check_module_item_map(
+        &item_map,
+        foo.module_id,
+        "
+            Vec: t v
+            drop: v
+            std: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_respects_visibility() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        mod bar {
+            pub struct Pub;
+            pub(crate) struct PubCrate;
+            pub(super) struct PubSuper;
+            pub(in crate::bar) struct PubInBar;
+            struct Private;
+            pub use self::baz::Reexported;
+            use self::baz::NotReexported;
+            mod baz {
+                pub struct Reexported;
+                pub struct NotReexported;
+            }
+        }
+
+        //- /foo.rs
+        use crate::bar::Pub;
+        use crate::bar::PubCrate;
+        use crate::bar::PubSuper;
+        use crate::bar::PubInBar;
+        use crate::bar::Private;
+        use crate::bar::Reexported;
+        use crate::bar::NotReexported;
+        use crate::bar::baz;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            NotReexported: _
+            Private: _
+            Pub: t v
+            PubCrate: t v
+            PubInBar: _
+            PubSuper: t v
+            Reexported: t v
+            baz: _
+        ",
+    );
+}
+
+#[test]
+fn item_map_across_crates_respects_visibility() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        use test_crate::Pub;
+        use test_crate::PubCrate;
+        use test_crate::Private;
+
+        //- /lib.rs
+        pub struct Pub;
+        pub(crate) struct PubCrate;
+        struct Private;
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id, CfgOptions::default());
+    let lib_crate = crate_graph.add_crate_root(lib_id, CfgOptions::default());
+    crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
+
+    db.set_crate_graph(crate_graph);
+
+    let module = hir::source_binder::module_from_file_id(&db, main_id)
+        .unwrap()
+        .unwrap();
+    let item_map = db.item_map(main_crate).unwrap();
+
+    check_module_item_map(
+        &item_map,
+        module.module_id,
+        "
+            Private: _
+            Pub: t v
+            PubCrate: _
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_respects_cfg() {
+    let (mut db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        #[cfg(feature = \"foo\")]
+        pub struct Foo;
+        #[cfg(not(feature = \"foo\"))]
+        pub struct NotFoo;
+        #[cfg(all(unix, not(test)))]
+        pub struct UnixNotTest;
+        #[cfg(any(windows, test))]
+        pub struct WindowsOrTest;
+        #[cfg(test)]
+        #[cfg(feature = \"bar\")]
+        pub struct TestAndBar;
+        #[cfg(test)]
+        mod tests;
+        #[cfg(not(test))]
+        mod not_tests;
+        #[cfg(test)]
+        use crate::tests::Baz;
+        #[cfg(not(test))]
+        use crate::tests::Quux;
+        macro_rules! s { ($name:ident) => { pub struct $name; } }
+        #[cfg(unix)]
+        s!(Unix);
+        <|>
+
+        //- /tests.rs
+        pub struct Baz;
+        pub struct Quux;
+
+        //- /not_tests.rs
+    ",
+    );
+    let mut cfg_options = CfgOptions::default();
+    cfg_options.insert_atom("test".into());
+    cfg_options.insert_key_value("feature".into(), "foo".into());
+    let mut crate_graph = CrateGraph::default();
+    let krate = crate_graph.add_crate_root(pos.file_id, cfg_options);
+    db.set_crate_graph(crate_graph);
+
+    let module = hir::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap();
+    let item_map = db.item_map(krate).unwrap();
+    check_module_item_map(
+        &item_map,
+        module.module_id,
+        "
+            Baz: t v
+            Foo: t v
+            WindowsOrTest: t v
+            tests: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_does_not_mix_crates_sharing_a_module() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        pub struct Lib;
+
+        //- /main.rs
+        mod foo;
+        pub struct Main;
+
+        //- /foo.rs
+        use crate::Lib;
+        use crate::Main;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Lib: t v
+            Main: _
+        ",
+    );
+}
+
 #[test]
 fn typing_inside_a_function_should_not_invalidate_item_map() {
     let (mut db, pos) = MockDatabase::with_position(
@@ -158,10 +617,13 @@ fn typing_inside_a_function_should_not_invalidate_item_map() {
         pub struct Baz;
     ",
     );
-    let source_root = db.file_source_root(pos.file_id);
+    let krate = hir::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap()
+        .krate;
     {
         let events = db.log_executed(|| {
-            db.item_map(source_root).unwrap();
+            db.item_map(krate).unwrap();
         });
         assert!(format!("{:?}", events).contains("item_map"))
     }
@@ -180,7 +642,7 @@ fn typing_inside_a_function_should_not_invalidate_item_map() {
 
     {
         let events = db.log_executed(|| {
-            db.item_map(source_root).unwrap();
+            db.item_map(krate).unwrap();
         });
         assert!(
             !format!("{:?}", events).contains("_item_map"),
@@ -23,7 +23,7 @@ impl fmt::Debug for Name {
 }
 
 impl Name {
-    fn new(text: SmolStr) -> Name {
+    pub(crate) fn new(text: SmolStr) -> Name {
         Name { text }
     }
 
@@ -31,6 +31,19 @@ impl Name {
         Name::new("[missing name]".into())
     }
 
+    pub(crate) fn self_param() -> Name {
+        Name::new("self".into())
+    }
+
+    pub(crate) fn self_type() -> Name {
+        Name::new("Self".into())
+    }
+
+    /// The `Output` associated type of the `Fn` traits.
+    pub(crate) fn output() -> Name {
+        Name::new("Output".into())
+    }
+
     pub(crate) fn tuple_field_name(idx: usize) -> Name {
         Name::new(idx.to_string().into())
     }
@@ -51,6 +64,21 @@ impl Name {
             "u128" => KnownName::U128,
             "f32" => KnownName::F32,
             "f64" => KnownName::F64,
+            "bool" => KnownName::Bool,
+            "char" => KnownName::Char,
+            "str" => KnownName::Str,
+            "Clone" => KnownName::Clone,
+            "Deref" => KnownName::Deref,
+            "Target" => KnownName::Target,
+            "IntoIterator" => KnownName::IntoIterator,
+            "Iterator" => KnownName::Iterator,
+            "Item" => KnownName::Item,
+            "Fn" => KnownName::Fn,
+            "FnMut" => KnownName::FnMut,
+            "FnOnce" => KnownName::FnOnce,
+            "Output" => KnownName::Output,
+            "len" => KnownName::Len,
+            "Self" => KnownName::SelfType,
             _ => return None,
         };
         Some(name)
@@ -84,7 +112,7 @@ impl AsName for ra_db::Dependency {
 // const ISIZE: Name = Name::new("isize")
 // ```
 // but const-fn is not that powerful yet.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum KnownName {
     Isize,
     I8,
@@ -102,4 +130,25 @@ pub(crate) enum KnownName {
 
     F32,
     F64,
+
+    Bool,
+    Char,
+    Str,
+
+    Clone,
+    Deref,
+    Target,
+
+    IntoIterator,
+    Iterator,
+    Item,
+
+    Fn,
+    FnMut,
+    FnOnce,
+    Output,
+
+    Len,
+
+    SelfType,
 }
@@ -1,11 +1,34 @@
+use std::sync::Arc;
+
 use ra_syntax::{ast, AstNode, TextRange};
 
-use crate::{Name, AsName};
+use crate::{Name, AsName, name::KnownName, type_ref::TypeRef};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Path {
     pub kind: PathKind,
-    pub segments: Vec<Name>,
+    pub segments: Vec<PathSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathSegment {
+    pub name: Name,
+    pub args_and_bindings: Option<Arc<GenericArgs>>,
+}
+
+/// Generic arguments to a path segment (e.g. the `i32` in `Option<i32>`),
+/// including bindings of associated types, like in `Iterator<Item = Foo>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GenericArgs {
+    pub args: Vec<GenericArg>,
+    pub bindings: Vec<(Name, TypeRef)>,
+}
+
+/// A single generic argument.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GenericArg {
+    Type(TypeRef),
+    // or lifetime...
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -14,6 +37,8 @@ pub enum PathKind {
     Self_,
     Super,
     Crate,
+    /// `::foo`, a path starting at the root of the dependency `foo`.
+    Abs,
 }
 
 impl Path {
@@ -29,9 +54,29 @@ impl Path {
         let mut kind = PathKind::Plain;
         let mut segments = Vec::new();
         loop {
-            let segment = path.segment()?;
-            match segment.kind()? {
-                ast::PathSegmentKind::Name(name) => segments.push(name.as_name()),
+            let segment_syntax = path.segment()?;
+            match segment_syntax.kind()? {
+                ast::PathSegmentKind::Name(name) => {
+                    let args = segment_syntax
+                        .type_arg_list()
+                        .and_then(GenericArgs::from_ast)
+                        .or_else(|| {
+                            GenericArgs::from_fn_like_path_ast(
+                                segment_syntax.param_list(),
+                                segment_syntax.ret_type(),
+                            )
+                        })
+                        .map(Arc::new);
+                    let segment = PathSegment {
+                        name: name.as_name(),
+                        args_and_bindings: args,
+                    };
+                    segments.push(segment);
+                    if segment_syntax.has_leading_coloncolon() {
+                        kind = PathKind::Abs;
+                        break;
+                    }
+                }
                 ast::PathSegmentKind::CrateKw => {
                     kind = PathKind::Crate;
                     break;
@@ -65,17 +110,133 @@ impl Path {
         }
     }
 
+    /// Converts an `ast::NameRef` into a single-identifier `Path`.
+    pub fn from_name_ref(name_ref: ast::NameRef) -> Path {
+        name_ref.as_name().into()
+    }
+
     /// `true` is this path is a single identifier, like `foo`
     pub fn is_ident(&self) -> bool {
         self.kind == PathKind::Plain && self.segments.len() == 1
     }
 
+    /// `true` if this path is just a standalone `self`
+    pub fn is_self(&self) -> bool {
+        self.kind == PathKind::Self_ && self.segments.is_empty()
+    }
+
+    /// `true` if this path is just a standalone `Self`
+    pub fn is_self_type(&self) -> bool {
+        self.kind == PathKind::Plain && self.segments.len() == 1 && self.starts_with_self_type()
+    }
+
+    /// `true` if the first segment of this path is `Self`, as in `Self::new`
+    pub fn starts_with_self_type(&self) -> bool {
+        self.kind == PathKind::Plain
+            && self.segments.first().map_or(false, |s| {
+                s.name.as_known_name() == Some(KnownName::SelfType)
+            })
+    }
+
+    /// Replaces `Self` in the generic arguments of this path by `self_type`.
+    pub(crate) fn replace_self_type_in_args(&self, self_type: &TypeRef) -> Path {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| PathSegment {
+                name: segment.name.clone(),
+                args_and_bindings: segment
+                    .args_and_bindings
+                    .as_ref()
+                    .map(|args| Arc::new(args.replace_self_type(self_type))),
+            })
+            .collect();
+        Path {
+            kind: self.kind,
+            segments,
+        }
+    }
+
     /// If this path is a single identifier, like `foo`, return its name.
     pub fn as_ident(&self) -> Option<&Name> {
         if self.kind != PathKind::Plain || self.segments.len() > 1 {
             return None;
         }
-        self.segments.first()
+        self.segments.first().map(|s| &s.name)
+    }
+}
+
+impl GenericArgs {
+    fn from_ast(node: ast::TypeArgList) -> Option<GenericArgs> {
+        let mut args = Vec::new();
+        for type_arg in node.type_args() {
+            let type_ref = TypeRef::from_ast_opt(type_arg.type_ref());
+            args.push(GenericArg::Type(type_ref));
+        }
+        let mut bindings = Vec::new();
+        for assoc_type_arg in node.assoc_type_args() {
+            if let Some(name_ref) = assoc_type_arg.name_ref() {
+                let type_ref = TypeRef::from_ast_opt(assoc_type_arg.type_ref());
+                bindings.push((name_ref.as_name(), type_ref));
+            }
+        }
+        // lifetimes ignored for now
+        if !args.is_empty() || !bindings.is_empty() {
+            Some(GenericArgs { args, bindings })
+        } else {
+            None
+        }
+    }
+
+    fn replace_self_type(&self, self_type: &TypeRef) -> GenericArgs {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| match arg {
+                GenericArg::Type(type_ref) => {
+                    GenericArg::Type(type_ref.replace_self_type(self_type))
+                }
+            })
+            .collect();
+        let bindings = self
+            .bindings
+            .iter()
+            .map(|(name, type_ref)| (name.clone(), type_ref.replace_self_type(self_type)))
+            .collect();
+        GenericArgs { args, bindings }
+    }
+
+    /// Desugars the `(A, B) -> C` of `Fn(A, B) -> C` to
+    /// `<(A, B), Output = C>`.
+    fn from_fn_like_path_ast(
+        params: Option<ast::ParamList>,
+        ret_type: Option<ast::RetType>,
+    ) -> Option<GenericArgs> {
+        let params = params?;
+        let param_types = params
+            .params()
+            .map(|param| TypeRef::from_ast_opt(param.type_ref()))
+            .collect();
+        let output = ret_type
+            .and_then(|it| it.type_ref())
+            .map(TypeRef::from_ast)
+            .unwrap_or_else(TypeRef::unit);
+        Some(GenericArgs {
+            args: vec![GenericArg::Type(TypeRef::Tuple(param_types))],
+            bindings: vec![(Name::output(), output)],
+        })
+    }
+}
+
+impl From<Name> for Path {
+    fn from(name: Name) -> Path {
+        Path {
+            kind: PathKind::Plain,
+            segments: vec![PathSegment {
+                name,
+                args_and_bindings: None,
+            }],
+        }
     }
 }
 
@@ -131,7 +292,7 @@ fn convert_path(prefix: Option<Path>, path: ast::Path) -> Option<Path> {
     let prefix = if let Some(qual) = path.qualifier() {
         Some(convert_path(prefix, qual)?)
     } else {
-        None
+        prefix
     };
     let segment = path.segment()?;
     let res = match segment.kind()? {
@@ -140,7 +301,10 @@ fn convert_path(prefix: Option<Path>, path: ast::Path) -> Option<Path> {
                 kind: PathKind::Plain,
                 segments: Vec::with_capacity(1),
             });
-            res.segments.push(name.as_name());
+            res.segments.push(PathSegment {
+                name: name.as_name(),
+                args_and_bindings: None, // no type args in use
+            });
             res
         }
         ast::PathSegmentKind::CrateKw => {
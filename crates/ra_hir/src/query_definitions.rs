@@ -5,13 +5,14 @@ use std::{
 
 use rustc_hash::FxHashMap;
 use ra_syntax::{
-    AstNode, SyntaxNode,
-    ast::{self, NameOwner, ModuleItemOwner}
+    AstNode, SyntaxNode, SyntaxKind,
+    ast::{self, AttrsOwner, NameOwner, TypeParamList, TypeParamsOwner, WhereClause}
 };
-use ra_db::{SourceRootId, FileId, Cancelable,};
+use ra_db::{CrateId, CfgOptions, FileId, Cancelable,};
 
 use crate::{
-    SourceFileItems, SourceItemId, DefKind, Function, DefId, Name, AsName,
+    SourceFileItems, SourceItemId, DefLoc, DefKind, DefId, Name, AsName, HirFileId,
+    cfg,
     db::HirDatabase,
     function::FnScopes,
     module::{
@@ -20,26 +21,32 @@ use crate::{
         nameres::{InputModuleItems, ItemMap, Resolver},
     },
     ty::{self, InferenceResult, Ty},
-    adt::{StructData, EnumData},
+    adt::{StructData, EnumData, EnumVariantData},
+    traits::TraitData,
+    type_alias::TypeAliasData,
+    consts::ConstData,
+    generics::GenericParams,
 };
 
-pub(super) fn fn_scopes(db: &impl HirDatabase, def_id: DefId) -> Arc<FnScopes> {
-    let function = Function::new(def_id);
-    let syntax = function.syntax(db);
-    let res = FnScopes::new(syntax.borrowed());
-    Arc::new(res)
+pub(super) fn fn_scopes(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<FnScopes>> {
+    let body = db.body_hir(def_id)?;
+    let res = FnScopes::new(body);
+    Ok(Arc::new(res))
 }
 
 pub(super) fn infer(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<InferenceResult>> {
-    let function = Function::new(def_id);
-    ty::infer(db, function).map(Arc::new)
+    ty::infer(db, def_id)
 }
 
 pub(super) fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ty> {
     ty::type_for_def(db, def_id)
 }
 
-pub(super) fn type_for_field(db: &impl HirDatabase, def_id: DefId, field: Name) -> Cancelable<Ty> {
+pub(super) fn type_for_field(
+    db: &impl HirDatabase,
+    def_id: DefId,
+    field: Name,
+) -> Cancelable<Option<Ty>> {
     ty::type_for_field(db, def_id, field)
 }
 
@@ -58,18 +65,125 @@ pub(super) fn enum_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<
     let syntax = db.file_item(def_loc.source_item_id);
     let enum_def =
         ast::EnumDef::cast(syntax.borrowed()).expect("enum def should point to EnumDef node");
-    Ok(Arc::new(EnumData::new(enum_def.borrowed())))
+    Ok(Arc::new(EnumData::new(db, &def_loc, enum_def.borrowed())))
+}
+
+pub(super) fn enum_variant_data(
+    db: &impl HirDatabase,
+    def_id: DefId,
+) -> Cancelable<Arc<EnumVariantData>> {
+    let def_loc = def_id.loc(db);
+    assert!(def_loc.kind == DefKind::EnumVariant);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let variant_def = ast::EnumVariant::cast(syntax.borrowed())
+        .expect("enum variant def should point to EnumVariant node");
+    let enum_node = syntax
+        .borrowed()
+        .ancestors()
+        .find(|it| it.kind() == SyntaxKind::ENUM_DEF)
+        .expect("enum variants are always nested in enums");
+    let file_items = db.file_items(def_loc.source_item_id.file_id);
+    let enum_loc = DefLoc {
+        kind: DefKind::Enum,
+        source_item_id: SourceItemId {
+            file_id: def_loc.source_item_id.file_id,
+            item_id: Some(file_items.id_of_unchecked(enum_node)),
+        },
+        ..def_loc
+    };
+    Ok(Arc::new(EnumVariantData::new(
+        variant_def.borrowed(),
+        enum_loc.id(db),
+    )))
+}
+
+pub(super) fn trait_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<TraitData>> {
+    let def_loc = def_id.loc(db);
+    assert!(def_loc.kind == DefKind::Trait);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let trait_def =
+        ast::TraitDef::cast(syntax.borrowed()).expect("trait def should point to TraitDef node");
+    Ok(Arc::new(TraitData::new(db, &def_loc, trait_def.borrowed())))
+}
+
+pub(super) fn type_alias_data(
+    db: &impl HirDatabase,
+    def_id: DefId,
+) -> Cancelable<Arc<TypeAliasData>> {
+    let def_loc = def_id.loc(db);
+    assert!(def_loc.kind == DefKind::Type);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let type_def =
+        ast::TypeDef::cast(syntax.borrowed()).expect("type def should point to TypeDef node");
+    Ok(Arc::new(TypeAliasData::new(type_def.borrowed())))
 }
 
-pub(super) fn file_items(db: &impl HirDatabase, file_id: FileId) -> Arc<SourceFileItems> {
+pub(super) fn const_data(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<ConstData>> {
+    let def_loc = def_id.loc(db);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let data = match def_loc.kind {
+        DefKind::Const => {
+            let const_def = ast::ConstDef::cast(syntax.borrowed())
+                .expect("const def should point to ConstDef node");
+            ConstData::from_const(const_def.borrowed())
+        }
+        DefKind::Static => {
+            let static_def = ast::StaticDef::cast(syntax.borrowed())
+                .expect("static def should point to StaticDef node");
+            ConstData::from_static(static_def.borrowed())
+        }
+        kind => panic!("const_data called for {:?}", kind),
+    };
+    Ok(Arc::new(data))
+}
+
+pub(super) fn generic_params(db: &impl HirDatabase, def_id: DefId) -> Arc<GenericParams> {
+    let def_loc = def_id.loc(db);
+    let syntax = db.file_item(def_loc.source_item_id);
+    let mut owner = syntax.borrowed();
+    if def_loc.kind == DefKind::EnumVariant {
+        // variants share the generic parameters of their enum
+        owner = owner
+            .ancestors()
+            .find(|it| it.kind() == SyntaxKind::ENUM_DEF)
+            .unwrap_or(owner);
+    }
+    // methods see the generic parameters of their impl block
+    let parent_params = if def_loc.kind == DefKind::Function {
+        owner
+            .parent()
+            .and_then(|it| it.parent())
+            .and_then(ast::ImplItem::cast)
+            .map(|impl_item| {
+                let generics =
+                    GenericParams::new(None, impl_item.type_param_list(), impl_item.where_clause());
+                Arc::new(generics)
+            })
+    } else {
+        None
+    };
+    let type_param_list = owner.children().find_map(TypeParamList::cast);
+    let where_clause = owner.children().find_map(WhereClause::cast);
+    Arc::new(GenericParams::new(
+        parent_params,
+        type_param_list,
+        where_clause,
+    ))
+}
+
+pub(super) fn file_items(db: &impl HirDatabase, file_id: HirFileId) -> Arc<SourceFileItems> {
     let mut res = SourceFileItems::new(file_id);
-    let source_file = db.source_file(file_id);
+    let source_file = db.hir_source_file(file_id);
     let source_file = source_file.borrowed();
     source_file
         .syntax()
         .descendants()
-        .filter_map(ast::ModuleItem::cast)
-        .map(|it| it.syntax().owned())
+        .filter(|it| {
+            ast::ModuleItem::cast(*it).is_some()
+                || ast::MacroCall::cast(*it).is_some()
+                || ast::EnumVariant::cast(*it).is_some()
+        })
+        .map(|it| it.owned())
         .for_each(|it| {
             res.alloc(it);
         });
@@ -79,39 +193,51 @@ pub(super) fn file_items(db: &impl HirDatabase, file_id: FileId) -> Arc<SourceFi
 pub(super) fn file_item(db: &impl HirDatabase, source_item_id: SourceItemId) -> SyntaxNode {
     match source_item_id.item_id {
         Some(id) => db.file_items(source_item_id.file_id)[id].clone(),
-        None => db.source_file(source_item_id.file_id).syntax().owned(),
+        None => db.hir_source_file(source_item_id.file_id).syntax().owned(),
     }
 }
 
 pub(crate) fn submodules(
     db: &impl HirDatabase,
+    krate: CrateId,
     source: ModuleSource,
 ) -> Cancelable<Arc<Vec<Submodule>>> {
     db.check_canceled()?;
     let file_id = source.file_id();
+    let crate_graph = db.crate_graph();
+    let cfg_options = crate_graph.cfg_options(krate);
     let submodules = match source.resolve(db) {
-        ModuleSourceNode::SourceFile(it) => collect_submodules(db, file_id, it.borrowed()),
+        ModuleSourceNode::SourceFile(it) => {
+            collect_submodules(db, cfg_options, file_id, it.borrowed())
+        }
         ModuleSourceNode::Module(it) => it
             .borrowed()
             .item_list()
-            .map(|it| collect_submodules(db, file_id, it))
+            .map(|it| collect_submodules(db, cfg_options, file_id, it))
             .unwrap_or_else(Vec::new),
     };
     return Ok(Arc::new(submodules));
 
     fn collect_submodules<'a>(
         db: &impl HirDatabase,
+        cfg_options: &CfgOptions,
         file_id: FileId,
         root: impl ast::ModuleItemOwner<'a>,
     ) -> Vec<Submodule> {
         modules(root)
+            .filter(|(_, m)| cfg::is_enabled(cfg_options, m.syntax()))
             .map(|(name, m)| {
-                if m.has_semi() {
-                    Submodule::Declaration(name)
+                let path = m
+                    .attrs()
+                    .filter_map(|it| it.as_key_value())
+                    .find(|(key, _)| key == "path")
+                    .map(|(_, value)| value);
+                let source = if m.has_semi() {
+                    None
                 } else {
-                    let src = ModuleSource::new_inline(db, file_id, m);
-                    Submodule::Definition(name, src)
-                }
+                    Some(ModuleSource::new_inline(db, file_id, m))
+                };
+                Submodule { name, path, source }
             })
             .collect()
     }
@@ -133,24 +259,24 @@ pub(crate) fn modules<'a>(
 
 pub(super) fn input_module_items(
     db: &impl HirDatabase,
-    source_root: SourceRootId,
+    krate: CrateId,
     module_id: ModuleId,
 ) -> Cancelable<Arc<InputModuleItems>> {
-    let module_tree = db.module_tree(source_root)?;
+    let module_tree = db.module_tree(krate)?;
     let source = module_id.source(&module_tree);
-    let file_items = db.file_items(source.file_id());
+    let file_id = source.file_id().into();
     let res = match source.resolve(db) {
         ModuleSourceNode::SourceFile(it) => {
-            let items = it.borrowed().items();
-            InputModuleItems::new(&file_items, items)
+            let nodes = it.borrowed().syntax().children();
+            InputModuleItems::new(db, krate, module_id, file_id, nodes)
         }
         ModuleSourceNode::Module(it) => {
-            let items = it
+            let nodes = it
                 .borrowed()
                 .item_list()
                 .into_iter()
-                .flat_map(|it| it.items());
-            InputModuleItems::new(&file_items, items)
+                .flat_map(|it| it.syntax().children());
+            InputModuleItems::new(db, krate, module_id, file_id, nodes)
         }
     };
     Ok(Arc::new(res))
@@ -158,19 +284,19 @@ pub(super) fn input_module_items(
 
 pub(super) fn item_map(
     db: &impl HirDatabase,
-    source_root: SourceRootId,
+    krate: CrateId,
 ) -> Cancelable<Arc<ItemMap>> {
     let start = Instant::now();
-    let module_tree = db.module_tree(source_root)?;
+    let module_tree = db.module_tree(krate)?;
     let input = module_tree
         .modules()
         .map(|id| {
-            let items = db.input_module_items(source_root, id)?;
+            let items = db.input_module_items(krate, id)?;
             Ok((id, items))
         })
         .collect::<Cancelable<FxHashMap<_, _>>>()?;
 
-    let resolver = Resolver::new(db, &input, source_root, module_tree);
+    let resolver = Resolver::new(db, &input, krate, module_tree);
     let res = resolver.resolve()?;
     let elapsed = start.elapsed();
     log::info!("item_map: {:?}", elapsed);
@@ -5,6 +5,8 @@
 ///
 /// So, this modules should not be used during hir construction, it exists
 /// purely for "IDE needs".
+use std::sync::Arc;
+
 use ra_db::{FileId, FilePosition, Cancelable};
 use ra_editor::find_node_at_offset;
 use ra_syntax::{
@@ -13,7 +15,7 @@ use ra_syntax::{
 };
 
 use crate::{
-    HirDatabase, Module, Function, SourceItemId,
+    HirDatabase, Module, Function, SourceItemId, HirFileId, Path, MacroCallLoc, MacroExpansion,
     module::ModuleSource,
     DefKind, DefLoc, AsName,
 };
@@ -79,12 +81,18 @@ fn module_from_source(
     module_source: ModuleSource,
 ) -> Cancelable<Option<Module>> {
     let source_root_id = db.file_source_root(module_source.file_id());
-    let module_tree = db.module_tree(source_root_id)?;
-    let m = module_tree
-        .modules_with_sources()
-        .find(|(_id, src)| src == &module_source);
-    let module_id = ctry!(m).0;
-    Ok(Some(Module::new(db, source_root_id, module_id)?))
+    // A file might belong to several crates (e.g. a lib and a bin sharing a
+    // module), in which case we just pick the first one.
+    for &krate in db.source_root_crates(source_root_id).iter() {
+        let module_tree = db.module_tree(krate)?;
+        let m = module_tree
+            .modules_with_sources()
+            .find(|(_id, src)| src == &module_source);
+        if let Some((module_id, _)) = m {
+            return Ok(Some(Module::new(db, krate, module_id)?));
+        }
+    }
+    Ok(None)
 }
 
 pub fn function_from_source(
@@ -102,7 +110,7 @@ pub fn function_from_module(
     module: &Module,
     fn_def: ast::FnDef,
 ) -> Function {
-    let file_id = module.source().file_id();
+    let file_id: HirFileId = module.source().file_id().into();
     let file_items = db.file_items(file_id);
     let item_id = file_items.id_of(file_id, fn_def.syntax());
     let source_item_id = SourceItemId {
@@ -111,7 +119,7 @@ pub fn function_from_module(
     };
     let def_loc = DefLoc {
         kind: DefKind::Function,
-        source_root_id: module.source_root_id,
+        krate: module.krate,
         module_id: module.module_id,
         source_item_id,
     };
@@ -126,3 +134,28 @@ pub fn function_from_child_node(
     let fn_def = ctry!(node.ancestors().find_map(ast::FnDef::cast));
     function_from_source(db, file_id, fn_def)
 }
+
+/// Expands `macro_call` with the `macro_rules!` definition it resolves to in
+/// its module.
+pub fn expand_macro_call(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    macro_call: ast::MacroCall,
+) -> Cancelable<Option<Arc<MacroExpansion>>> {
+    let module = ctry!(module_from_child_node(db, file_id, macro_call.syntax())?);
+    let path = ctry!(macro_call.path().and_then(Path::from_ast));
+    let def = ctry!(module.resolve_macro(db, &path)?);
+    let file_id: HirFileId = file_id.into();
+    let file_items = db.file_items(file_id);
+    let source_item_id = SourceItemId {
+        file_id,
+        item_id: Some(file_items.id_of(file_id, macro_call.syntax())),
+    };
+    let loc = MacroCallLoc {
+        krate: module.krate,
+        module_id: module.module_id,
+        source_item_id,
+        def,
+    };
+    Ok(db.expand_macro_invocation(loc.id(db)))
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use ra_syntax::ast::{self, AstNode, NameOwner, ModuleItemOwner};
+
+use crate::{
+    DefId, DefLoc, DefKind, SourceItemId, Cancelable, Name, AsName, Function, Type,
+    db::HirDatabase,
+    generics::GenericParams,
+};
+
+/// A trait definition, e.g. `trait Foo { fn foo(&self); }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trait {
+    def_id: DefId,
+}
+
+impl Trait {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        Trait { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn trait_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<TraitData>> {
+        db.trait_data(self.def_id)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.trait_data(self.def_id)?.name.clone())
+    }
+
+    pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
+        db.generic_params(self.def_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraitData {
+    name: Option<Name>,
+    items: Vec<TraitItem>,
+}
+
+impl TraitData {
+    pub(crate) fn new(db: &impl HirDatabase, def_loc: &DefLoc, trait_def: ast::TraitDef) -> Self {
+        let name = trait_def.name().map(|n| n.as_name());
+        let file_id = def_loc.source_item_id.file_id;
+        let file_items = db.file_items(file_id);
+        let items = if let Some(item_list) = trait_def.item_list() {
+            item_list
+                .items()
+                .filter_map(|item| match item {
+                    ast::ModuleItem::FnDef(fn_def) => {
+                        let item_id = file_items.id_of(file_id, fn_def.syntax());
+                        let fn_loc = DefLoc {
+                            kind: DefKind::Function,
+                            source_item_id: SourceItemId {
+                                file_id,
+                                item_id: Some(item_id),
+                            },
+                            ..def_loc.clone()
+                        };
+                        Some(TraitItem::Method(Function::new(fn_loc.id(db))))
+                    }
+                    ast::ModuleItem::TypeDef(type_def) => {
+                        let item_id = file_items.id_of(file_id, type_def.syntax());
+                        let type_loc = DefLoc {
+                            kind: DefKind::Type,
+                            source_item_id: SourceItemId {
+                                file_id,
+                                item_id: Some(item_id),
+                            },
+                            ..def_loc.clone()
+                        };
+                        Some(TraitItem::Type(Type::new(type_loc.id(db))))
+                    }
+                    // TODO: associated consts
+                    _ => None,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        TraitData { name, items }
+    }
+
+    pub fn name(&self) -> Option<&Name> {
+        self.name.as_ref()
+    }
+
+    pub fn items(&self) -> &[TraitItem] {
+        &self.items
+    }
+
+    /// Returns the associated type declaration `name`, if there is one.
+    pub fn associated_type(&self, db: &impl HirDatabase, name: &Name) -> Cancelable<Option<Type>> {
+        for item in self.items.iter() {
+            if let TraitItem::Type(t) = item {
+                if t.name(db)?.as_ref() == Some(name) {
+                    return Ok(Some(*t));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// An item inside a trait definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraitItem {
+    Method(Function),
+    /// An associated type declaration, e.g. `type Item;`.
+    Type(Type),
+    // TODO: Const
+}
@@ -1,24 +1,32 @@
 mod primitive;
+pub(crate) mod method_resolution;
+pub(crate) mod autoderef;
+pub(crate) mod traits;
+
 #[cfg(test)]
 mod tests;
 
 use std::sync::Arc;
-use std::{fmt, mem};
+use std::{fmt, iter, mem, borrow::Cow, ops::Index};
 
 use log;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use ena::unify::{InPlaceUnificationTable, UnifyKey, UnifyValue, NoError};
 
-use ra_db::{LocalSyntaxPtr, Cancelable};
-use ra_syntax::{
-    ast::{self, AstNode, LoopBodyOwner, ArgListOwner, PrefixOp},
-    SyntaxNodeRef
-};
+use ra_db::Cancelable;
 
 use crate::{
-    Def, DefId, FnScopes, Module, Function, Struct, Enum, Path, Name, AsName,
+    Def, DefId, FnScopes, Module, Function, Struct, Enum, EnumVariant, Type, Const, Static,
+    Path, PathKind, Name, ConstExpr,
+    name::KnownName,
     db::HirDatabase,
     type_ref::{TypeRef, Mutability},
+    generics::GenericParams,
+    path::GenericArg,
+    expr::{
+        Body, Expr, Array, Literal, ExprId, Pat, PatId, UnaryOp, BindingAnnotation, Statement,
+    },
+    function::FnSignature,
 };
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -75,10 +83,36 @@ impl UnifyValue for TypeVarValue {
     }
 }
 
+/// The kinds of placeholders we need during type inference. Besides normal
+/// type variables, we need int and float variables for inference of literal
+/// values (e.g. `100` could be one of several integer types).
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum InferTy {
     TypeVar(TypeVarId),
-    // later we'll have IntVar and FloatVar as well
+    /// A variable for an integer literal without suffix; it can only be
+    /// unified with integer types and defaults to `i32`.
+    IntVar(TypeVarId),
+    /// A variable for a float literal without suffix; it can only be unified
+    /// with float types and defaults to `f64`.
+    FloatVar(TypeVarId),
+}
+
+impl InferTy {
+    fn to_inner(&self) -> TypeVarId {
+        match self {
+            InferTy::TypeVar(ty) | InferTy::IntVar(ty) | InferTy::FloatVar(ty) => *ty,
+        }
+    }
+
+    /// The type a variable of this kind resolves to if nothing else is known
+    /// about it.
+    fn fallback_value(&self) -> Ty {
+        match self {
+            InferTy::TypeVar(..) => Ty::Unknown,
+            InferTy::IntVar(..) => Ty::Int(primitive::IntTy::I32),
+            InferTy::FloatVar(..) => Ty::Float(primitive::FloatTy::F64),
+        }
+    }
 }
 
 /// When inferring an expression, we propagate downward whatever type hint we
@@ -86,9 +120,8 @@ pub enum InferTy {
 #[derive(Clone, PartialEq, Eq, Debug)]
 struct Expectation {
     ty: Ty,
-    // TODO: In some cases, we need to be aware whether the expectation is that
-    // the type match exactly what we passed, or whether it just needs to be
-    // coercible to the expected type. See Expectation::rvalue_hint in rustc.
+    // Whether the type needs to match exactly or only be coercible to `ty` is
+    // decided by the caller: coercion sites use `infer_expr_coerce`.
 }
 
 impl Expectation {
@@ -125,14 +158,16 @@ pub enum Ty {
         def_id: DefId,
         /// The name, for displaying.
         name: Name,
-        // later we'll need generic substitutions here
+        /// Substitutions for the generic parameters of the type.
+        substs: Substs,
     },
 
     /// The pointee of a string slice. Written as `str`.
     Str,
 
-    // An array with the given length. Written as `[T; n]`.
-    // Array(Ty, ty::Const),
+    /// An array with the given length. Written as `[T; n]`.
+    Array(TyRef, ArrayLen),
+
     /// The pointee of an array slice.  Written as `[T]`.
     Slice(TyRef),
 
@@ -143,6 +178,20 @@ pub enum Ty {
     /// `&'a mut T` or `&'a T`.
     Ref(TyRef, Mutability),
 
+    /// The definition of a function. This is the type of `foo` itself in
+    /// `fn foo() -> i32 { 1 }`, as opposed to `fn() -> i32`, which is a
+    /// pointer. The signature is kept with the function's generic parameters
+    /// uninstantiated; `substs` holds the substitutions for them.
+    FnDef {
+        /// The DefId of the function.
+        def_id: DefId,
+        /// The name, for displaying.
+        name: Name,
+        sig: Arc<FnSig>,
+        /// Substitutions for the generic parameters of the function.
+        substs: Substs,
+    },
+
     /// A pointer to a function.  Written as `fn() -> i32`.
     ///
     /// For example the type of `bar` here:
@@ -153,11 +202,15 @@ pub enum Ty {
     /// ```
     FnPtr(Arc<FnSig>),
 
-    // A trait, defined with `dyn trait`.
-    // Dynamic(),
-    // The anonymous type of a closure. Used to represent the type of
-    // `|a| a`.
-    // Closure(DefId, ClosureSubsts<'tcx>),
+    /// The anonymous type of a closure. Used to represent the type of
+    /// `|a| a`.
+    Closure {
+        /// The DefId of the function containing the closure.
+        def_id: DefId,
+        /// The lambda expression of the closure.
+        expr: ExprId,
+        sig: Arc<FnSig>,
+    },
 
     // The anonymous type of a generator. Used to represent the type of
     // `|a| yield a`.
@@ -172,19 +225,30 @@ pub enum Ty {
     /// A tuple type.  For example, `(i32, bool)`.
     Tuple(Arc<[Ty]>),
 
-    // The projection of an associated type.  For example,
-    // `<T as Trait<..>>::N`.pub
-    // Projection(ProjectionTy),
-
-    // Opaque (`impl Trait`) type found in a return type.
-    // The `DefId` comes either from
-    // * the `impl Trait` ast::Ty node,
-    // * or the `existential type` declaration
-    // The substitutions are for the generics of the function in question.
-    // Opaque(DefId, Substs),
+    /// The projection of an associated type. For example,
+    /// `<T as Trait<..>>::N`. Projections are normalized to the type given in
+    /// the matching impl once the self type is known.
+    Projection(ProjectionTy),
+
+    /// A trait object, written as `dyn Trait + ..`. It implements the traits
+    /// of its bounds.
+    Dynamic(Arc<[TraitBound]>),
+
+    /// An opaque type, written as `impl Trait + ..`, e.g. in a return type.
+    /// All we know about it are its bounds.
+    // TODO: the opaque types of different functions are different, even if
+    // their bounds are the same; identify them by the function's DefId
+    Opaque(Arc<[TraitBound]>),
+
+    /// A type parameter; for example, `T` in `fn f<T>(x: T) {}
+    Param {
+        /// The index of the parameter in the generic parameter list of its
+        /// definition.
+        idx: u32,
+        /// The name of the parameter, for displaying.
+        name: Name,
+    },
 
-    // A type parameter; for example, `T` in `fn f<T>(x: T) {}
-    // Param(ParamTy),
     /// A type variable used during type checking. Not to be confused with a
     /// type parameter.
     Infer(InferTy),
@@ -198,16 +262,200 @@ pub enum Ty {
 
 type TyRef = Arc<Ty>;
 
+/// The length of an array type, if we were able to evaluate it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ArrayLen {
+    Known(u64),
+    Unknown,
+}
+
+impl ArrayLen {
+    fn eval(db: &impl HirDatabase, module: &Module, len: &ConstExpr) -> Cancelable<ArrayLen> {
+        Ok(len.eval(db, module)?.map_or(ArrayLen::Unknown, ArrayLen::Known))
+    }
+}
+
+impl fmt::Display for ArrayLen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArrayLen::Known(len) => write!(f, "{}", len),
+            ArrayLen::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+/// A list of substitutions for generic parameters.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Substs(Arc<[Ty]>);
+
+impl Substs {
+    pub fn empty() -> Substs {
+        Substs(Arc::new([]))
+    }
+
+    /// The identity substitution for the given generics, i.e. every parameter
+    /// (including those of the parent) is substituted by itself.
+    fn identity(generics: &GenericParams) -> Substs {
+        Substs(
+            generics
+                .params_including_parent()
+                .into_iter()
+                .map(|p| Ty::Param {
+                    idx: p.idx,
+                    name: p.name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .into(),
+        )
+    }
+
+    pub fn tys(&self) -> &[Ty] {
+        &self.0
+    }
+
+    fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
+        // Without an Arc::make_mut_slice, we can't avoid the clone here:
+        let mut v: Vec<_> = self.0.iter().cloned().collect();
+        for t in &mut v {
+            t.walk_mut(f);
+        }
+        self.0 = v.into();
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FnSig {
     input: Vec<Ty>,
     output: Ty,
 }
 
+/// An associated type of a trait, applied to a self type and the trait's
+/// generic arguments, e.g. `<Vec<u32> as IntoIterator>::Item`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ProjectionTy {
+    /// The DefId of the trait.
+    pub(crate) trait_def_id: DefId,
+    /// The name of the trait, for displaying.
+    pub(crate) trait_name: Name,
+    /// The name of the associated type.
+    pub(crate) name: Name,
+    /// The self type, followed by the generic arguments of the trait.
+    pub(crate) substs: Substs,
+}
+
+impl ProjectionTy {
+    pub fn self_ty(&self) -> &Ty {
+        &self.substs.0[0]
+    }
+
+    fn trait_substs(&self) -> Substs {
+        Substs(self.substs.0[1..].into())
+    }
+}
+
+/// A trait bound of a `dyn Trait` or `impl Trait` type, e.g.
+/// `Iterator<Item = u32>`. The self type is the bounded type itself, so it's
+/// left out.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TraitBound {
+    /// The DefId of the trait.
+    pub(crate) trait_def_id: DefId,
+    /// The name of the trait, for displaying.
+    pub(crate) trait_name: Name,
+    /// The generic arguments of the trait.
+    pub(crate) substs: Substs,
+    /// The associated types given in the bound, like `Item = u32`.
+    pub(crate) assoc_types: Arc<[(Name, Ty)]>,
+}
+
+impl TraitBound {
+    /// Lowers the bound `path`, resolving the trait in `module`.
+    fn from_hir(
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        path: &Path,
+    ) -> Cancelable<Option<TraitBound>> {
+        let trait_def_id = ctry!(module.resolve_path(db, path)?.take_types());
+        let trait_def = match trait_def_id.resolve(db)? {
+            Def::Trait(t) => t,
+            _ => return Ok(None),
+        };
+        let substs = Ty::substs_from_path(db, module, generics, path, trait_def_id)?;
+        let mut assoc_types = Vec::new();
+        let args = path.segments.last().and_then(|s| s.args_and_bindings.as_ref());
+        if let Some(args) = args {
+            for (name, type_ref) in args.bindings.iter() {
+                let ty = Ty::from_hir(db, module, generics, type_ref)?;
+                assoc_types.push((name.clone(), ty));
+            }
+        }
+        Ok(Some(TraitBound {
+            trait_def_id,
+            trait_name: trait_def.name(db)?.unwrap_or_else(Name::missing),
+            substs,
+            assoc_types: assoc_types.into(),
+        }))
+    }
+
+    fn from_hir_all(
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        paths: &[Path],
+    ) -> Cancelable<Arc<[TraitBound]>> {
+        let mut bounds = Vec::new();
+        for path in paths {
+            bounds.extend(TraitBound::from_hir(db, module, generics, path)?);
+        }
+        Ok(bounds.into())
+    }
+
+    /// The value of the associated type `name` given in this bound, if any.
+    pub(crate) fn assoc_type(&self, name: &Name) -> Option<&Ty> {
+        self.assoc_types
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, ty)| ty)
+    }
+
+    fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
+        self.substs.walk_mut(f);
+        let mut v: Vec<_> = self.assoc_types.iter().cloned().collect();
+        for (_, t) in &mut v {
+            t.walk_mut(f);
+        }
+        self.assoc_types = v.into();
+    }
+}
+
+impl fmt::Display for TraitBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.trait_name)?;
+        if self.substs.0.is_empty() && self.assoc_types.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        let args = self.substs.0.iter().map(|ty| ty.to_string());
+        let assoc_types = self
+            .assoc_types
+            .iter()
+            .map(|(name, ty)| format!("{} = {}", name, ty));
+        for (i, arg) in args.chain(assoc_types).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ">")
+    }
+}
+
 impl Ty {
     pub(crate) fn from_hir(
         db: &impl HirDatabase,
         module: &Module,
+        generics: &GenericParams,
         type_ref: &TypeRef,
     ) -> Cancelable<Self> {
         Ok(match type_ref {
@@ -215,29 +463,38 @@ impl Ty {
             TypeRef::Tuple(inner) => {
                 let inner_tys = inner
                     .iter()
-                    .map(|tr| Ty::from_hir(db, module, tr))
+                    .map(|tr| Ty::from_hir(db, module, generics, tr))
                     .collect::<Cancelable<Vec<_>>>()?;
                 Ty::Tuple(inner_tys.into())
             }
-            TypeRef::Path(path) => Ty::from_hir_path(db, module, path)?,
+            TypeRef::Path(path) => Ty::from_hir_path(db, module, generics, path)?,
             TypeRef::RawPtr(inner, mutability) => {
-                let inner_ty = Ty::from_hir(db, module, inner)?;
+                let inner_ty = Ty::from_hir(db, module, generics, inner)?;
                 Ty::RawPtr(Arc::new(inner_ty), *mutability)
             }
-            TypeRef::Array(_inner) => Ty::Unknown, // TODO
+            TypeRef::Array(inner, len) => {
+                let inner_ty = Ty::from_hir(db, module, generics, inner)?;
+                Ty::Array(Arc::new(inner_ty), ArrayLen::eval(db, module, len)?)
+            }
             TypeRef::Slice(inner) => {
-                let inner_ty = Ty::from_hir(db, module, inner)?;
+                let inner_ty = Ty::from_hir(db, module, generics, inner)?;
                 Ty::Slice(Arc::new(inner_ty))
             }
             TypeRef::Reference(inner, mutability) => {
-                let inner_ty = Ty::from_hir(db, module, inner)?;
+                let inner_ty = Ty::from_hir(db, module, generics, inner)?;
                 Ty::Ref(Arc::new(inner_ty), *mutability)
             }
+            TypeRef::ImplTrait(paths) => {
+                Ty::Opaque(TraitBound::from_hir_all(db, module, generics, paths)?)
+            }
+            TypeRef::DynTrait(paths) => {
+                Ty::Dynamic(TraitBound::from_hir_all(db, module, generics, paths)?)
+            }
             TypeRef::Placeholder => Ty::Unknown,
             TypeRef::Fn(params) => {
                 let mut inner_tys = params
                     .iter()
-                    .map(|tr| Ty::from_hir(db, module, tr))
+                    .map(|tr| Ty::from_hir(db, module, generics, tr))
                     .collect::<Cancelable<Vec<_>>>()?;
                 let return_ty = inner_tys
                     .pop()
@@ -248,13 +505,55 @@ impl Ty {
                 };
                 Ty::FnPtr(Arc::new(sig))
             }
+            TypeRef::Projection {
+                self_type,
+                trait_path,
+                name,
+            } => {
+                let self_ty = Ty::from_hir(db, module, generics, self_type)?;
+                Ty::from_hir_projection(db, module, generics, self_ty, trait_path, name)?
+            }
             TypeRef::Error => Ty::Unknown,
         })
     }
 
+    /// Lowers `<self_ty as trait_path>::name`. The projection is left
+    /// unnormalized; that happens during inference.
+    fn from_hir_projection(
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        self_ty: Ty,
+        trait_path: &Path,
+        name: &Name,
+    ) -> Cancelable<Self> {
+        let trait_def_id = match module.resolve_path(db, trait_path)?.take_types() {
+            Some(def_id) => def_id,
+            None => return Ok(Ty::Unknown),
+        };
+        let trait_def = match trait_def_id.resolve(db)? {
+            Def::Trait(t) => t,
+            _ => return Ok(Ty::Unknown),
+        };
+        if trait_def.trait_data(db)?.associated_type(db, name)?.is_none() {
+            return Ok(Ty::Unknown);
+        }
+        let trait_substs = Ty::substs_from_path(db, module, generics, trait_path, trait_def_id)?;
+        let substs: Vec<_> = std::iter::once(self_ty)
+            .chain(trait_substs.0.iter().cloned())
+            .collect();
+        Ok(Ty::Projection(ProjectionTy {
+            trait_def_id,
+            trait_name: trait_def.name(db)?.unwrap_or_else(Name::missing),
+            name: name.clone(),
+            substs: Substs(substs.into()),
+        }))
+    }
+
     pub(crate) fn from_hir_path(
         db: &impl HirDatabase,
         module: &Module,
+        generics: &GenericParams,
         path: &Path,
     ) -> Cancelable<Self> {
         if let Some(name) = path.as_ident() {
@@ -265,6 +564,18 @@ impl Ty {
             } else if let Some(float_ty) = primitive::FloatTy::from_name(name) {
                 return Ok(Ty::Float(float_ty));
             }
+            match name.as_known_name() {
+                Some(KnownName::Bool) => return Ok(Ty::Bool),
+                Some(KnownName::Char) => return Ok(Ty::Char),
+                Some(KnownName::Str) => return Ok(Ty::Str),
+                _ => {}
+            }
+            if let Some(param) = generics.find_by_name(name) {
+                return Ok(Ty::Param {
+                    idx: param.idx,
+                    name: param.name.clone(),
+                });
+            }
         }
 
         // Resolve in module (in type namespace)
@@ -274,35 +585,58 @@ impl Ty {
             return Ok(Ty::Unknown);
         };
         let ty = db.type_for_def(resolved)?;
-        Ok(ty)
-    }
-
-    // TODO: These should not be necessary long-term, since everything will work on HIR
-    pub(crate) fn from_ast_opt(
-        db: &impl HirDatabase,
-        module: &Module,
-        node: Option<ast::TypeRef>,
-    ) -> Cancelable<Self> {
-        node.map(|n| Ty::from_ast(db, module, n))
-            .unwrap_or(Ok(Ty::Unknown))
+        let substs = Ty::substs_from_path(db, module, generics, path, resolved)?;
+        if let Def::Type(_) = resolved.resolve(db)? {
+            // the alias' own parameters occur in the aliased type
+            return Ok(ty.subst(&substs));
+        }
+        Ok(ty.apply_substs(substs))
     }
 
-    pub(crate) fn from_ast(
+    /// Collects the generic arguments of the last segment of `path` for the
+    /// generic parameters of `resolved`. Parameters without an explicit
+    /// argument, and those of the impl block of a method, are substituted by
+    /// `Ty::Unknown`.
+    fn substs_from_path(
         db: &impl HirDatabase,
         module: &Module,
-        node: ast::TypeRef,
-    ) -> Cancelable<Self> {
-        Ty::from_hir(db, module, &TypeRef::from_ast(node))
+        generics: &GenericParams,
+        path: &Path,
+        resolved: DefId,
+    ) -> Cancelable<Substs> {
+        let def_generics = db.generic_params(resolved);
+        let param_count = def_generics.count_params_including_parent();
+        let args = path
+            .segments
+            .last()
+            .and_then(|segment| segment.args_and_bindings.as_ref());
+        let mut substs = Vec::with_capacity(param_count);
+        substs.extend(iter::repeat(Ty::Unknown).take(def_generics.count_parent_params()));
+        if let Some(args) = args {
+            for arg in args.args.iter().take(def_generics.params.len()) {
+                let ty = match arg {
+                    GenericArg::Type(type_ref) => Ty::from_hir(db, module, generics, type_ref)?,
+                };
+                substs.push(ty);
+            }
+        }
+        // add Unknown for remaining parameters
+        // TODO: handle defaults
+        for _ in substs.len()..param_count {
+            substs.push(Ty::Unknown);
+        }
+        Ok(Substs(substs.into()))
     }
 
     pub fn unit() -> Self {
         Ty::Tuple(Arc::new([]))
     }
 
+    /// Calls `f` on all types contained in `self`, children first (so `f` can
+    /// replace a type without the replacement being visited again).
     fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
-        f(self);
         match self {
-            Ty::Slice(t) => Arc::make_mut(t).walk_mut(f),
+            Ty::Slice(t) | Ty::Array(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::RawPtr(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Ref(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Tuple(ts) => {
@@ -313,16 +647,48 @@ impl Ty {
                 }
                 *ts = v.into();
             }
-            Ty::FnPtr(sig) => {
+            Ty::FnPtr(sig) | Ty::Closure { sig, .. } => {
                 let sig_mut = Arc::make_mut(sig);
                 for input in &mut sig_mut.input {
                     input.walk_mut(f);
                 }
                 sig_mut.output.walk_mut(f);
             }
-            Ty::Adt { .. } => {} // need to walk type parameters later
+            Ty::Adt { substs, .. } => substs.walk_mut(f),
+            Ty::FnDef { substs, .. } => substs.walk_mut(f),
+            Ty::Projection(proj) => proj.substs.walk_mut(f),
+            Ty::Dynamic(bounds) | Ty::Opaque(bounds) => {
+                let mut v: Vec<_> = bounds.iter().cloned().collect();
+                for bound in &mut v {
+                    bound.walk_mut(f);
+                }
+                *bounds = v.into();
+            }
             _ => {}
         }
+        f(self);
+    }
+
+    /// Whether we know this type well enough to report a mismatch involving
+    /// it: it must be fully inferred, and not contain types we don't handle
+    /// completely yet, like type parameters (which aren't always substituted)
+    /// or `dyn Trait` (we don't do unsizing coercions to it).
+    fn is_reportable(&self) -> bool {
+        let mut reportable = true;
+        self.clone().walk_mut(&mut |ty| match ty {
+            Ty::Unknown
+            | Ty::Infer(_)
+            | Ty::Param { .. }
+            | Ty::Projection(_)
+            | Ty::Dynamic(_)
+            | Ty::Opaque(_)
+            | Ty::FnDef { .. }
+            | Ty::FnPtr(_)
+            | Ty::Closure { .. }
+            | Ty::RawPtr(..) => reportable = false,
+            _ => {}
+        });
+        reportable
     }
 
     fn fold(mut self, f: &mut impl FnMut(Ty) -> Ty) -> Ty {
@@ -332,6 +698,68 @@ impl Ty {
         });
         self
     }
+
+    /// Replaces the substitutions of an ADT or function type by `substs`.
+    pub fn apply_substs(self, substs: Substs) -> Ty {
+        match self {
+            Ty::Adt { def_id, name, .. } => Ty::Adt {
+                def_id,
+                name,
+                substs,
+            },
+            Ty::FnDef {
+                def_id, name, sig, ..
+            } => Ty::FnDef {
+                def_id,
+                name,
+                sig,
+                substs,
+            },
+            _ => self,
+        }
+    }
+
+    /// Replaces type parameters in this type using the given `Substs`. (So e.g.
+    /// if `self` is `&[T]`, where type parameter T has index 0, and the
+    /// `Substs` contain `u32` at index 0, we'll have `&[u32]` afterwards.)
+    pub fn subst(self, substs: &Substs) -> Ty {
+        self.fold(&mut |ty| match ty {
+            Ty::Param { idx, name } => {
+                if (idx as usize) < substs.0.len() {
+                    substs.0[idx as usize].clone()
+                } else {
+                    // not in the substitution, leave as is
+                    Ty::Param { idx, name }
+                }
+            }
+            ty => ty,
+        })
+    }
+
+    /// Returns the type parameters of this type if it has some (i.e. is an ADT
+    /// or function); so if `self` is `Option<u32>`, this returns the `u32`.
+    pub fn substs(&self) -> Option<Substs> {
+        match self {
+            Ty::Adt { substs, .. } | Ty::FnDef { substs, .. } => Some(substs.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Substs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        for (i, t) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", t)?;
+        }
+        write!(f, ">")
+    }
 }
 
 impl fmt::Display for Ty {
@@ -344,6 +772,7 @@ impl fmt::Display for Ty {
             Ty::Float(t) => write!(f, "{}", t.ty_to_string()),
             Ty::Str => write!(f, "str"),
             Ty::Slice(t) => write!(f, "[{}]", t),
+            Ty::Array(t, len) => write!(f, "[{}; {}]", t, len),
             Ty::RawPtr(t, m) => write!(f, "*{}{}", m.as_keyword_for_ptr(), t),
             Ty::Ref(t, m) => write!(f, "&{}{}", m.as_keyword_for_ref(), t),
             Ty::Never => write!(f, "!"),
@@ -361,7 +790,53 @@ impl fmt::Display for Ty {
                 }
                 write!(f, ") -> {}", sig.output)
             }
-            Ty::Adt { name, .. } => write!(f, "{}", name),
+            Ty::Closure { sig, .. } => {
+                write!(f, "|")?;
+                for (i, t) in sig.input.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", t)?;
+                }
+                write!(f, "| -> {}", sig.output)
+            }
+            Ty::FnDef {
+                name, sig, substs, ..
+            } => {
+                write!(f, "fn {}{}(", name, substs)?;
+                for (i, t) in sig.input.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", t.clone().subst(substs))?;
+                }
+                write!(f, ") -> {}", sig.output.clone().subst(substs))
+            }
+            Ty::Adt { name, substs, .. } => write!(f, "{}{}", name, substs),
+            Ty::Param { name, .. } => write!(f, "{}", name),
+            Ty::Projection(proj) => write!(
+                f,
+                "<{} as {}{}>::{}",
+                proj.self_ty(),
+                proj.trait_name,
+                proj.trait_substs(),
+                proj.name
+            ),
+            Ty::Dynamic(bounds) | Ty::Opaque(bounds) => {
+                let keyword = if let Ty::Dynamic(_) = self {
+                    "dyn"
+                } else {
+                    "impl"
+                };
+                write!(f, "{} ", keyword)?;
+                for (i, bound) in bounds.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " + ")?;
+                    }
+                    write!(f, "{}", bound)?;
+                }
+                Ok(())
+            }
             Ty::Unknown => write!(f, "[unknown]"),
             Ty::Infer(..) => write!(f, "_"),
         }
@@ -369,41 +844,142 @@ impl fmt::Display for Ty {
 }
 
 pub fn type_for_fn(db: &impl HirDatabase, f: Function) -> Cancelable<Ty> {
-    let syntax = f.syntax(db);
+    let signature = f.signature(db);
     let module = f.module(db)?;
-    let node = syntax.borrowed();
-    // TODO we ignore type parameters for now
-    let input = node
-        .param_list()
-        .map(|pl| {
-            pl.params()
-                .map(|p| Ty::from_ast_opt(db, &module, p.type_ref()))
-                .collect()
-        })
-        .unwrap_or_else(|| Ok(Vec::new()))?;
-    let output = if let Some(type_ref) = node.ret_type().and_then(|rt| rt.type_ref()) {
-        Ty::from_ast(db, &module, type_ref)?
-    } else {
-        Ty::unit()
+    let generics = f.generic_params(db);
+    let impl_self_type = f.impl_block(db)?.map(|b| b.target_type().clone());
+    let lower = |type_ref: &TypeRef| match &impl_self_type {
+        Some(self_type) => {
+            let type_ref = type_ref.replace_self_type(self_type);
+            Ty::from_hir(db, &module, &generics, &type_ref)
+        }
+        None => Ty::from_hir(db, &module, &generics, type_ref),
     };
+    // the self parameter (if any) is the first input, as in `S::method(&s)`
+    let input = signature
+        .self_param()
+        .into_iter()
+        .chain(signature.params())
+        .map(lower)
+        .collect::<Cancelable<Vec<_>>>()?;
+    let output = lower(signature.ret_type())?;
     let sig = FnSig { input, output };
-    Ok(Ty::FnPtr(Arc::new(sig)))
+    Ok(Ty::FnDef {
+        def_id: f.def_id(),
+        name: signature.name().clone(),
+        sig: Arc::new(sig),
+        substs: Substs::identity(&generics),
+    })
 }
 
 pub fn type_for_struct(db: &impl HirDatabase, s: Struct) -> Cancelable<Ty> {
+    let generics = s.generic_params(db);
     Ok(Ty::Adt {
         def_id: s.def_id(),
         name: s.name(db)?.unwrap_or_else(Name::missing),
+        substs: Substs::identity(&generics),
     })
 }
 
 pub fn type_for_enum(db: &impl HirDatabase, s: Enum) -> Cancelable<Ty> {
+    let generics = s.generic_params(db);
     Ok(Ty::Adt {
         def_id: s.def_id(),
         name: s.name(db)?.unwrap_or_else(Name::missing),
+        substs: Substs::identity(&generics),
+    })
+}
+
+/// The type of an enum variant in the value namespace: a constructor function
+/// for tuple variants, the enum itself otherwise.
+pub fn type_for_enum_variant(db: &impl HirDatabase, ev: EnumVariant) -> Cancelable<Ty> {
+    let enum_ty = type_for_enum(db, ev.parent_enum(db)?)?;
+    let variant_data = ev.variant_data(db)?;
+    if !variant_data.is_tuple() {
+        return Ok(enum_ty);
+    }
+    let module = ev.def_id().module(db)?;
+    let generics = db.generic_params(ev.def_id());
+    let input = variant_data
+        .fields()
+        .iter()
+        .map(|field| Ty::from_hir(db, &module, &generics, field.type_ref()))
+        .collect::<Cancelable<Vec<_>>>()?;
+    let sig = FnSig {
+        input,
+        output: enum_ty,
+    };
+    Ok(Ty::FnDef {
+        def_id: ev.def_id(),
+        name: ev.name(db)?.unwrap_or_else(Name::missing),
+        sig: Arc::new(sig),
+        substs: Substs::identity(&generics),
     })
 }
 
+/// The aliased type of a type alias, in terms of the alias' own type
+/// parameters.
+pub fn type_for_type_alias(db: &impl HirDatabase, t: Type) -> Cancelable<Ty> {
+    if is_cyclic_type_alias(db, t)? {
+        return Ok(Ty::Unknown);
+    }
+    let data = t.type_alias_data(db)?;
+    let module = t.def_id().module(db)?;
+    let generics = t.generic_params(db);
+    Ty::from_hir(db, &module, &generics, data.type_ref())
+}
+
+/// Whether the aliased type of `t` refers to `t` itself, directly or through
+/// other aliases, like in `type A = B; type B = A;`. Lowering such an alias
+/// would run into a query cycle.
+fn is_cyclic_type_alias(db: &impl HirDatabase, t: Type) -> Cancelable<bool> {
+    let mut visited = FxHashSet::default();
+    let mut stack = vec![t];
+    while let Some(alias) = stack.pop() {
+        let module = alias.def_id().module(db)?;
+        let generics = alias.generic_params(db);
+        let mut paths = Vec::new();
+        alias
+            .type_alias_data(db)?
+            .type_ref()
+            .walk_paths(&mut |path| paths.push(path.clone()));
+        for path in paths {
+            let is_param = path
+                .as_ident()
+                .map_or(false, |name| generics.find_by_name(name).is_some());
+            if is_param {
+                continue;
+            }
+            let resolved = match module.resolve_path(db, &path)?.take_types() {
+                Some(it) => it,
+                None => continue,
+            };
+            if resolved == t.def_id() {
+                return Ok(true);
+            }
+            if !visited.insert(resolved) {
+                continue;
+            }
+            if let Def::Type(alias) = resolved.resolve(db)? {
+                stack.push(alias);
+            }
+        }
+    }
+    Ok(false)
+}
+
+pub fn type_for_const(db: &impl HirDatabase, c: Const) -> Cancelable<Ty> {
+    let data = c.const_data(db)?;
+    let module = c.def_id().module(db)?;
+    Ty::from_hir(db, &module, &GenericParams::default(), data.type_ref())
+}
+
+pub fn type_for_static(db: &impl HirDatabase, s: Static) -> Cancelable<Ty> {
+    let data = s.const_data(db)?;
+    let module = s.def_id().module(db)?;
+    Ty::from_hir(db, &module, &GenericParams::default(), data.type_ref())
+}
+
 pub fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ty> {
     let def = def_id.resolve(db)?;
     match def {
@@ -414,6 +990,14 @@ pub fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ty> {
         Def::Function(f) => type_for_fn(db, f),
         Def::Struct(s) => type_for_struct(db, s),
         Def::Enum(e) => type_for_enum(db, e),
+        Def::EnumVariant(ev) => type_for_enum_variant(db, ev),
+        Def::Trait(..) => {
+            log::debug!("trying to get type for trait {:?}", def_id);
+            Ok(Ty::Unknown)
+        }
+        Def::Type(t) => type_for_type_alias(db, t),
+        Def::Const(c) => type_for_const(db, c),
+        Def::Static(s) => type_for_static(db, s),
         Def::Item => {
             log::debug!("trying to get type for item of unknown type {:?}", def_id);
             Ok(Ty::Unknown)
@@ -421,77 +1005,279 @@ pub fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ty> {
     }
 }
 
-pub(super) fn type_for_field(db: &impl HirDatabase, def_id: DefId, field: Name) -> Cancelable<Ty> {
+/// Returns the type of the field `field` of the struct or enum variant
+/// `def_id`, or `None` if there is no such field.
+pub(super) fn type_for_field(
+    db: &impl HirDatabase,
+    def_id: DefId,
+    field: Name,
+) -> Cancelable<Option<Ty>> {
     let def = def_id.resolve(db)?;
     let variant_data = match def {
-        Def::Struct(s) => {
-            let variant_data = s.variant_data(db)?;
-            variant_data
-        }
+        Def::Struct(s) => s.variant_data(db)?,
+        Def::EnumVariant(ev) => ev.variant_data(db)?,
         // TODO: unions
-        // TODO: enum variants
-        _ => panic!(
-            "trying to get type for field in non-struct/variant {:?}",
-            def_id
-        ),
+        _ => return Ok(None),
     };
     let module = def_id.module(db)?;
-    let type_ref = if let Some(tr) = variant_data.get_field_type_ref(&field) {
-        tr
-    } else {
-        return Ok(Ty::Unknown);
-    };
-    Ty::from_hir(db, &module, &type_ref)
+    let generics = db.generic_params(def_id);
+    let type_ref = ctry!(variant_data.get_field_type_ref(&field));
+    Ok(Some(Ty::from_hir(db, &module, &generics, &type_ref)?))
 }
 
+/// The result of type inference: A mapping from expressions and patterns to
+/// types, and the type errors found along the way.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct InferenceResult {
-    type_of: FxHashMap<LocalSyntaxPtr, Ty>,
+    type_of_expr: FxHashMap<ExprId, Ty>,
+    type_of_pat: FxHashMap<PatId, Ty>,
+    diagnostics: Vec<InferenceDiagnostic>,
 }
 
 impl InferenceResult {
-    pub fn type_of_node(&self, node: SyntaxNodeRef) -> Option<Ty> {
-        self.type_of.get(&LocalSyntaxPtr::new(node)).cloned()
+    pub fn diagnostics(&self) -> &[InferenceDiagnostic] {
+        &self.diagnostics
+    }
+}
+
+/// A type error found during inference. We only report errors we're fairly
+/// sure about, since our inference is still incomplete.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InferenceDiagnostic {
+    /// The type of `expr` doesn't match, or coerce to, the expected type.
+    MismatchedType {
+        expr: ExprId,
+        expected: Ty,
+        actual: Ty,
+    },
+    /// The path expression `expr` doesn't resolve to anything.
+    UnresolvedPath { expr: ExprId },
+    /// The call or method call `expr` has `found` arguments instead of
+    /// `expected` (not counting the receiver of a method call).
+    WrongArgCount {
+        expr: ExprId,
+        expected: usize,
+        found: usize,
+    },
+    /// The field `field` doesn't exist. `expr` is either a field access or the
+    /// value of the field in a struct literal.
+    NoSuchField { expr: ExprId, field: Name },
+}
+
+impl Index<ExprId> for InferenceResult {
+    type Output = Ty;
+
+    fn index(&self, expr: ExprId) -> &Ty {
+        self.type_of_expr.get(&expr).unwrap_or(&Ty::Unknown)
+    }
+}
+
+impl Index<PatId> for InferenceResult {
+    type Output = Ty;
+
+    fn index(&self, pat: PatId) -> &Ty {
+        self.type_of_pat.get(&pat).unwrap_or(&Ty::Unknown)
     }
 }
 
+/// A loop we're currently inferring, which `break` expressions refer to.
+#[derive(Clone, Debug)]
+struct BreakableContext {
+    /// The loop's label, which labeled `break`s refer to.
+    label: Option<Name>,
+    /// Whether there is a `break` for this loop; a `loop` without one diverges.
+    may_break: bool,
+    /// The type of the values given to `break`, i.e. the type of the loop.
+    break_ty: Ty,
+}
+
+/// The inference context contains all information needed during type inference.
 #[derive(Clone, Debug)]
-pub struct InferenceContext<'a, D: HirDatabase> {
+struct InferenceContext<'a, D: HirDatabase> {
     db: &'a D,
+    /// The function being inferred.
+    def_id: DefId,
+    body: Arc<Body>,
     scopes: Arc<FnScopes>,
     module: Module,
+    /// The generic parameters of the function being inferred.
+    generics: Arc<GenericParams>,
+    /// The target type of the impl block containing the function, which is
+    /// what `Self` refers to.
+    impl_self_type: Option<TypeRef>,
     var_unification_table: InPlaceUnificationTable<TypeVarId>,
-    type_of: FxHashMap<LocalSyntaxPtr, Ty>,
+    type_of_expr: FxHashMap<ExprId, Ty>,
+    type_of_pat: FxHashMap<PatId, Ty>,
+    /// The return type of the function (or closure) being inferred; the
+    /// expected type for `return` expressions.
+    return_ty: Ty,
+    /// The loops around the current expression, innermost last.
+    breakables: Vec<BreakableContext>,
+    /// Whether we're inside an `unsafe` block or `unsafe fn`, i.e. whether raw
+    /// pointers may be dereferenced.
+    in_unsafe_context: bool,
+    diagnostics: Vec<InferenceDiagnostic>,
 }
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
-    fn new(db: &'a D, scopes: Arc<FnScopes>, module: Module) -> Self {
+    fn new(
+        db: &'a D,
+        def_id: DefId,
+        body: Arc<Body>,
+        scopes: Arc<FnScopes>,
+        module: Module,
+        generics: Arc<GenericParams>,
+        impl_self_type: Option<TypeRef>,
+    ) -> Self {
         InferenceContext {
-            type_of: FxHashMap::default(),
+            type_of_expr: FxHashMap::default(),
+            type_of_pat: FxHashMap::default(),
             var_unification_table: InPlaceUnificationTable::new(),
             db,
+            def_id,
+            body,
             scopes,
             module,
+            generics,
+            impl_self_type,
+            return_ty: Ty::Unknown,
+            breakables: Vec::new(),
+            in_unsafe_context: false,
+            diagnostics: Vec::new(),
         }
     }
 
     fn resolve_all(mut self) -> InferenceResult {
-        let mut types = mem::replace(&mut self.type_of, FxHashMap::default());
-        for ty in types.values_mut() {
+        let mut expr_types = mem::replace(&mut self.type_of_expr, FxHashMap::default());
+        for ty in expr_types.values_mut() {
+            let resolved = self.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
+            *ty = resolved;
+        }
+        let mut pat_types = mem::replace(&mut self.type_of_pat, FxHashMap::default());
+        for ty in pat_types.values_mut() {
             let resolved = self.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
             *ty = resolved;
         }
-        InferenceResult { type_of: types }
+        let diagnostics = self.resolve_diagnostics();
+        InferenceResult {
+            type_of_expr: expr_types,
+            type_of_pat: pat_types,
+            diagnostics,
+        }
+    }
+
+    /// Resolves the types in the collected diagnostics, dropping the type
+    /// mismatches we can't be sure about, and those caused by a mismatch in a
+    /// subexpression.
+    fn resolve_diagnostics(&mut self) -> Vec<InferenceDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for diagnostic in mem::replace(&mut self.diagnostics, Vec::new()) {
+            let diagnostic = match diagnostic {
+                InferenceDiagnostic::MismatchedType {
+                    expr,
+                    expected,
+                    actual,
+                } => {
+                    let expected = self.resolve_ty_completely(expected);
+                    let actual = self.resolve_ty_completely(actual);
+                    if !expected.is_reportable() || !actual.is_reportable() {
+                        continue;
+                    }
+                    InferenceDiagnostic::MismatchedType {
+                        expr,
+                        expected,
+                        actual,
+                    }
+                }
+                _ => diagnostic,
+            };
+            diagnostics.push(diagnostic);
+        }
+        let mismatched = diagnostics
+            .iter()
+            .filter_map(|d| match d {
+                InferenceDiagnostic::MismatchedType { expr, .. } => Some(*expr),
+                _ => None,
+            })
+            .collect::<FxHashSet<_>>();
+        diagnostics.retain(|d| match d {
+            InferenceDiagnostic::MismatchedType { expr, .. } => {
+                !has_mismatched_descendant(&self.body, *expr, &mismatched)
+            }
+            _ => true,
+        });
+        diagnostics
+    }
+
+    fn push_diagnostic(&mut self, diagnostic: InferenceDiagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn report_mismatch(&mut self, expr: ExprId, expected: &Ty, actual: &Ty) {
+        self.push_diagnostic(InferenceDiagnostic::MismatchedType {
+            expr,
+            expected: expected.clone(),
+            actual: actual.clone(),
+        });
+    }
+
+    fn write_expr_ty(&mut self, expr: ExprId, ty: Ty) {
+        self.type_of_expr.insert(expr, ty);
+    }
+
+    fn write_pat_ty(&mut self, pat: PatId, ty: Ty) {
+        self.type_of_pat.insert(pat, ty);
+    }
+
+    fn unify_substs(&mut self, substs1: &Substs, substs2: &Substs) -> bool {
+        substs1
+            .0
+            .iter()
+            .zip(substs2.0.iter())
+            .all(|(t1, t2)| self.unify(t1, t2))
     }
 
-    fn write_ty(&mut self, node: SyntaxNodeRef, ty: Ty) {
-        self.type_of.insert(LocalSyntaxPtr::new(node), ty);
+    fn unify_bounds(&mut self, bounds1: &[TraitBound], bounds2: &[TraitBound]) -> bool {
+        bounds1.len() == bounds2.len()
+            && bounds1.iter().zip(bounds2.iter()).all(|(b1, b2)| {
+                b1.trait_def_id == b2.trait_def_id
+                    && b1.assoc_types.len() == b2.assoc_types.len()
+                    && self.unify_substs(&b1.substs, &b2.substs)
+                    && b1
+                        .assoc_types
+                        .iter()
+                        .zip(b2.assoc_types.iter())
+                        .all(|((n1, t1), (n2, t2))| n1 == n2 && self.unify(t1, t2))
+            })
     }
 
     fn unify(&mut self, ty1: &Ty, ty2: &Ty) -> bool {
-        match (ty1, ty2) {
+        // try to resolve type vars first
+        let ty1 = self.resolve_ty_shallow(ty1);
+        let ty2 = self.resolve_ty_shallow(ty2);
+        match (&*ty1, &*ty2) {
             (Ty::Unknown, ..) => true,
             (.., Ty::Unknown) => true,
+            (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2)))
+            | (Ty::Infer(InferTy::IntVar(tv1)), Ty::Infer(InferTy::IntVar(tv2)))
+            | (Ty::Infer(InferTy::FloatVar(tv1)), Ty::Infer(InferTy::FloatVar(tv2))) => {
+                // both type vars are unknown since we tried to resolve them
+                self.var_unification_table.union(*tv1, *tv2);
+                true
+            }
+            (Ty::Infer(InferTy::TypeVar(tv)), other)
+            | (other, Ty::Infer(InferTy::TypeVar(tv)))
+            | (Ty::Infer(InferTy::IntVar(tv)), other @ Ty::Int(_))
+            | (other @ Ty::Int(_), Ty::Infer(InferTy::IntVar(tv)))
+            | (Ty::Infer(InferTy::IntVar(tv)), other @ Ty::Uint(_))
+            | (other @ Ty::Uint(_), Ty::Infer(InferTy::IntVar(tv)))
+            | (Ty::Infer(InferTy::FloatVar(tv)), other @ Ty::Float(_))
+            | (other @ Ty::Float(_), Ty::Infer(InferTy::FloatVar(tv))) => {
+                // the type var is unknown since we tried to resolve it
+                self.var_unification_table
+                    .union_value(*tv, TypeVarValue::Known(other.clone()));
+                true
+            }
             (Ty::Bool, _)
             | (Ty::Str, _)
             | (Ty::Never, _)
@@ -501,38 +1287,103 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             | (Ty::Float(..), Ty::Float(..)) => ty1 == ty2,
             (
                 Ty::Adt {
-                    def_id: def_id1, ..
+                    def_id: def_id1,
+                    substs: substs1,
+                    ..
                 },
                 Ty::Adt {
-                    def_id: def_id2, ..
+                    def_id: def_id2,
+                    substs: substs2,
+                    ..
                 },
-            ) if def_id1 == def_id2 => true,
+            ) if def_id1 == def_id2 => self.unify_substs(substs1, substs2),
+            (
+                Ty::FnDef {
+                    def_id: def_id1,
+                    substs: substs1,
+                    ..
+                },
+                Ty::FnDef {
+                    def_id: def_id2,
+                    substs: substs2,
+                    ..
+                },
+            ) if def_id1 == def_id2 => self.unify_substs(substs1, substs2),
+            (Ty::Param { idx: idx1, .. }, Ty::Param { idx: idx2, .. }) => idx1 == idx2,
+            (Ty::Projection(proj1), Ty::Projection(proj2))
+                if proj1.trait_def_id == proj2.trait_def_id && proj1.name == proj2.name =>
+            {
+                self.unify_substs(&proj1.substs, &proj2.substs)
+            }
             (Ty::Slice(t1), Ty::Slice(t2)) => self.unify(t1, t2),
+            (Ty::Dynamic(bounds1), Ty::Dynamic(bounds2))
+            | (Ty::Opaque(bounds1), Ty::Opaque(bounds2)) => self.unify_bounds(bounds1, bounds2),
+            (Ty::Array(t1, len1), Ty::Array(t2, len2))
+                if len1 == len2 || *len1 == ArrayLen::Unknown || *len2 == ArrayLen::Unknown =>
+            {
+                self.unify(t1, t2)
+            }
             (Ty::RawPtr(t1, m1), Ty::RawPtr(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::Ref(t1, m1), Ty::Ref(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::FnPtr(sig1), Ty::FnPtr(sig2)) if sig1 == sig2 => true,
+            (
+                Ty::Closure {
+                    def_id: def_id1,
+                    expr: expr1,
+                    ..
+                },
+                Ty::Closure {
+                    def_id: def_id2,
+                    expr: expr2,
+                    ..
+                },
+            ) => def_id1 == def_id2 && expr1 == expr2,
             (Ty::Tuple(ts1), Ty::Tuple(ts2)) if ts1.len() == ts2.len() => ts1
                 .iter()
                 .zip(ts2.iter())
                 .all(|(t1, t2)| self.unify(t1, t2)),
-            (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2))) => {
-                self.var_unification_table.union(*tv1, *tv2);
-                true
-            }
-            (Ty::Infer(InferTy::TypeVar(tv)), other) | (other, Ty::Infer(InferTy::TypeVar(tv))) => {
-                self.var_unification_table
-                    .union_value(*tv, TypeVarValue::Known(other.clone()));
-                true
-            }
             _ => false,
         }
     }
 
-    fn new_type_var(&mut self) -> Ty {
-        Ty::Infer(InferTy::TypeVar(
-            self.var_unification_table.new_key(TypeVarValue::Unknown),
-        ))
-    }
+    /// Lowers `type_ref` to a type, normalizing projections and replacing
+    /// unknown parts by type variables.
+    fn make_ty(&mut self, type_ref: &TypeRef) -> Cancelable<Ty> {
+        let ty = match &self.impl_self_type {
+            Some(self_type) => {
+                let type_ref = type_ref.replace_self_type(self_type);
+                Ty::from_hir(self.db, &self.module, &self.generics, &type_ref)?
+            }
+            None => Ty::from_hir(self.db, &self.module, &self.generics, type_ref)?,
+        };
+        let ty = ty.normalize(self.db)?;
+        Ok(self.insert_type_vars(ty))
+    }
+
+    /// Resolves the known type variables in `ty`, so that projections on them
+    /// can be normalized, and normalizes them.
+    fn normalize(&mut self, ty: Ty) -> Cancelable<Ty> {
+        let ty = self.resolve_ty_as_possible(ty);
+        ty.normalize(self.db)
+    }
+
+    fn new_type_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::TypeVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
+
+    fn new_integer_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::IntVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
+
+    fn new_float_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::FloatVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
 
     /// Replaces Ty::Unknown by a new type var, so we can maybe still infer it.
     fn insert_type_vars_shallow(&mut self, ty: Ty) -> Ty {
@@ -552,61 +1403,204 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     /// known type.
     fn resolve_ty_as_possible(&mut self, ty: Ty) -> Ty {
         ty.fold(&mut |ty| match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                if let Some(known_ty) = self.var_unification_table.probe_value(tv).known() {
+            Ty::Infer(tv) => {
+                let inner = tv.to_inner();
+                if let Some(known_ty) = self.var_unification_table.probe_value(inner).known() {
                     // known_ty may contain other variables that are known by now
                     self.resolve_ty_as_possible(known_ty.clone())
                 } else {
-                    Ty::Infer(InferTy::TypeVar(tv))
+                    Ty::Infer(tv)
                 }
             }
             _ => ty,
         })
     }
 
+    /// If `ty` is a type variable with known type, returns that type;
+    /// otherwise, return ty.
+    fn resolve_ty_shallow<'b>(&mut self, ty: &'b Ty) -> Cow<'b, Ty> {
+        let mut ty = Cow::Borrowed(ty);
+        // The type variable could resolve to a type variable with a known
+        // type, so we need to loop here.
+        while let Ty::Infer(tv) = &*ty {
+            match self.var_unification_table.probe_value(tv.to_inner()).known() {
+                Some(known_ty) => ty = Cow::Owned(known_ty.clone()),
+                None => break,
+            }
+        }
+        ty
+    }
+
     /// Resolves the type completely; type variables without known type are
-    /// replaced by Ty::Unknown.
+    /// replaced by their fallback value, i.e. Ty::Unknown for normal type
+    /// variables and `i32`/`f64` for integer/float variables.
     fn resolve_ty_completely(&mut self, ty: Ty) -> Ty {
         ty.fold(&mut |ty| match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                if let Some(known_ty) = self.var_unification_table.probe_value(tv).known() {
+            Ty::Infer(tv) => {
+                let inner = tv.to_inner();
+                if let Some(known_ty) = self.var_unification_table.probe_value(inner).known() {
                     // known_ty may contain other variables that are known by now
                     self.resolve_ty_completely(known_ty.clone())
                 } else {
-                    Ty::Unknown
+                    tv.fallback_value()
                 }
             }
             _ => ty,
         })
     }
 
-    fn infer_path_expr(&mut self, expr: ast::PathExpr) -> Cancelable<Option<Ty>> {
-        let ast_path = ctry!(expr.path());
-        let path = ctry!(Path::from_ast(ast_path));
-        if path.is_ident() {
+    fn infer_path_expr(&mut self, expr: ExprId, path: &Path) -> Cancelable<Option<Ty>> {
+        if path.is_ident() || path.is_self() {
             // resolve locally
-            let name = ctry!(ast_path.segment().and_then(|s| s.name_ref()));
-            if let Some(scope_entry) = self.scopes.resolve_local_name(name) {
-                let ty = ctry!(self.type_of.get(&scope_entry.ptr()));
+            let name = path.as_ident().cloned().unwrap_or_else(Name::self_param);
+            if let Some(scope_entry) = self.scopes.resolve_local_name(expr, name) {
+                let ty = ctry!(self.type_of_pat.get(&scope_entry.pat()));
                 let ty = self.resolve_ty_as_possible(ty.clone());
                 return Ok(Some(ty));
             };
         };
 
+        // `Self::new` refers to the associated function of the impl's type
+        let path = match self.replace_self_type_prefix(path) {
+            Some(path) => Cow::Owned(path),
+            None => Cow::Borrowed(path),
+        };
+        let path = &*path;
+
         // resolve in module
-        let resolved = ctry!(self.module.resolve_path(self.db, &path)?.take_values());
+        let (resolved, self_ty) = match self.module.resolve_path(self.db, path)?.take_values() {
+            Some(def_id) => (def_id, None),
+            None => {
+                let (self_ty, def_id) = ctry!(self.resolve_associated_fn(path)?);
+                (def_id, Some(self_ty))
+            }
+        };
         let ty = self.db.type_for_def(resolved)?;
-        let ty = self.insert_type_vars(ty);
+        let substs =
+            Ty::substs_from_path(self.db, &self.module, &self.generics, path, resolved)?;
+        let ty = self.insert_type_vars(ty.apply_substs(substs));
+        if let (Some(self_ty), Some(substs)) = (self_ty, ty.substs()) {
+            self.unify_impl_self_ty(resolved, &substs, &self_ty)?;
+        }
         Ok(Some(ty))
     }
 
-    fn resolve_variant(&self, path: Option<ast::Path>) -> Cancelable<(Ty, Option<DefId>)> {
-        let path = if let Some(path) = path.and_then(Path::from_ast) {
-            path
-        } else {
-            return Ok((Ty::Unknown, None));
+    /// Replaces a leading `Self` in a value path like `Self::new` by the path
+    /// of the impl's type. Returns `None` if there's nothing to replace.
+    fn replace_self_type_prefix(&self, path: &Path) -> Option<Path> {
+        if !path.starts_with_self_type() {
+            return None;
+        }
+        let self_path = match &self.impl_self_type {
+            Some(TypeRef::Path(self_path)) => self_path,
+            _ => return None,
+        };
+        let mut segments = self_path.segments.clone();
+        segments.extend(path.segments.iter().skip(1).cloned());
+        Some(Path {
+            kind: self_path.kind,
+            segments,
+        })
+    }
+
+    /// Resolves a path like `Foo::new` to an associated function of the type
+    /// `Foo`, which may be defined in an impl of `Foo` or of a trait in scope.
+    /// Returns the type along with the function.
+    fn resolve_associated_fn(&mut self, path: &Path) -> Cancelable<Option<(Ty, DefId)>> {
+        let (name, type_segments) = match path.segments.split_last() {
+            Some((last, rest)) if !rest.is_empty() => (&last.name, rest),
+            _ => return Ok(None),
+        };
+        let type_path = Path {
+            kind: path.kind,
+            segments: type_segments.to_vec(),
+        };
+        let ty = self.make_ty(&TypeRef::Path(type_path))?;
+        let ty = self.resolve_ty_as_possible(ty);
+        let def_id = ty
+            .clone()
+            .lookup_associated_fn(self.db, &self.module, &self.generics, name)?;
+        Ok(def_id.map(|def_id| (ty, def_id)))
+    }
+
+    /// Unifies the target type of the impl block containing the method
+    /// `def_id`, with the method's substitutions `substs` applied, with
+    /// `self_ty`, the type the method was found on. This infers the
+    /// substitutions for the impl's type parameters.
+    fn unify_impl_self_ty(
+        &mut self,
+        def_id: DefId,
+        substs: &Substs,
+        self_ty: &Ty,
+    ) -> Cancelable<()> {
+        let impl_block = match Function::new(def_id).impl_block(self.db)? {
+            Some(impl_block) => impl_block,
+            None => return Ok(()),
+        };
+        let module = impl_block.module(self.db)?;
+        let generics = impl_block.generic_params();
+        let impl_ty = Ty::from_hir(self.db, &module, generics, impl_block.target_type())?;
+        let impl_ty = self.insert_type_vars(impl_ty.subst(substs));
+        self.unify(&impl_ty, self_ty);
+        Ok(())
+    }
+
+    /// Whether the value path `path`, which we failed to resolve, really
+    /// doesn't resolve to anything, as opposed to referring to something we
+    /// don't support yet, like the associated const in `u32::MAX`. That's
+    /// only the case if all modules the path goes through have no glob
+    /// imports, which we don't resolve yet.
+    fn is_unresolved_for_sure(&self, path: &Path) -> Cancelable<bool> {
+        if path.starts_with_self_type() {
+            return Ok(false);
+        }
+        for len in 0..path.segments.len() {
+            let module = if len == 0 && path.kind == PathKind::Plain {
+                // the first segment is looked up in this module, whose scope
+                // includes the prelude
+                if !self.module.has_complete_prelude(self.db)? {
+                    return Ok(false);
+                }
+                self.module.clone()
+            } else {
+                let prefix = Path {
+                    kind: path.kind,
+                    segments: path.segments[..len].to_vec(),
+                };
+                match self.module.resolve_path(self.db, &prefix)?.take_types() {
+                    Some(def_id) => match def_id.resolve(self.db)? {
+                        Def::Module(module) => module,
+                        // e.g. an enum or a struct, whose associated items,
+                        // including derived ones, we don't resolve completely
+                        _ => return Ok(false),
+                    },
+                    // e.g. `super::foo` in the crate root
+                    None if len == 0 => return Ok(true),
+                    None => {
+                        // e.g. a primitive type or a type parameter
+                        let ty = Ty::from_hir_path(self.db, &self.module, &self.generics, &prefix)?;
+                        return Ok(ty == Ty::Unknown);
+                    }
+                }
+            };
+            if module.has_glob_imports(self.db)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn resolve_variant(&mut self, path: Option<&Path>) -> Cancelable<(Ty, Option<DefId>)> {
+        let path = match path {
+            Some(path) if path.is_self_type() => match &self.impl_self_type {
+                Some(TypeRef::Path(self_path)) => self_path.clone(),
+                _ => return Ok((Ty::Unknown, None)),
+            },
+            Some(path) => path.clone(),
+            None => return Ok((Ty::Unknown, None)),
         };
-        let def_id = if let Some(def_id) = self.module.resolve_path(self.db, &path)?.take_types() {
+        let path = &path;
+        let def_id = if let Some(def_id) = self.module.resolve_path(self.db, path)?.take_types() {
             def_id
         } else {
             return Ok((Ty::Unknown, None));
@@ -614,300 +1608,979 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         Ok(match def_id.resolve(self.db)? {
             Def::Struct(s) => {
                 let ty = type_for_struct(self.db, s)?;
+                let substs =
+                    Ty::substs_from_path(self.db, &self.module, &self.generics, path, def_id)?;
+                let ty = self.insert_type_vars(ty.apply_substs(substs));
                 (ty, Some(def_id))
             }
+            Def::EnumVariant(ev) => {
+                let ty = type_for_enum(self.db, ev.parent_enum(self.db)?)?;
+                let substs =
+                    Ty::substs_from_path(self.db, &self.module, &self.generics, path, def_id)?;
+                let ty = self.insert_type_vars(ty.apply_substs(substs));
+                (ty, Some(def_id))
+            }
+            Def::Type(_) => {
+                // a struct literal through an alias, e.g. `Alias { .. }`
+                let ty = Ty::from_hir_path(self.db, &self.module, &self.generics, path)?;
+                let ty = self.insert_type_vars(ty);
+                let def_id = match &ty {
+                    Ty::Adt { def_id, .. } => Some(*def_id),
+                    _ => None,
+                };
+                (ty, def_id)
+            }
             _ => (Ty::Unknown, None),
         })
     }
 
-    fn infer_expr_opt(
-        &mut self,
-        expr: Option<ast::Expr>,
-        expected: &Expectation,
-    ) -> Cancelable<Ty> {
-        if let Some(e) = expr {
-            self.infer_expr(e, expected)
-        } else {
-            Ok(Ty::Unknown)
-        }
+    /// Returns the type of the field `name` of the variant `def_id` (if any),
+    /// with the substitutions of `ty` applied.
+    fn field_ty(&self, ty: &Ty, def_id: Option<DefId>, name: Name) -> Cancelable<Ty> {
+        let def_id = match def_id {
+            Some(def_id) => def_id,
+            None => return Ok(Ty::Unknown),
+        };
+        let substs = ty.substs().unwrap_or_else(Substs::empty);
+        Ok(self
+            .db
+            .type_for_field(def_id, name)?
+            .map_or(Ty::Unknown, |ty| ty.subst(&substs)))
     }
 
-    fn infer_expr(&mut self, expr: ast::Expr, expected: &Expectation) -> Cancelable<Ty> {
-        let ty = match expr {
-            ast::Expr::IfExpr(e) => {
-                if let Some(condition) = e.condition() {
-                    let expected = if condition.pat().is_none() {
-                        Expectation::has_type(Ty::Bool)
-                    } else {
-                        Expectation::none()
-                    };
-                    self.infer_expr_opt(condition.expr(), &expected)?;
-                    // TODO write type for pat
+    /// Infers the type of the pattern `pat`, which matches a value of type
+    /// `expected`, and writes types for all bindings in it. Returns the type
+    /// of the matched value (for `ref x`, that's the type of `x`'s referent).
+    fn infer_pat(&mut self, pat: PatId, expected: &Ty) -> Cancelable<Ty> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let expected = self.resolve_ty_shallow(expected);
+        let ty = match &body[pat] {
+            Pat::Tuple { args, ellipsis } => {
+                let expected_tys: Option<&[Ty]> = match &*expected {
+                    Ty::Tuple(tys) => Some(tys),
+                    _ => None,
+                };
+                let (prefix, suffix) = args.split_at(ellipsis.unwrap_or(args.len()));
+                // `..` matches the elements between the prefix and the
+                // suffix, so the suffix is matched against the end of the tuple
+                let len = match (ellipsis, expected_tys) {
+                    (Some(_), Some(tys)) => tys.len().max(args.len()),
+                    _ => args.len(),
                 };
-                let if_ty = self.infer_block_opt(e.then_branch(), expected)?;
-                if let Some(else_branch) = e.else_branch() {
-                    self.infer_block(else_branch, expected)?;
+                // without an expected tuple type, `..` leaves the arity unknown
+                let arity_known = ellipsis.is_none() || expected_tys.is_some();
+                let expected_tys = expected_tys.unwrap_or(&[]);
+                let mut tys: Vec<Ty> = (0..len)
+                    .map(|i| expected_tys.get(i).cloned().unwrap_or(Ty::Unknown))
+                    .collect();
+                let positions = (0..prefix.len()).chain(len - suffix.len()..len);
+                for (&sub_pat, i) in prefix.iter().chain(suffix).zip(positions) {
+                    let expected_ty = tys[i].clone();
+                    tys[i] = self.infer_pat(sub_pat, &expected_ty)?;
+                }
+                if arity_known {
+                    Ty::Tuple(tys.into())
                 } else {
-                    // no else branch -> unit
-                    self.unify(&expected.ty, &Ty::unit()); // actually coerce
+                    Ty::Unknown
                 }
-                if_ty
             }
-            ast::Expr::BlockExpr(e) => self.infer_block_opt(e.block(), expected)?,
-            ast::Expr::LoopExpr(e) => {
-                self.infer_block_opt(e.loop_body(), &Expectation::has_type(Ty::unit()))?;
-                // TODO never, or the type of the break param
-                Ty::Unknown
+            Pat::Ref { pat, mutability } => {
+                let expected_inner = match &*expected {
+                    Ty::Ref(inner, m2) if m2 == mutability => Ty::clone(inner),
+                    _ => Ty::Unknown,
+                };
+                let inner_ty = self.infer_pat(*pat, &expected_inner)?;
+                Ty::Ref(Arc::new(inner_ty), *mutability)
             }
-            ast::Expr::WhileExpr(e) => {
-                if let Some(condition) = e.condition() {
-                    let expected = if condition.pat().is_none() {
-                        Expectation::has_type(Ty::Bool)
-                    } else {
-                        Expectation::none()
+            Pat::TupleStruct { path, args } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                self.unify(&ty, &expected);
+                for (i, &sub_pat) in args.iter().enumerate() {
+                    let field_ty = self.field_ty(&ty, def_id, Name::tuple_field_name(i))?;
+                    self.infer_pat(sub_pat, &field_ty)?;
+                }
+                ty
+            }
+            Pat::Struct { path, args } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                self.unify(&ty, &expected);
+                for field_pat in args {
+                    let field_ty = self.field_ty(&ty, def_id, field_pat.name.clone())?;
+                    self.infer_pat(field_pat.pat, &field_ty)?;
+                }
+                ty
+            }
+            Pat::Path(path) => self.resolve_variant(Some(path))?.0,
+            Pat::Slice { args } => {
+                let expected_elem = match &*expected {
+                    Ty::Slice(elem) => Ty::clone(elem),
+                    _ => Ty::Unknown,
+                };
+                for &sub_pat in args {
+                    self.infer_pat(sub_pat, &expected_elem)?;
+                }
+                Ty::clone(&expected)
+            }
+            Pat::Bind { mode, subpat, .. } => {
+                let inner_ty = match subpat {
+                    Some(sub_pat) => self.infer_pat(*sub_pat, &expected)?,
+                    None => Ty::clone(&expected),
+                };
+                let inner_ty = self.insert_type_vars_shallow(inner_ty);
+                let bound_ty = match mode {
+                    BindingAnnotation::Ref => {
+                        Ty::Ref(Arc::new(inner_ty.clone()), Mutability::Shared)
+                    }
+                    BindingAnnotation::RefMut => {
+                        Ty::Ref(Arc::new(inner_ty.clone()), Mutability::Mut)
+                    }
+                    BindingAnnotation::Mutable | BindingAnnotation::Unannotated => {
+                        inner_ty.clone()
+                    }
+                };
+                let bound_ty = self.resolve_ty_as_possible(bound_ty);
+                self.write_pat_ty(pat, bound_ty);
+                return Ok(inner_ty);
+            }
+            Pat::Lit(expr) => {
+                let lit_ty = self.infer_expr(*expr, &Expectation::none())?;
+                // like other non-reference patterns, literal patterns match
+                // through references (default binding modes)
+                let mut matched_ty = Ty::clone(&expected);
+                let mut derefs = 0;
+                loop {
+                    let snapshot = self.var_unification_table.snapshot();
+                    if self.unify(&lit_ty, &matched_ty) {
+                        self.var_unification_table.commit(snapshot);
+                        break;
+                    }
+                    self.var_unification_table.rollback_to(snapshot);
+                    matched_ty = match matched_ty {
+                        Ty::Ref(inner, _) => self.resolve_ty_shallow(&inner).into_owned(),
+                        _ => {
+                            self.report_mismatch(*expr, &expected, &lit_ty);
+                            derefs = 0;
+                            break;
+                        }
                     };
-                    self.infer_expr_opt(condition.expr(), &expected)?;
-                    // TODO write type for pat
+                    derefs += 1;
+                }
+                if derefs > 0 {
+                    Ty::clone(&expected)
+                } else {
+                    self.resolve_ty_as_possible(lit_ty)
+                }
+            }
+            Pat::Wild | Pat::Missing => Ty::clone(&expected),
+        };
+        // use a new type variable if we got Ty::Unknown here
+        let ty = self.insert_type_vars_shallow(ty);
+        self.unify(&ty, &expected);
+        let ty = self.resolve_ty_as_possible(ty);
+        self.write_pat_ty(pat, ty.clone());
+        Ok(ty)
+    }
+
+    fn infer_expr(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Cancelable<Ty> {
+        let ty = self.infer_expr_inner(tgt_expr, expected)?;
+        // `!` coerces to any type
+        if ty != Ty::Never && !self.unify(&ty, &expected.ty) {
+            self.report_mismatch(tgt_expr, &expected.ty, &ty);
+        }
+        let ty = self.resolve_ty_as_possible(ty);
+        self.write_expr_ty(tgt_expr, ty.clone());
+        Ok(ty)
+    }
+
+    /// Infers an expression at a coercion site (a call argument, the
+    /// initializer of an annotated `let`, a returned value, ...), where its
+    /// type only needs to coerce to the expected type. The type recorded for
+    /// the expression is its own type; the returned type is the expected type
+    /// if the coercion succeeded.
+    fn infer_expr_coerce(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Cancelable<Ty> {
+        let ty = self.infer_expr_inner(tgt_expr, expected)?;
+        let coerced = self.coerce(&ty, &expected.ty)?;
+        if !coerced {
+            self.report_mismatch(tgt_expr, &expected.ty, &ty);
+        }
+        let ty = self.resolve_ty_as_possible(ty);
+        self.write_expr_ty(tgt_expr, ty.clone());
+        if coerced && ty != Ty::Never && expected.ty != Ty::Unknown {
+            Ok(self.resolve_ty_as_possible(expected.ty.clone()))
+        } else {
+            Ok(ty)
+        }
+    }
+
+    /// Unifies `from_ty` with `to_ty`, allowing the coercions rustc does at
+    /// coercion sites: `!` to any type, `&mut T` to `&T`, deref coercions like
+    /// `&String` to `&str` or `&Box<T>` to `&T`, and unsizing `&[T; N]` to
+    /// `&[T]`. Returns whether the coercion succeeded.
+    fn coerce(&mut self, from_ty: &Ty, to_ty: &Ty) -> Cancelable<bool> {
+        let from_ty = self.resolve_ty_shallow(from_ty).into_owned();
+        let to_ty = self.resolve_ty_shallow(to_ty).into_owned();
+        match (&from_ty, &to_ty) {
+            (Ty::Never, _) => return Ok(true),
+            (Ty::Ref(from_inner, from_mut), Ty::Ref(to_inner, to_mut))
+                if from_mut == to_mut || *to_mut == Mutability::Shared =>
+            {
+                // try `&T`, `&*T`, `&**T`, ... until one of them fits
+                let from_inner = self.resolve_ty_as_possible(Ty::clone(from_inner));
+                for derefed_ty in from_inner.autoderef(self.db, false) {
+                    if self.unify_maybe_unsized(derefed_ty?, to_inner) {
+                        return Ok(true);
+                    }
+                }
+                return Ok(false);
+            }
+            (Ty::RawPtr(from_inner, from_mut), Ty::RawPtr(to_inner, to_mut))
+                if from_mut == to_mut || *to_mut == Mutability::Shared =>
+            {
+                let from_inner = self.resolve_ty_as_possible(Ty::clone(from_inner));
+                return Ok(self.unify_maybe_unsized(from_inner, to_inner));
+            }
+            _ => {}
+        }
+        Ok(self.unify(&from_ty, &to_ty))
+    }
+
+    /// Unifies the pointee types of a pointer coercion, unsizing `[T; N]` to
+    /// `[T]` if necessary. Nothing is unified if this fails.
+    fn unify_maybe_unsized(&mut self, from_ty: Ty, to_ty: &Ty) -> bool {
+        let unsized_ty = match &from_ty {
+            Ty::Array(elem_ty, _) => Some(Ty::Slice(Arc::clone(elem_ty))),
+            _ => None,
+        };
+        for ty in iter::once(from_ty).chain(unsized_ty) {
+            let snapshot = self.var_unification_table.snapshot();
+            if self.unify(&ty, to_ty) {
+                self.var_unification_table.commit(snapshot);
+                return true;
+            }
+            self.var_unification_table.rollback_to(snapshot);
+        }
+        false
+    }
+
+    fn infer_expr_inner(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Cancelable<Ty> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let ty = match &body[tgt_expr] {
+            Expr::Missing => Ty::Unknown,
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.infer_expr(*condition, &Expectation::has_type(Ty::Bool))?;
+                let expected = self.branch_expectation(expected);
+                let then_ty = self.infer_expr_coerce(*then_branch, &expected)?;
+                let else_ty = match else_branch {
+                    Some(else_branch) => self.infer_expr_coerce(*else_branch, &expected)?,
+                    None => {
+                        // no else branch -> unit
+                        self.unify(&expected.ty, &Ty::unit()); // actually coerce
+                        Ty::unit()
+                    }
                 };
-                self.infer_block_opt(e.loop_body(), &Expectation::has_type(Ty::unit()))?;
-                // TODO always unit?
-                Ty::unit()
+                self.merge_branch_tys(&expected, &[then_ty, else_ty])
             }
-            ast::Expr::ForExpr(e) => {
-                let _iterable_ty = self.infer_expr_opt(e.iterable(), &Expectation::none());
-                if let Some(_pat) = e.pat() {
-                    // TODO write type for pat
+            Expr::Block { statements, tail } => self.infer_block(statements, *tail, expected)?,
+            Expr::Unsafe { body } => {
+                let prev = mem::replace(&mut self.in_unsafe_context, true);
+                let result = self.infer_expr(*body, expected);
+                self.in_unsafe_context = prev;
+                result?
+            }
+            Expr::Loop { body, label } => {
+                let break_ty = self.new_type_var();
+                let ctxt = self.infer_loop_body(*body, label, break_ty)?;
+                if ctxt.may_break {
+                    ctxt.break_ty
+                } else {
+                    Ty::Never
                 }
-                self.infer_block_opt(e.loop_body(), &Expectation::has_type(Ty::unit()))?;
-                // TODO always unit?
+            }
+            Expr::While {
+                condition,
+                body,
+                label,
+            } => {
+                self.infer_expr(*condition, &Expectation::has_type(Ty::Bool))?;
+                self.infer_loop_body(*body, label, Ty::unit())?;
                 Ty::unit()
             }
-            ast::Expr::LambdaExpr(e) => {
-                let _body_ty = self.infer_expr_opt(e.body(), &Expectation::none())?;
-                Ty::Unknown
+            Expr::For {
+                iterable,
+                pat,
+                body,
+                label,
+            } => {
+                let iterable_ty = self.infer_expr(*iterable, &Expectation::none())?;
+                let item_ty = traits::iterator_item_ty(self.db, &iterable_ty)?;
+                let item_ty = self.insert_type_vars(item_ty.unwrap_or(Ty::Unknown));
+                self.infer_pat(*pat, &item_ty)?;
+                self.infer_loop_body(*body, label, Ty::unit())?;
+                Ty::unit()
+            }
+            Expr::Lambda {
+                body,
+                args,
+                arg_types,
+            } => {
+                assert_eq!(args.len(), arg_types.len());
+
+                // the expected signature, e.g. from an `F: Fn(u32) -> u32` bound
+                let expected_sig = match &*self.resolve_ty_shallow(&expected.ty) {
+                    Ty::FnPtr(sig) | Ty::Closure { sig, .. } => Some(Arc::clone(sig)),
+                    _ => None,
+                };
+                let mut input = Vec::with_capacity(args.len());
+                for (i, (arg_pat, arg_type)) in args.iter().zip(arg_types.iter()).enumerate() {
+                    let expected_arg_ty = expected_sig
+                        .as_ref()
+                        .and_then(|sig| sig.input.get(i).cloned())
+                        .unwrap_or(Ty::Unknown);
+                    let expected_arg_ty = self.insert_type_vars(expected_arg_ty);
+                    let arg_ty = if let Some(type_ref) = arg_type {
+                        let ty = self.make_ty(type_ref)?;
+                        self.unify(&ty, &expected_arg_ty);
+                        ty
+                    } else {
+                        expected_arg_ty
+                    };
+                    input.push(self.infer_pat(*arg_pat, &arg_ty)?);
+                }
+
+                // `return` and `break` in the body refer to the closure itself
+                let ret_ty = match &expected_sig {
+                    Some(sig) => self.insert_type_vars(sig.output.clone()),
+                    None => self.new_type_var(),
+                };
+                let prev_ret_ty = mem::replace(&mut self.return_ty, ret_ty.clone());
+                let prev_breakables = mem::replace(&mut self.breakables, Vec::new());
+                let result = self.infer_expr(*body, &Expectation::has_type(ret_ty.clone()));
+                self.return_ty = prev_ret_ty;
+                self.breakables = prev_breakables;
+                result?;
+                let sig = FnSig {
+                    input,
+                    output: ret_ty,
+                };
+                Ty::Closure {
+                    def_id: self.def_id,
+                    expr: tgt_expr,
+                    sig: Arc::new(sig),
+                }
             }
-            ast::Expr::CallExpr(e) => {
-                let callee_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
+            Expr::Call { callee, args } => {
+                let callee_ty = self.infer_expr(*callee, &Expectation::none())?;
                 let (arg_tys, ret_ty) = match &callee_ty {
-                    Ty::FnPtr(sig) => (&sig.input[..], sig.output.clone()),
+                    Ty::FnPtr(sig) | Ty::Closure { sig, .. } => {
+                        (sig.input.clone(), sig.output.clone())
+                    }
+                    Ty::FnDef { sig, substs, .. } => {
+                        let arg_tys = sig
+                            .input
+                            .iter()
+                            .map(|ty| ty.clone().subst(substs))
+                            .collect();
+                        (arg_tys, sig.output.clone().subst(substs))
+                    }
                     _ => {
                         // not callable
                         // TODO report an error?
-                        (&[][..], Ty::Unknown)
+                        (Vec::new(), Ty::Unknown)
                     }
                 };
-                if let Some(arg_list) = e.arg_list() {
-                    for (i, arg) in arg_list.args().enumerate() {
-                        self.infer_expr(
-                            arg,
-                            &Expectation::has_type(arg_tys.get(i).cloned().unwrap_or(Ty::Unknown)),
-                        )?;
+                self.infer_call_args(tgt_expr, args, &arg_tys, &callee_ty, 0)?;
+                ret_ty
+            }
+            Expr::MethodCall {
+                receiver,
+                args,
+                method_name,
+            } => {
+                let receiver_ty = self.infer_expr(*receiver, &Expectation::none())?;
+                let resolved = receiver_ty.clone().lookup_method(
+                    self.db,
+                    &self.module,
+                    &self.generics,
+                    method_name,
+                )?;
+                let method_ty = match resolved {
+                    Some((derefed_receiver_ty, def_id)) => {
+                        // the type parameters of the method are inferred; those
+                        // of its impl follow from the receiver
+                        let param_count = self
+                            .db
+                            .generic_params(def_id)
+                            .count_params_including_parent();
+                        let substs = Substs(vec![Ty::Unknown; param_count].into());
+                        let method_ty = self.db.type_for_def(def_id)?.apply_substs(substs);
+                        let method_ty = self.insert_type_vars(method_ty);
+                        if let Some(substs) = method_ty.substs() {
+                            self.unify_impl_self_ty(def_id, &substs, &derefed_receiver_ty)?;
+                        }
+                        method_ty
                     }
-                }
+                    None => {
+                        let method_ty = self.builtin_method(receiver_ty, method_name)?;
+                        self.insert_type_vars(method_ty.unwrap_or(Ty::Unknown))
+                    }
+                };
+                // the receiver is passed as the self parameter, which only
+                // function items have
+                let (arg_tys, ret_ty, self_param_count) = match &method_ty {
+                    Ty::FnPtr(sig) => (sig.input.clone(), sig.output.clone(), 0),
+                    Ty::FnDef { sig, substs, .. } => {
+                        let arg_tys = sig
+                            .input
+                            .iter()
+                            .skip(1)
+                            .map(|ty| ty.clone().subst(substs))
+                            .collect();
+                        (arg_tys, sig.output.clone().subst(substs), 1)
+                    }
+                    _ => (Vec::new(), Ty::Unknown, 0),
+                };
+                self.infer_call_args(tgt_expr, args, &arg_tys, &method_ty, self_param_count)?;
                 ret_ty
             }
-            ast::Expr::MethodCallExpr(e) => {
-                let _receiver_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                if let Some(arg_list) = e.arg_list() {
-                    for arg in arg_list.args() {
-                        // TODO unify / expect argument type
-                        self.infer_expr(arg, &Expectation::none())?;
+            Expr::Match { expr, arms } => {
+                let expr_ty = self.infer_expr(*expr, &Expectation::none())?;
+                let expected = self.branch_expectation(expected);
+                let mut arm_tys = Vec::new();
+                for arm in arms {
+                    for &pat in &arm.pats {
+                        self.infer_pat(pat, &expr_ty)?;
                     }
+                    if let Some(guard_expr) = arm.guard {
+                        self.infer_expr(guard_expr, &Expectation::has_type(Ty::Bool))?;
+                    }
+                    arm_tys.push(self.infer_expr_coerce(arm.expr, &expected)?);
                 }
-                Ty::Unknown
+                self.merge_branch_tys(&expected, &arm_tys)
             }
-            ast::Expr::MatchExpr(e) => {
-                let _ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                if let Some(match_arm_list) = e.match_arm_list() {
-                    for arm in match_arm_list.arms() {
-                        // TODO type the bindings in pat
-                        // TODO type the guard
-                        let _ty = self.infer_expr_opt(arm.expr(), &Expectation::none())?;
+            Expr::Tuple { exprs } => {
+                let expected_tys: &[Ty] = match &expected.ty {
+                    Ty::Tuple(tys) => tys,
+                    _ => &[],
+                };
+                let mut tys = Vec::new();
+                for (i, expr) in exprs.iter().enumerate() {
+                    let expected_ty = expected_tys.get(i).cloned().unwrap_or(Ty::Unknown);
+                    tys.push(self.infer_expr(*expr, &Expectation::has_type(expected_ty))?);
+                }
+                Ty::Tuple(tys.into())
+            }
+            Expr::Array(array) => {
+                let elem_ty = match &expected.ty {
+                    Ty::Array(elem_ty, _) | Ty::Slice(elem_ty) => Ty::clone(elem_ty),
+                    _ => self.new_type_var(),
+                };
+                let len = match array {
+                    Array::ElementList(items) => {
+                        for &item in items {
+                            self.infer_expr_coerce(item, &Expectation::has_type(elem_ty.clone()))?;
+                        }
+                        ArrayLen::Known(items.len() as u64)
+                    }
+                    Array::Repeat {
+                        initializer,
+                        repeat,
+                        len,
+                    } => {
+                        let usize_ty = Ty::Uint(primitive::UintTy::Usize);
+                        self.infer_expr_coerce(
+                            *initializer,
+                            &Expectation::has_type(elem_ty.clone()),
+                        )?;
+                        self.infer_expr(*repeat, &Expectation::has_type(usize_ty))?;
+                        ArrayLen::eval(self.db, &self.module, len)?
+                    }
+                };
+                Ty::Array(Arc::new(elem_ty), len)
+            }
+            Expr::Path(p) => match self.infer_path_expr(tgt_expr, p)? {
+                Some(ty) => ty,
+                None => {
+                    if self.is_unresolved_for_sure(p)? {
+                        let diagnostic = InferenceDiagnostic::UnresolvedPath { expr: tgt_expr };
+                        self.push_diagnostic(diagnostic);
                     }
-                    // TODO unify all the match arm types
                     Ty::Unknown
+                }
+            },
+            Expr::Continue => Ty::Never,
+            Expr::Break { expr, label } => {
+                let ctxt = match label {
+                    Some(_) => self.breakables.iter_mut().rev().find(|it| it.label == *label),
+                    None => self.breakables.last_mut(),
+                };
+                let break_ty = match ctxt {
+                    Some(ctxt) => {
+                        ctxt.may_break = true;
+                        ctxt.break_ty.clone()
+                    }
+                    None => Ty::Unknown,
+                };
+                if let Some(expr) = expr {
+                    self.infer_expr(*expr, &Expectation::has_type(break_ty))?;
                 } else {
-                    Ty::Unknown
+                    self.unify(&break_ty, &Ty::unit());
                 }
-            }
-            ast::Expr::TupleExpr(_e) => Ty::Unknown,
-            ast::Expr::ArrayExpr(_e) => Ty::Unknown,
-            ast::Expr::PathExpr(e) => self.infer_path_expr(e)?.unwrap_or(Ty::Unknown),
-            ast::Expr::ContinueExpr(_e) => Ty::Never,
-            ast::Expr::BreakExpr(_e) => Ty::Never,
-            ast::Expr::ParenExpr(e) => self.infer_expr_opt(e.expr(), expected)?,
-            ast::Expr::Label(_e) => Ty::Unknown,
-            ast::Expr::ReturnExpr(e) => {
-                self.infer_expr_opt(e.expr(), &Expectation::none())?;
                 Ty::Never
             }
-            ast::Expr::MatchArmList(_) | ast::Expr::MatchArm(_) | ast::Expr::MatchGuard(_) => {
-                // Can this even occur outside of a match expression?
-                Ty::Unknown
+            Expr::Return { expr } => {
+                let ret_ty = self.return_ty.clone();
+                if let Some(expr) = expr {
+                    self.infer_expr_coerce(*expr, &Expectation::has_type(ret_ty))?;
+                } else {
+                    self.unify(&ret_ty, &Ty::unit());
+                }
+                Ty::Never
             }
-            ast::Expr::StructLit(e) => {
-                let (ty, def_id) = self.resolve_variant(e.path())?;
-                if let Some(nfl) = e.named_field_list() {
-                    for field in nfl.fields() {
-                        let field_ty = if let (Some(def_id), Some(nr)) = (def_id, field.name_ref())
-                        {
-                            self.db.type_for_field(def_id, nr.as_name())?
-                        } else {
-                            Ty::Unknown
-                        };
-                        self.infer_expr_opt(field.expr(), &Expectation::has_type(field_ty))?;
+            Expr::StructLit {
+                path,
+                fields,
+                spread,
+            } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                for field in fields {
+                    if let Some(def_id) = def_id {
+                        if self.db.type_for_field(def_id, field.name.clone())?.is_none() {
+                            self.push_diagnostic(InferenceDiagnostic::NoSuchField {
+                                expr: field.expr,
+                                field: field.name.clone(),
+                            });
+                        }
                     }
+                    let field_ty = self.field_ty(&ty, def_id, field.name.clone())?;
+                    self.infer_expr_coerce(field.expr, &Expectation::has_type(field_ty))?;
+                }
+                if let Some(expr) = spread {
+                    self.infer_expr(*expr, &Expectation::has_type(ty.clone()))?;
                 }
                 ty
             }
-            ast::Expr::NamedFieldList(_) | ast::Expr::NamedField(_) => {
-                // Can this even occur outside of a struct literal?
-                Ty::Unknown
-            }
-            ast::Expr::IndexExpr(_e) => Ty::Unknown,
-            ast::Expr::FieldExpr(e) => {
-                let receiver_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                if let Some(nr) = e.name_ref() {
-                    let ty = match receiver_ty {
+            Expr::Field { expr, name } => {
+                let receiver_ty = self.infer_expr(*expr, &Expectation::none())?;
+                let mut ty = None;
+                let mut last_derefed_ty = Ty::Unknown;
+                for derefed_ty in receiver_ty.autoderef(self.db, self.in_unsafe_context) {
+                    last_derefed_ty = derefed_ty?;
+                    let field_ty = match last_derefed_ty.clone() {
                         Ty::Tuple(fields) => {
-                            let i = nr.text().parse::<usize>().ok();
+                            let i = name.to_string().parse::<usize>().ok();
                             i.and_then(|i| fields.get(i).cloned())
-                                .unwrap_or(Ty::Unknown)
                         }
-                        Ty::Adt { def_id, .. } => self.db.type_for_field(def_id, nr.as_name())?,
-                        _ => Ty::Unknown,
+                        Ty::Adt {
+                            def_id, ref substs, ..
+                        } => self
+                            .db
+                            .type_for_field(def_id, name.clone())?
+                            .map(|ty| ty.subst(substs)),
+                        _ => None,
                     };
-                    self.insert_type_vars(ty)
-                } else {
-                    Ty::Unknown
+                    if field_ty.is_some() {
+                        ty = field_ty;
+                        break;
+                    }
+                }
+                match ty {
+                    Some(ty) => self.insert_type_vars(ty),
+                    None => {
+                        // if we couldn't deref any further, the field doesn't
+                        // exist; otherwise we just don't know the type
+                        match last_derefed_ty {
+                            Ty::Adt { .. } | Ty::Tuple(_) if *name != Name::missing() => {
+                                self.push_diagnostic(InferenceDiagnostic::NoSuchField {
+                                    expr: tgt_expr,
+                                    field: name.clone(),
+                                });
+                            }
+                            _ => {}
+                        }
+                        self.new_type_var()
+                    }
                 }
             }
-            ast::Expr::TryExpr(e) => {
-                let _inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
+            Expr::Try { expr } => {
+                let _inner_ty = self.infer_expr(*expr, &Expectation::none())?;
                 Ty::Unknown
             }
-            ast::Expr::CastExpr(e) => {
-                let _inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                let cast_ty = Ty::from_ast_opt(self.db, &self.module, e.type_ref())?;
-                let cast_ty = self.insert_type_vars(cast_ty);
+            Expr::Cast { expr, type_ref } => {
+                let _inner_ty = self.infer_expr(*expr, &Expectation::none())?;
+                let cast_ty = self.make_ty(type_ref)?;
                 // TODO do the coercion...
                 cast_ty
             }
-            ast::Expr::RefExpr(e) => {
+            Expr::Ref { expr, mutability } => {
                 // TODO pass the expectation down
-                let inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                let m = Mutability::from_mutable(e.is_mut());
-                // TODO reference coercions etc.
-                Ty::Ref(Arc::new(inner_ty), m)
-            }
-            ast::Expr::PrefixExpr(e) => {
-                let inner_ty = self.infer_expr_opt(e.expr(), &Expectation::none())?;
-                match e.op() {
-                    Some(PrefixOp::Deref) => {
-                        match inner_ty {
-                            // builtin deref:
-                            Ty::Ref(ref_inner, _) => (*ref_inner).clone(),
-                            Ty::RawPtr(ptr_inner, _) => (*ptr_inner).clone(),
-                            // TODO Deref::deref
-                            _ => Ty::Unknown,
+                let inner_ty = self.infer_expr(*expr, &Expectation::none())?;
+                Ty::Ref(Arc::new(inner_ty), *mutability)
+            }
+            Expr::UnaryOp { expr, op } => {
+                let inner_ty = self.infer_expr(*expr, &Expectation::none())?;
+                match op {
+                    Some(UnaryOp::Deref) => {
+                        // builtin deref (raw pointers can always be
+                        // dereferenced explicitly, if unsafely), else
+                        // `Deref::deref`
+                        match inner_ty.builtin_deref(true) {
+                            Some(derefed_ty) => derefed_ty,
+                            None => inner_ty.deref_by_trait(self.db)?.unwrap_or(Ty::Unknown),
                         }
                     }
                     _ => Ty::Unknown,
                 }
             }
-            ast::Expr::RangeExpr(_e) => Ty::Unknown,
-            ast::Expr::BinExpr(_e) => Ty::Unknown,
-            ast::Expr::Literal(_e) => Ty::Unknown,
+            Expr::BinaryOp { lhs, rhs } => {
+                self.infer_expr(*lhs, &Expectation::none())?;
+                self.infer_expr(*rhs, &Expectation::none())?;
+                Ty::Unknown
+            }
+            Expr::Index { base, index } => {
+                let base_ty = self.infer_expr(*base, &Expectation::none())?;
+                let mut elem_ty = None;
+                for derefed_ty in base_ty.autoderef(self.db, self.in_unsafe_context) {
+                    match derefed_ty? {
+                        Ty::Array(t, _) | Ty::Slice(t) => {
+                            elem_ty = Some(Ty::clone(&t));
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                match elem_ty {
+                    Some(elem_ty) => {
+                        let usize_ty = Ty::Uint(primitive::UintTy::Usize);
+                        self.infer_expr(*index, &Expectation::has_type(usize_ty))?;
+                        elem_ty
+                    }
+                    None => {
+                        // TODO: `Index` impls
+                        self.infer_expr(*index, &Expectation::none())?;
+                        Ty::Unknown
+                    }
+                }
+            }
+            Expr::Literal(lit) => match lit {
+                Literal::Bool => Ty::Bool,
+                Literal::String => Ty::Ref(Arc::new(Ty::Str), Mutability::Shared),
+                Literal::ByteString { len } => {
+                    let len = len.map_or(ArrayLen::Unknown, ArrayLen::Known);
+                    let byte_array = Ty::Array(Arc::new(Ty::Uint(primitive::UintTy::U8)), len);
+                    Ty::Ref(Arc::new(byte_array), Mutability::Shared)
+                }
+                Literal::Char => Ty::Char,
+                Literal::Byte => Ty::Uint(primitive::UintTy::U8),
+                Literal::IntNumber { suffix } => {
+                    let suffix_ty = suffix.as_ref().and_then(|suffix| {
+                        primitive::IntTy::from_string(suffix)
+                            .map(Ty::Int)
+                            .or_else(|| primitive::UintTy::from_string(suffix).map(Ty::Uint))
+                    });
+                    match suffix_ty {
+                        Some(ty) => ty,
+                        None => self.new_integer_var(),
+                    }
+                }
+                Literal::FloatNumber { suffix } => {
+                    let suffix_ty = suffix
+                        .as_ref()
+                        .and_then(|suffix| primitive::FloatTy::from_string(suffix));
+                    match suffix_ty {
+                        Some(float_ty) => Ty::Float(float_ty),
+                        None => self.new_float_var(),
+                    }
+                }
+            },
         };
+        let ty = self.normalize(ty)?;
         // use a new type variable if we got Ty::Unknown here
-        let ty = self.insert_type_vars_shallow(ty);
-        self.unify(&ty, &expected.ty);
-        self.write_ty(expr.syntax(), ty.clone());
-        Ok(ty)
+        Ok(self.insert_type_vars_shallow(ty))
     }
 
-    fn infer_block_opt(
+    /// The type of a method of a builtin type that we know about without
+    /// seeing its impl, like `len` on arrays and slices.
+    // TODO: use lang items to find the inherent impls of builtin types in core
+    fn builtin_method(&self, receiver_ty: Ty, name: &Name) -> Cancelable<Option<Ty>> {
+        if name.as_known_name() != Some(KnownName::Len) {
+            return Ok(None);
+        }
+        for derefed_ty in receiver_ty.autoderef(self.db, false) {
+            match derefed_ty? {
+                Ty::Array(..) | Ty::Slice(..) => {
+                    let sig = FnSig {
+                        input: Vec::new(),
+                        output: Ty::Uint(primitive::UintTy::Usize),
+                    };
+                    return Ok(Some(Ty::FnPtr(Arc::new(sig))));
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Infers the arguments of a call of `callee_ty`, whose parameter types
+    /// are `arg_tys`. If the callee is a function item, closure arguments are
+    /// also checked against the `Fn` bounds of its type parameters. The first
+    /// `skipped_params` parameters of the callee, like the receiver of a
+    /// method call, are not part of `args`.
+    fn infer_call_args(
         &mut self,
-        node: Option<ast::Block>,
-        expected: &Expectation,
-    ) -> Cancelable<Ty> {
-        if let Some(b) = node {
-            self.infer_block(b, expected)
+        call_expr: ExprId,
+        args: &[ExprId],
+        arg_tys: &[Ty],
+        callee_ty: &Ty,
+        skipped_params: usize,
+    ) -> Cancelable<()> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        match callee_ty {
+            Ty::FnPtr(_) | Ty::FnDef { .. } | Ty::Closure { .. } if args.len() != arg_tys.len() => {
+                self.push_diagnostic(InferenceDiagnostic::WrongArgCount {
+                    expr: call_expr,
+                    expected: arg_tys.len(),
+                    found: args.len(),
+                });
+            }
+            _ => {}
+        }
+        for (i, &arg) in args.iter().enumerate() {
+            let arg_ty = arg_tys.get(i).cloned().unwrap_or(Ty::Unknown);
+            let closure_sig = match (callee_ty, &body[arg]) {
+                (
+                    Ty::FnDef {
+                        def_id, sig, substs, ..
+                    },
+                    Expr::Lambda { .. },
+                ) => match sig.input.get(skipped_params + i) {
+                    Some(param_ty) => self.closure_sig_from_bounds(*def_id, param_ty, substs)?,
+                    None => None,
+                },
+                _ => None,
+            };
+            match closure_sig {
+                Some(closure_sig) => {
+                    let ty = self.infer_expr(arg, &Expectation::has_type(closure_sig))?;
+                    self.unify(&arg_ty, &ty);
+                }
+                None => {
+                    self.infer_expr_coerce(arg, &Expectation::has_type(arg_ty))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// If `param_ty` is a type parameter of the function `callee` with a
+    /// `Fn(A, ..) -> R` bound (or `FnMut`, `FnOnce`), returns `fn(A, ..) -> R`
+    /// with `substs` applied, as the expected type of a closure argument.
+    // TODO: use lang items instead of matching on the trait's name
+    fn closure_sig_from_bounds(
+        &self,
+        callee: DefId,
+        param_ty: &Ty,
+        substs: &Substs,
+    ) -> Cancelable<Option<Ty>> {
+        let idx = match param_ty {
+            Ty::Param { idx, .. } => *idx,
+            _ => return Ok(None),
+        };
+        let generics = self.db.generic_params(callee);
+        let module = callee.module(self.db)?;
+        for pred in generics.predicates_including_parent() {
+            match Ty::from_hir(self.db, &module, &generics, &pred.type_ref)? {
+                Ty::Param { idx: pred_idx, .. } if pred_idx == idx => {}
+                _ => continue,
+            }
+            let segment = match pred.trait_path.segments.last() {
+                Some(segment) => segment,
+                None => continue,
+            };
+            match segment.name.as_known_name() {
+                Some(KnownName::Fn) | Some(KnownName::FnMut) | Some(KnownName::FnOnce) => {}
+                _ => continue,
+            }
+            let args = match &segment.args_and_bindings {
+                Some(args) => args,
+                None => continue,
+            };
+            let params = match args.args.first() {
+                Some(GenericArg::Type(TypeRef::Tuple(params))) => params,
+                _ => continue,
+            };
+            let input = params
+                .iter()
+                .map(|tr| Ok(Ty::from_hir(self.db, &module, &generics, tr)?.subst(substs)))
+                .collect::<Cancelable<Vec<_>>>()?;
+            let output = match args
+                .bindings
+                .iter()
+                .find(|(name, _)| name.as_known_name() == Some(KnownName::Output))
+            {
+                Some((_, tr)) => Ty::from_hir(self.db, &module, &generics, tr)?.subst(substs),
+                None => Ty::unit(),
+            };
+            let sig = FnSig { input, output };
+            return Ok(Some(Ty::FnPtr(Arc::new(sig))));
+        }
+        Ok(None)
+    }
+
+    /// The expectation for the branches of an `if` or `match`. If we don't
+    /// know anything, the branches still need to agree on a type, so we use a
+    /// new type variable.
+    fn branch_expectation(&mut self, expected: &Expectation) -> Expectation {
+        match expected.ty {
+            Ty::Unknown => Expectation::has_type(self.new_type_var()),
+            _ => expected.clone(),
+        }
+    }
+
+    /// The type of an `if` or `match` whose branches have been inferred with
+    /// `expected`: `!` if all branches diverge, otherwise the expected type.
+    fn merge_branch_tys(&mut self, expected: &Expectation, branch_tys: &[Ty]) -> Ty {
+        if branch_tys.iter().all(|ty| *ty == Ty::Never) {
+            Ty::Never
         } else {
-            Ok(Ty::Unknown)
+            self.resolve_ty_as_possible(expected.ty.clone())
         }
     }
 
-    fn infer_block(&mut self, node: ast::Block, expected: &Expectation) -> Cancelable<Ty> {
-        for stmt in node.statements() {
+    /// Infers the body of a loop whose `break`s have type `break_ty`, and
+    /// returns the loop's context, which tells whether it was broken out of.
+    fn infer_loop_body(
+        &mut self,
+        body: ExprId,
+        label: &Option<Name>,
+        break_ty: Ty,
+    ) -> Cancelable<BreakableContext> {
+        self.breakables.push(BreakableContext {
+            label: label.clone(),
+            may_break: false,
+            break_ty,
+        });
+        let result = self.infer_expr(body, &Expectation::has_type(Ty::unit()));
+        let ctxt = self.breakables.pop().expect("breakable stack broken");
+        result?;
+        Ok(ctxt)
+    }
+
+    fn infer_block(
+        &mut self,
+        statements: &[Statement],
+        tail: Option<ExprId>,
+        expected: &Expectation,
+    ) -> Cancelable<Ty> {
+        let mut diverges = false;
+        // statements we couldn't lower, like unexpanded `panic!()` calls, may
+        // diverge as well
+        let mut may_diverge = false;
+        for stmt in statements {
             match stmt {
-                ast::Stmt::LetStmt(stmt) => {
-                    let decl_ty = Ty::from_ast_opt(self.db, &self.module, stmt.type_ref())?;
-                    let decl_ty = self.insert_type_vars(decl_ty);
-                    let ty = if let Some(expr) = stmt.initializer() {
-                        let expr_ty = self.infer_expr(expr, &Expectation::has_type(decl_ty))?;
-                        expr_ty
+                Statement::Let {
+                    pat,
+                    type_ref,
+                    initializer,
+                } => {
+                    let decl_ty = if let Some(type_ref) = type_ref {
+                        self.make_ty(type_ref)?
+                    } else {
+                        self.insert_type_vars(Ty::Unknown)
+                    };
+                    let ty = if let Some(expr) = initializer {
+                        let expr_ty = self
+                            .infer_expr_coerce(*expr, &Expectation::has_type(decl_ty.clone()))?;
+                        diverges |= expr_ty == Ty::Never;
+                        // the initializer coerces to the declared type, if
+                        // there is one
+                        if type_ref.is_some() {
+                            decl_ty
+                        } else {
+                            expr_ty
+                        }
                     } else {
                         decl_ty
                     };
 
-                    if let Some(pat) = stmt.pat() {
-                        self.write_ty(pat.syntax(), ty);
-                    };
+                    self.infer_pat(*pat, &ty)?;
                 }
-                ast::Stmt::ExprStmt(expr_stmt) => {
-                    self.infer_expr_opt(expr_stmt.expr(), &Expectation::none())?;
+                Statement::Expr(expr) => {
+                    let ty = self.infer_expr(*expr, &Expectation::none())?;
+                    diverges |= ty == Ty::Never;
+                    may_diverge |= self.body[*expr] == Expr::Missing;
                 }
             }
         }
-        let ty = if let Some(expr) = node.expr() {
-            self.infer_expr(expr, expected)?
+        let ty = if let Some(expr) = tail {
+            self.infer_expr_coerce(expr, expected)?
+        } else if diverges {
+            // a block without tail expression that always returns early (or
+            // panics, loops forever, ...) can have any type
+            Ty::Never
+        } else if may_diverge {
+            Ty::Unknown
         } else {
             Ty::unit()
         };
-        self.write_ty(node.syntax(), ty.clone());
         Ok(ty)
     }
-}
-
-pub fn infer(db: &impl HirDatabase, function: Function) -> Cancelable<InferenceResult> {
-    let scopes = function.scopes(db);
-    let module = function.module(db)?;
-    let mut ctx = InferenceContext::new(db, scopes, module);
-
-    let syntax = function.syntax(db);
-    let node = syntax.borrowed();
 
-    if let Some(param_list) = node.param_list() {
-        for param in param_list.params() {
-            let pat = if let Some(pat) = param.pat() {
-                pat
-            } else {
-                continue;
+    fn collect_fn_signature(&mut self, signature: &FnSignature) -> Cancelable<()> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        // the self parameter (if any) comes first in the body's arguments
+        let self_param_count = if signature.has_self_param() { 1 } else { 0 };
+        for (i, &pat) in body.args().iter().enumerate() {
+            let type_ref = match i.checked_sub(self_param_count) {
+                Some(idx) => signature.params().get(idx),
+                None => signature.self_param(),
             };
-            if let Some(type_ref) = param.type_ref() {
-                let ty = Ty::from_ast(db, &ctx.module, type_ref)?;
-                let ty = ctx.insert_type_vars(ty);
-                ctx.type_of.insert(LocalSyntaxPtr::new(pat.syntax()), ty);
-            } else {
-                // TODO self param
-                let type_var = ctx.new_type_var();
-                ctx.type_of
-                    .insert(LocalSyntaxPtr::new(pat.syntax()), type_var);
+            let ty = match type_ref {
+                Some(type_ref) => self.make_ty(type_ref)?,
+                None => Ty::Unknown,
             };
+            self.infer_pat(pat, &ty)?;
         }
+        self.return_ty = self.make_ty(signature.ret_type())?;
+        self.in_unsafe_context = signature.is_unsafe();
+        Ok(())
     }
 
-    let ret_ty = if let Some(type_ref) = node.ret_type().and_then(|n| n.type_ref()) {
-        let ty = Ty::from_ast(db, &ctx.module, type_ref)?;
-        ctx.insert_type_vars(ty)
-    } else {
-        Ty::unit()
-    };
-
-    if let Some(block) = node.body() {
-        ctx.infer_block(block, &Expectation::has_type(ret_ty))?;
+    fn infer_body(&mut self) -> Cancelable<()> {
+        let body_expr = self.body.body_expr();
+        let ret_ty = self.return_ty.clone();
+        self.infer_expr_coerce(body_expr, &Expectation::has_type(ret_ty))?;
+        Ok(())
     }
+}
+
+fn has_mismatched_descendant(body: &Body, expr: ExprId, mismatched: &FxHashSet<ExprId>) -> bool {
+    let mut found = false;
+    body[expr].walk_child_exprs(|child| {
+        found = found
+            || mismatched.contains(&child)
+            || has_mismatched_descendant(body, child, mismatched);
+    });
+    found
+}
+
+pub fn infer(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<InferenceResult>> {
+    let function = Function::new(def_id); // TODO: consts also need inference
+    let body = function.body(db)?;
+    let scopes = db.fn_scopes(def_id)?;
+    let module = function.module(db)?;
+    let generics = function.generic_params(db);
+    let impl_self_type = function.impl_block(db)?.map(|b| b.target_type().clone());
+    let mut ctx =
+        InferenceContext::new(db, def_id, body, scopes, module, generics, impl_self_type);
+
+    let signature = function.signature(db);
+    ctx.collect_fn_signature(&signature)?;
+
+    ctx.infer_body()?;
 
-    Ok(ctx.resolve_all())
+    Ok(Arc::new(ctx.resolve_all()))
 }
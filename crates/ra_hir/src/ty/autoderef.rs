@@ -0,0 +1,120 @@
+//! In certain situations, rust automatically inserts derefs as necessary: for
+//! example, field accesses `foo.bar` still work when `foo` is actually a
+//! reference to a type with the field `bar`. This is an approximation of the
+//! logic in rustc (which lives in librustc_typeck/check/autoderef.rs).
+
+use crate::{HirDatabase, Cancelable, Def, ImplItem, name::KnownName, type_ref::TypeRef};
+use super::{Ty, method_resolution::impl_substs};
+
+/// rustc stops after 128 steps; this is also our protection against infinite
+/// `Deref` chains.
+const AUTODEREF_RECURSION_LIMIT: usize = 128;
+
+/// Iterator over `ty`, `*ty`, `**ty`, ... as created by `Ty::autoderef`.
+pub struct Autoderef<'a, D: HirDatabase> {
+    db: &'a D,
+    next: Option<Ty>,
+    steps: usize,
+    include_raw_pointers: bool,
+}
+
+impl<'a, D: HirDatabase> Iterator for Autoderef<'a, D> {
+    type Item = Cancelable<Ty>;
+
+    fn next(&mut self) -> Option<Cancelable<Ty>> {
+        let ty = self.next.take()?;
+        if self.steps >= AUTODEREF_RECURSION_LIMIT {
+            return None;
+        }
+        self.steps += 1;
+        match ty.deref(self.db, self.include_raw_pointers) {
+            Ok(next) => self.next = next,
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(ty))
+    }
+}
+
+impl Ty {
+    /// Returns `self`, followed by all types that can be reached from it by
+    /// (auto)dereferencing. Raw pointers are only dereferenced if
+    /// `include_raw_pointers` is set, i.e. in unsafe contexts.
+    pub fn autoderef<'a, D: HirDatabase>(
+        self,
+        db: &'a D,
+        include_raw_pointers: bool,
+    ) -> Autoderef<'a, D> {
+        Autoderef {
+            db,
+            next: Some(self),
+            steps: 0,
+            include_raw_pointers,
+        }
+    }
+
+    fn deref(&self, db: &impl HirDatabase, include_raw_pointers: bool) -> Cancelable<Option<Ty>> {
+        if let Some(derefed) = self.builtin_deref(include_raw_pointers) {
+            return Ok(Some(derefed));
+        }
+        self.deref_by_trait(db)
+    }
+
+    pub(crate) fn builtin_deref(&self, include_raw_pointers: bool) -> Option<Ty> {
+        match self {
+            Ty::Ref(t, _) => Some(Ty::clone(t)),
+            Ty::RawPtr(t, _) if include_raw_pointers => Some(Ty::clone(t)),
+            _ => None,
+        }
+    }
+
+    /// Dereferences `self` using a user `Deref` impl, if we can find one.
+    pub(crate) fn deref_by_trait(&self, db: &impl HirDatabase) -> Cancelable<Option<Ty>> {
+        let krate = match self {
+            Ty::Adt { def_id, .. } => def_id.loc(db).krate,
+            _ => return Ok(None),
+        };
+        let impls = db.impls_in_crate(krate)?;
+        for impl_block in impls.lookup_trait_impl_blocks(self) {
+            let module = impl_block.module(db)?;
+            // TODO: use lang items instead of matching on the trait's name
+            let trait_path = match impl_block.target_trait() {
+                Some(TypeRef::Path(path)) => path,
+                _ => continue,
+            };
+            let trait_def_id = match module.resolve_path(db, trait_path)?.take_types() {
+                Some(def_id) => def_id,
+                None => continue,
+            };
+            let is_deref = match trait_def_id.resolve(db)? {
+                Def::Trait(t) => {
+                    t.name(db)?.and_then(|n| n.as_known_name()) == Some(KnownName::Deref)
+                }
+                _ => false,
+            };
+            if !is_deref {
+                continue;
+            }
+            let target = impl_block
+                .items()
+                .iter()
+                .find_map(|item| match item {
+                    ImplItem::Type { name, type_ref }
+                        if name.as_known_name() == Some(KnownName::Target) =>
+                    {
+                        Some(type_ref)
+                    }
+                    _ => None,
+                });
+            let target = match target {
+                Some(it) => it,
+                None => continue,
+            };
+            let generics = impl_block.generic_params();
+            let impl_ty = Ty::from_hir(db, &module, generics, impl_block.target_type())?;
+            let target_ty = Ty::from_hir(db, &module, generics, target)?;
+            let substs = impl_substs(&impl_ty, self, generics);
+            return Ok(Some(target_ty.subst(&substs)));
+        }
+        Ok(None)
+    }
+}
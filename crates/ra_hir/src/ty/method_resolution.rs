@@ -0,0 +1,341 @@
+//! This module is concerned with finding methods that a given type provides.
+//! For details about how this works in rustc, see the method lookup page in the
+//! [rustc guide](https://rust-lang.github.io/rustc-guide/method-lookup.html)
+//! and the corresponding code mostly in librustc_typeck/check/method/probe.rs.
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use ra_db::CrateId;
+
+use crate::{
+    HirDatabase, Def, DefId, Cancelable, Function, Module, Name, Trait, TraitItem,
+    impl_block::{ImplBlock, ImplItem},
+    generics::GenericParams,
+    type_ref::TypeRef,
+};
+use super::{Ty, Substs, traits::{self, TraitRef, Implementation}};
+
+/// This is used as a key for indexing impls.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TyFingerprint {
+    Adt(DefId),
+    // we'll also want to index impls for primitive types etc.
+}
+
+impl TyFingerprint {
+    /// Creates a TyFingerprint for looking up an impl. Only certain types can
+    /// have impls: if we have some `struct S`, we can have an `impl S`, but not
+    /// `impl &S`. Hence, this will return `None` for reference types and such.
+    fn for_impl(ty: &Ty) -> Option<TyFingerprint> {
+        match ty {
+            Ty::Adt { def_id, .. } => Some(TyFingerprint::Adt(*def_id)),
+            _ => None,
+        }
+    }
+}
+
+/// All impl blocks of a crate, indexed by the type they are for.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrateImplBlocks {
+    /// Inherent impls.
+    impls: FxHashMap<TyFingerprint, Vec<ImplBlock>>,
+    /// Trait impls.
+    trait_impls: FxHashMap<TyFingerprint, Vec<ImplBlock>>,
+    /// Trait impls, indexed by the trait. Unlike `trait_impls`, this also
+    /// contains blanket impls like `impl<T: Display> ToString for T`.
+    impls_by_trait: FxHashMap<DefId, Vec<ImplBlock>>,
+}
+
+impl CrateImplBlocks {
+    /// Returns the inherent impl blocks for `ty`, in no particular order.
+    pub fn lookup_impl_blocks<'a>(&'a self, ty: &Ty) -> impl Iterator<Item = &'a ImplBlock> + 'a {
+        let fingerprint = TyFingerprint::for_impl(ty);
+        fingerprint
+            .and_then(|f| self.impls.get(&f))
+            .into_iter()
+            .flat_map(|i| i.iter())
+    }
+
+    /// Returns the trait impl blocks for `ty`, in no particular order.
+    pub fn lookup_trait_impl_blocks<'a>(
+        &'a self,
+        ty: &Ty,
+    ) -> impl Iterator<Item = &'a ImplBlock> + 'a {
+        let fingerprint = TyFingerprint::for_impl(ty);
+        fingerprint
+            .and_then(|f| self.trait_impls.get(&f))
+            .into_iter()
+            .flat_map(|i| i.iter())
+    }
+
+    /// Returns all impl blocks of the trait `trait_def_id`, in no particular
+    /// order.
+    pub fn lookup_impl_blocks_for_trait<'a>(
+        &'a self,
+        trait_def_id: DefId,
+    ) -> impl Iterator<Item = &'a ImplBlock> + 'a {
+        self.impls_by_trait
+            .get(&trait_def_id)
+            .into_iter()
+            .flat_map(|i| i.iter())
+    }
+
+    fn collect(&mut self, db: &impl HirDatabase, module: Module) -> Cancelable<()> {
+        let module_impl_blocks = db.impls_in_module(module.krate, module.module_id)?;
+
+        for impl_id in module_impl_blocks.impl_ids() {
+            let impl_block = ImplBlock::from_id(module_impl_blocks.clone(), impl_id);
+            let target_ty = Ty::from_hir(
+                db,
+                &module,
+                impl_block.generic_params(),
+                impl_block.target_type(),
+            )?;
+            if let Some(TypeRef::Path(trait_path)) = impl_block.target_trait() {
+                if let Some(trait_def_id) = module.resolve_path(db, trait_path)?.take_types() {
+                    self.impls_by_trait
+                        .entry(trait_def_id)
+                        .or_default()
+                        .push(impl_block.clone());
+                }
+            }
+            if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
+                let impls = if impl_block.target_trait().is_some() {
+                    &mut self.trait_impls
+                } else {
+                    &mut self.impls
+                };
+                impls.entry(target_ty_fp).or_default().push(impl_block);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn impls_in_crate(
+    db: &impl HirDatabase,
+    krate: CrateId,
+) -> Cancelable<Arc<CrateImplBlocks>> {
+    let mut crate_impl_blocks = CrateImplBlocks {
+        impls: FxHashMap::default(),
+        trait_impls: FxHashMap::default(),
+        impls_by_trait: FxHashMap::default(),
+    };
+    // FIXME: like the item map, this is per source root instead of per crate
+    let module_tree = db.module_tree(krate)?;
+    for module_id in module_tree.modules() {
+        let module = Module::new(db, krate, module_id)?;
+        crate_impl_blocks.collect(db, module)?;
+    }
+    Ok(Arc::new(crate_impl_blocks))
+}
+
+impl Ty {
+    /// Looks up the method `name` for this type, returning the type it was
+    /// found on after autoderef and the `DefId` of the method. `module` and
+    /// `generics` are those of the calling function; they determine the traits
+    /// in scope and the where clauses that hold. Associated functions without
+    /// a `self` parameter are not considered.
+    pub fn lookup_method(
+        self,
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        name: &Name,
+    ) -> Cancelable<Option<(Ty, DefId)>> {
+        self.iterate_methods(db, module, generics, |ty, f| {
+            if &f.name(db) == name && f.has_self_param(db) {
+                Ok(Some((ty.clone(), f.def_id())))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Looks up the associated function `name` of this type, as in `Foo::new`.
+    /// Unlike for method calls, functions without a `self` parameter are
+    /// considered, and the type isn't autoderefed.
+    pub fn lookup_associated_fn(
+        self,
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        name: &Name,
+    ) -> Cancelable<Option<DefId>> {
+        let env = traits::lower_predicates(db, module, generics)?;
+        let traits_in_scope = module.traits_in_scope(db)?;
+        self.iterate_methods_of_ty(db, &env, &traits_in_scope, &mut |f| {
+            if &f.name(db) == name {
+                Ok(Some(f.def_id()))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Calls `callback` for each method of this type or of the types it
+    /// autoderefs to, together with the type the method was found on, until it
+    /// returns `Some`. For each type, inherent methods come first, followed by
+    /// the methods of the traits in scope in `module` which the type
+    /// implements. The traits of a `dyn Trait` or `impl Trait` type don't need
+    /// to be in scope.
+    // This would be nicer if it just returned an iterator, but that's really
+    // complicated with all the cancelable operations
+    pub fn iterate_methods<T>(
+        self,
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        mut callback: impl FnMut(&Ty, Function) -> Cancelable<Option<T>>,
+    ) -> Cancelable<Option<T>> {
+        let env = traits::lower_predicates(db, module, generics)?;
+        let traits_in_scope = module.traits_in_scope(db)?;
+        // For method calls, rust first does any number of autoderef, and then
+        // one autoref (i.e. when the method takes &self or &mut self). We just
+        // ignore the autoref part for now, since we don't typecheck the self
+        // parameter anyway. Raw pointers are never autoderefed for methods.
+        for derefed_ty in self.autoderef(db, false) {
+            let derefed_ty = derefed_ty?;
+            let result =
+                derefed_ty.iterate_methods_of_ty(db, &env, &traits_in_scope, &mut |f| {
+                    callback(&derefed_ty, f)
+                })?;
+            if result.is_some() {
+                return Ok(result);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Calls `callback` for the inherent methods of this type, then for the
+    /// methods of the traits in `traits_in_scope` it implements.
+    fn iterate_methods_of_ty<T>(
+        &self,
+        db: &impl HirDatabase,
+        env: &[TraitRef],
+        traits_in_scope: &[Trait],
+        callback: &mut impl FnMut(Function) -> Cancelable<Option<T>>,
+    ) -> Cancelable<Option<T>> {
+        if let Some(result) = self.iterate_inherent_methods(db, callback)? {
+            return Ok(Some(result));
+        }
+        let mut traits = self.bound_traits(db)?;
+        for &trait_def in traits_in_scope.iter() {
+            if !traits.contains(&trait_def) {
+                traits.push(trait_def);
+            }
+        }
+        for &trait_def in traits.iter() {
+            let result = self.iterate_trait_methods(db, env, trait_def, callback)?;
+            if result.is_some() {
+                return Ok(result);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The traits of the bounds of a `dyn Trait` or `impl Trait` type.
+    fn bound_traits(&self, db: &impl HirDatabase) -> Cancelable<Vec<Trait>> {
+        let bounds = match self {
+            Ty::Dynamic(bounds) | Ty::Opaque(bounds) => bounds,
+            _ => return Ok(Vec::new()),
+        };
+        let mut traits = Vec::new();
+        for bound in bounds.iter() {
+            if let Def::Trait(trait_def) = bound.trait_def_id.resolve(db)? {
+                traits.push(trait_def);
+            }
+        }
+        Ok(traits)
+    }
+
+    fn iterate_inherent_methods<T>(
+        &self,
+        db: &impl HirDatabase,
+        callback: &mut impl FnMut(Function) -> Cancelable<Option<T>>,
+    ) -> Cancelable<Option<T>> {
+        let krate = match self {
+            Ty::Adt { def_id, .. } => def_id.loc(db).krate,
+            _ => return Ok(None),
+        };
+        let impls = db.impls_in_crate(krate)?;
+
+        for impl_block in impls.lookup_impl_blocks(self) {
+            for item in impl_block.items() {
+                match item {
+                    ImplItem::Method(f) => {
+                        if let Some(result) = callback(f.clone())? {
+                            return Ok(Some(result));
+                        }
+                    }
+                    ImplItem::Type { .. } => {}
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Calls `callback` for the methods of `trait_def` if this type implements
+    /// it. Methods defined in the impl are preferred over the trait's default
+    /// methods.
+    fn iterate_trait_methods<T>(
+        &self,
+        db: &impl HirDatabase,
+        env: &[TraitRef],
+        trait_def: Trait,
+        callback: &mut impl FnMut(Function) -> Cancelable<Option<T>>,
+    ) -> Cancelable<Option<T>> {
+        let trait_data = trait_def.trait_data(db)?;
+        let has_methods = trait_data.items().iter().any(|item| match item {
+            TraitItem::Method(_) => true,
+            TraitItem::Type(_) => false,
+        });
+        if !has_methods {
+            return Ok(None);
+        }
+        let goal = TraitRef::for_trait(db, trait_def, self.clone());
+        let implementation = ctry!(traits::implements_in_env(db, env, &goal)?);
+        for item in trait_data.items() {
+            let trait_method = match item {
+                TraitItem::Method(f) => f,
+                TraitItem::Type(_) => continue,
+            };
+            let method = match &implementation {
+                Implementation::Impl(impl_block, _) => {
+                    let name = trait_method.name(db);
+                    impl_block
+                        .items()
+                        .iter()
+                        .find_map(|item| match item {
+                            ImplItem::Method(f) if f.name(db) == name => Some(f.clone()),
+                            _ => None,
+                        })
+                        .unwrap_or_else(|| trait_method.clone())
+                }
+                Implementation::Bound => trait_method.clone(),
+            };
+            if let Some(result) = callback(method)? {
+                return Ok(Some(result));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Computes the substitutions for the generic parameters of an impl block,
+/// given the (unsubstituted) target type of the impl and the actual type. So
+/// for `impl<T> Deref for Box<T>` and `Box<u32>`, this returns `<u32>`.
+/// Parameters that can't be determined are substituted by `Ty::Unknown`.
+pub(crate) fn impl_substs(impl_ty: &Ty, ty: &Ty, generics: &GenericParams) -> Substs {
+    let mut substs = vec![Ty::Unknown; generics.params.len()];
+    if let (Some(impl_ty_substs), Some(ty_substs)) = (impl_ty.substs(), ty.substs()) {
+        for (impl_ty_subst, ty_subst) in impl_ty_substs.tys().iter().zip(ty_substs.tys()) {
+            if let Ty::Param { idx, .. } = impl_ty_subst {
+                if let Some(subst) = substs.get_mut(*idx as usize) {
+                    *subst = ty_subst.clone();
+                }
+            }
+        }
+    }
+    Substs(substs.into())
+}
@@ -56,7 +56,7 @@ fn infer_paths() {
 fn a() -> u32 { 1 }
 
 mod b {
-    fn c() -> u32 { 1 }
+    pub fn c() -> u32 { 1 }
 }
 
 fn test() {
@@ -134,6 +134,696 @@ fn test() -> &mut &f64 {
     );
 }
 
+#[test]
+fn infer_generics() {
+    check_inference(
+        r#"
+struct A<T> {
+    x: T,
+}
+
+fn id<T>(t: T) -> T { t }
+
+fn test(a1: A<u32>, i: i32) {
+    a1.x;
+    let a2 = A { x: i };
+    a2.x;
+    let a3 = A::<i128> { x: 1 };
+    a3.x;
+    id(1u32);
+    id::<i128>(1);
+    let x: u64 = id(1);
+    let y = id(a1);
+    y.x;
+}
+"#,
+        "0007_generics.txt",
+    );
+}
+
+#[test]
+fn infer_inherent_method() {
+    check_inference(
+        r#"
+struct A;
+
+impl A {
+    fn foo(self, x: u32) -> i32 {}
+}
+
+mod b {
+    impl super::A {
+        fn bar(&self, x: u64) -> i64 {}
+    }
+}
+
+fn test(a: A) {
+    a.foo(1);
+    a.bar(1);
+    a.baz();
+}
+"#,
+        "0008_inherent_method.txt",
+    );
+}
+
+#[test]
+fn infer_generic_impl_methods() {
+    check_inference(
+        r#"
+struct V<T> { t: T }
+struct Pair<A, B> { a: A, b: B }
+
+impl<T> V<T> {
+    fn new(t: T) -> V<T> { V { t } }
+    fn get(&self) -> T { self.t }
+    fn pair<U>(self, u: U) -> Pair<T, U> {}
+}
+
+fn test(v: V<i64>, r: &V<u8>) {
+    let a = v.get();
+    let b = r.get();
+    let c = v.pair(1u16);
+    let d = V::<u32>::new(1);
+    let e = V::new(1i8).get();
+}
+"#,
+        "0022_generic_impl_methods.txt",
+    );
+}
+
+#[test]
+fn infer_autoderef() {
+    check_inference(
+        r#"
+trait Deref {
+    type Target;
+}
+
+struct A {
+    x: u32,
+}
+
+impl A {
+    fn foo(&self) -> i32 {}
+}
+
+struct Wrapper<T> {
+    inner: T,
+}
+
+impl<T> Deref for Wrapper<T> {
+    type Target = T;
+}
+
+fn test(a: &&A, p: *const A, w: Wrapper<A>) {
+    a.x;
+    a.foo();
+    p.x;
+    unsafe {
+        p.x;
+    }
+    w.x;
+    w.foo();
+    *w;
+}
+"#,
+        "0009_autoderef.txt",
+    );
+}
+
+#[test]
+fn infer_literal_vars() {
+    check_inference(
+        r#"
+fn takes_f32(x: f32) {}
+
+fn test() {
+    let a = 1;
+    let b: u8 = a;
+    let c = 2;
+    let d = 3usize;
+    let e = 1.5;
+    takes_f32(e);
+    let f = 2.5;
+    let g = 1f64;
+    let h = 0xff;
+    let s = "foo";
+    let bs = b"bar";
+    let ch = 'c';
+    let by = b'b';
+    let t = true;
+}
+"#,
+        "0010_literal_vars.txt",
+    );
+}
+
+#[test]
+fn infer_pattern() {
+    check_inference(
+        r#"
+struct A<T> {
+    x: T,
+    y: u32,
+}
+
+struct B(u8, i64);
+
+fn test(x: &i32, (p, q): (u8, &u16)) {
+    let y = x;
+    let &z = x;
+    let a = z;
+    let (c, d) = (1, "hello");
+    let A { x: e, y } = A { x: 1u64, y: 2 };
+    let A { ref x, .. } = A { x: "foo", y: 2 };
+    let B(f, g) = B(1, 2);
+    let h @ _ = y;
+
+    for (i, j) in "foo" {
+        i;
+    }
+
+    if let x @ true = &true {}
+
+    let lambda = |a: u64, b, c: i32| { a + b; c };
+
+    let ref ref_to_x = x;
+    let mut mut_x = x;
+    let ref mut mut_ref_to_x = x;
+    let k = mut_ref_to_x;
+}
+"#,
+        "0011_pattern.txt",
+    );
+}
+
+#[test]
+fn infer_literal_patterns() {
+    check_inference(
+        r#"
+fn test(b: bool, r: &u8, s: &&str) {
+    if let x @ true = b {}
+    if let y @ 1 = r {}
+    match s {
+        &"a" => {}
+        "b" => {}
+        _ => {}
+    }
+}
+"#,
+        "0023_literal_patterns.txt",
+    );
+}
+
+#[test]
+fn infer_tuple_rest_patterns() {
+    check_inference(
+        r#"
+fn test(t: (i32, bool, char, &str)) {
+    let (a, .., z) = (1u8, "s", 2u16);
+    let (b, ..) = t;
+    let (.., c, d) = t;
+    let (..) = t;
+    let (e, .., f, g) = (1u8, 2u16);
+}
+"#,
+        "0029_tuple_rest_patterns.txt",
+    );
+}
+
+#[test]
+fn infer_type_alias_cycles() {
+    check_inference(
+        r#"
+struct S<T>;
+type A = B;
+type B = A;
+type C = C;
+type D = S<D>;
+type E = A;
+type Name = &'static str;
+type Flag = bool;
+static NAME: Name = "";
+const INITIAL: char = 'a';
+
+fn test(a: A, c: C, d: D, e: E, f: Flag) {
+    a;
+    c;
+    d;
+    e;
+    f;
+    NAME;
+    INITIAL;
+}
+"#,
+        "0024_type_alias_cycles.txt",
+    );
+}
+
+#[test]
+fn infer_branches_and_divergence() {
+    check_inference(
+        r#"
+fn test(b: bool, c: u8) -> u32 {
+    let x = loop { break 5 };
+    let y = loop {};
+    let z = loop { break; };
+    let m = match c {
+        0 => 1,
+        n if n > 5 => return 2,
+        _ => 3u64,
+    };
+    let e = if b { 1u16 } else if b { 2 } else { return 3; };
+    let f = if b { return 4; } else { 5 };
+    let g = |a| { return a; };
+    while b {
+        break;
+    }
+    let d: i8 = return;
+    6
+}
+"#,
+        "0012_branches_and_divergence.txt",
+    );
+}
+
+#[test]
+fn infer_labeled_breaks() {
+    check_inference(
+        r#"
+fn test(b: bool) {
+    let x = 'outer: loop {
+        let y = loop {
+            if b {
+                break 'outer 1u32;
+            }
+            break "s";
+        };
+    };
+    'a: while b {
+        let z = loop {
+            break 'a;
+        };
+    }
+}
+"#,
+        "0030_labeled_breaks.txt",
+    );
+}
+
+#[test]
+fn infer_enum_variants() {
+    check_inference(
+        r#"
+enum E<T> {
+    A(T, u32),
+    B { x: T },
+    C,
+}
+
+use self::E::B;
+
+fn test() {
+    let a = E::A(1u64, 2);
+    let b = B { x: "foo" };
+    let c: E<i8> = E::C;
+    let f = E::A;
+    match a {
+        E::A(x, y) => {}
+        E::B { x } => {}
+        E::C => {}
+    }
+    if let B { x: s } = b {}
+}
+"#,
+        "0013_enum_variants.txt",
+    );
+}
+
+#[test]
+fn infer_type_aliases_consts_and_statics() {
+    check_inference(
+        r#"
+struct S<T> { x: T }
+mod result {
+    pub enum Result<T, E> { Ok(T), Err(E) }
+}
+struct Error;
+type Result<T> = result::Result<T, Error>;
+type SU32 = S<u32>;
+type Pair<A> = (A, A);
+
+const MAX: usize = 92;
+static NAME: &str = "foo";
+
+fn io() -> Result<()> {}
+
+fn test(p: Pair<u8>) {
+    let r = io();
+    let s = SU32 { x: 1 };
+    let x = s.x;
+    let a = p;
+    let m = MAX;
+    let n = NAME;
+}
+"#,
+        "0014_type_aliases_consts_and_statics.txt",
+    );
+}
+
+#[test]
+fn infer_associated_types() {
+    check_inference(
+        r#"
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<<Self as Iterator>::Item>;
+}
+trait IntoIterator {
+    type Item;
+    type IntoIter;
+}
+trait Trait<T> {
+    type Output;
+}
+enum Option<T> { Some(T), None }
+struct Vec<T> {}
+struct Iter<T> {}
+struct S;
+
+impl<T> Vec<T> {
+    fn iter(&self) -> Iter<T> {}
+}
+impl<T> Iterator for Iter<T> {
+    type Item = T;
+}
+impl<T> IntoIterator for Vec<T> {
+    type Item = T;
+    type IntoIter = Iter<T>;
+}
+impl Trait<u8> for S {
+    type Output = (u8, u32);
+}
+
+fn make<T>() -> T {}
+fn project<T: IntoIterator>(t: T) -> <T as IntoIterator>::IntoIter {}
+
+fn test(v: Vec<u64>) {
+    let out: <S as Trait<u8>>::Output = make();
+    let iter = project(v);
+    for x in v {}
+    for y in v.iter() {}
+    for z in iter {}
+}
+"#,
+        "0015_associated_types.txt",
+    );
+}
+
+#[test]
+fn infer_trait_method_calls() {
+    check_inference(
+        r#"
+struct S;
+struct NotDisplay;
+struct Wrapper<T> { t: T }
+trait Display { fn fmt(&self) -> u32; }
+trait ToString { fn to_string(&self) -> i64; }
+mod m {
+    pub trait Hidden { fn hidden(&self) -> u8; }
+    impl Hidden for super::S { fn hidden(&self) -> u8 {} }
+}
+
+impl Display for S { fn fmt(&self) -> u32 {} }
+impl<T> Display for Wrapper<T> where T: Display { fn fmt(&self) -> u32 {} }
+impl<T: Display> ToString for T {}
+
+fn generic<T: Display>(t: T) {
+    let a = t.fmt();
+    let b = t.to_string();
+}
+
+fn test(s: S, w: Wrapper<S>, n: Wrapper<NotDisplay>) {
+    let a = s.fmt();
+    let b = s.to_string();
+    let c = w.to_string();
+    let d = n.to_string();
+    let e = s.hidden();
+}
+"#,
+        "0016_trait_method_calls.txt",
+    );
+}
+
+#[test]
+fn infer_cyclic_trait_impls() {
+    check_inference(
+        r#"
+struct S;
+struct W<T> { t: T }
+trait Foo { fn foo(&self) -> u32; }
+impl<T: Foo> Foo for T {}
+impl<U: Foo> Foo for U {}
+trait Bar { fn bar(&self) -> u64; }
+impl<T> Bar for T where W<T>: Bar {}
+impl<U> Bar for U where W<U>: Bar {}
+
+fn test(s: S) {
+    let a = s.foo();
+    let b = s.bar();
+}
+"#,
+        "0025_cyclic_trait_impls.txt",
+    );
+}
+
+#[test]
+fn infer_closures() {
+    check_inference(
+        r#"
+struct Vec<T> {}
+struct Map<T> {}
+trait FnOnce {}
+trait Fn {}
+impl<T> Vec<T> {
+    fn map<U, F: Fn(T) -> U>(self, f: F) -> Map<U> {}
+}
+fn apply<F>(f: F) -> u64 where F: FnOnce(u32, i8) -> u64 {}
+
+fn test(v: Vec<u8>) {
+    let add = |a: u32, b| a + b;
+    let x = add(1, 2);
+    let unit = || {};
+    let r = apply(|a, b| { let c = a; 1 });
+    let m = v.map(|x| x);
+}
+"#,
+        "0017_closures.txt",
+    );
+}
+
+#[test]
+fn infer_coercion() {
+    check_inference(
+        r#"
+struct String {}
+trait Deref {
+    type Target;
+}
+impl Deref for String {
+    type Target = str;
+}
+fn takes_str(s: &str) -> usize {}
+fn takes_ref(x: &u32) {}
+fn as_str(s: &String) -> &str {
+    s
+}
+
+fn test(s: String, m: &mut u32) {
+    takes_str(&s);
+    takes_ref(m);
+    let a: &str = &s;
+    let b: &u32 = m;
+    let c: u32 = return;
+    let d = &s;
+}
+"#,
+        "0018_coercion.txt",
+    );
+}
+
+#[test]
+fn infer_unsizing_coercion() {
+    check_inference(
+        r#"
+fn takes_slice(s: &[u8]) {}
+fn takes_ptr(p: *const [u8]) {}
+
+fn test(a: &[u8; 2], m: &mut [u8; 3], p: *mut [u8; 4]) {
+    takes_slice(a);
+    takes_slice(m);
+    takes_ptr(p);
+    let s: &[u8] = &[1, 2];
+    let t: *const [u8] = p;
+    let n: &mut [u8] = a;
+}
+"#,
+        "0026_unsizing_coercion.txt",
+    );
+}
+
+#[test]
+fn infer_arrays() {
+    check_inference(
+        r#"
+const N: usize = 4;
+const M: usize = (N + 1) * 2;
+const BAD: usize = 1 - 2;
+fn takes_slice(s: &[u8]) {}
+
+fn test(a: [u32; N * 2], b: [u8; M], c: [i8; BAD], d: &[u16]) {
+    let x = [0u8; 16];
+    let y = [1, 2, 3];
+    let z: [u64; 0x10] = [0; 16];
+    let e = [[1u16; 2]; 3];
+    let i = a[1];
+    let j = d[0];
+    let k = e[0][1];
+    let l = x.len();
+    let m = d.len();
+    takes_slice(&x);
+    takes_slice(&b);
+}
+"#,
+        "0019_arrays.txt",
+    );
+}
+
+#[test]
+fn infer_array_len_cycles() {
+    check_inference(
+        r#"
+const C: usize = C + C;
+const A: usize = B * 2;
+const B: usize = A + 1;
+const N0: usize = 1;
+const N1: usize = N0 + N0;
+const N2: usize = N1 + N1;
+const N3: usize = N2 + N2;
+
+fn test(c: [u8; C], a: [u8; A], n: [u8; N3]) {
+    c;
+    a;
+    n;
+}
+"#,
+        "0027_array_len_cycles.txt",
+    );
+}
+
+#[test]
+fn infer_byte_string_literals() {
+    check_inference(
+        r#"
+fn takes_slice(s: &[u8]) {}
+
+fn test() {
+    let a = b"abc";
+    let b: &[u8; 3] = b"a\x62c";
+    let c: &[u8] = b"ab";
+    let d = br"a\b";
+    takes_slice(b"\
+        xyz");
+}
+"#,
+        "0028_byte_string_literals.txt",
+    );
+}
+
+#[test]
+fn infer_impl_trait_and_dyn_trait() {
+    check_inference(
+        r#"
+trait Iterator {
+    type Item;
+    fn count(self) -> usize;
+}
+trait Handler<T> {
+    fn handle(&self, t: T) -> bool;
+}
+trait Send {}
+trait Deref {
+    type Target;
+}
+struct Box<T> {}
+impl<T> Deref for Box<T> {
+    type Target = T;
+}
+struct Foo {}
+struct S {
+    h: Box<dyn Handler<u8>>,
+}
+fn foos() -> impl Iterator<Item = Foo> {}
+fn handler() -> Box<dyn Handler<u8> + Send> {}
+fn item(x: <dyn Iterator<Item = u32> as Iterator>::Item) {}
+
+fn test(s: S) {
+    let it = foos();
+    for f in foos() {
+        f;
+    }
+    let n = it.count();
+    let h = s.h;
+    let handled = h.handle(1);
+    let r = handler();
+}
+"#,
+        "0020_impl_trait_and_dyn_trait.txt",
+    );
+}
+
+#[test]
+fn infer_self_type() {
+    check_inference(
+        r#"
+struct Point<T> { x: T, y: T }
+
+impl Point<u32> {
+    fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+    fn origin() -> Point<u32> {
+        Self::new(0, 0)
+    }
+    fn by_value(self) -> u32 { self.x }
+    fn by_ref(&self) -> &u32 { &self.y }
+    fn by_mut_ref(&mut self) { self.x = 1; }
+    fn explicit(self: &Self, other: Self) -> bool {
+        let copy: Self = Point::new(other.x, self.y);
+        copy.x == other.y
+    }
+}
+
+trait Zero {
+    fn zero() -> Self;
+}
+
+impl Zero for u32 {
+    fn zero() -> Self { 0 }
+}
+
+fn test() {
+    let p = Point::origin();
+    let q = Point::<u32>::new(1, 2);
+    let x = p.by_ref();
+    let z = u32::zero();
+}
+"#,
+        "0021_self_type.txt",
+    );
+}
+
 fn infer(content: &str) -> String {
     let (db, _, file_id) = MockDatabase::with_single_file(content);
     let source_file = db.source_file(file_id);
@@ -147,7 +837,28 @@ fn infer(content: &str) -> String {
             .unwrap()
             .unwrap();
         let inference_result = func.infer(&db).unwrap();
-        for (syntax_ptr, ty) in &inference_result.type_of {
+        let body_syntax_mapping = func.body_syntax_mapping(&db).unwrap();
+        let mut types = Vec::new();
+        for (pat, ty) in inference_result.type_of_pat.iter() {
+            let syntax_ptr = match body_syntax_mapping.pat_syntax(*pat) {
+                Some(sp) => sp,
+                None => continue,
+            };
+            types.push((syntax_ptr, ty));
+        }
+        for (expr, ty) in inference_result.type_of_expr.iter() {
+            let syntax_ptr = match body_syntax_mapping.expr_syntax(*expr) {
+                Some(sp) => sp,
+                None => continue,
+            };
+            types.push((syntax_ptr, ty));
+        }
+        // sort by range so the snapshots don't depend on hash map order
+        types.sort_by_key(|(syntax_ptr, _)| {
+            let range = syntax_ptr.range();
+            (range.start(), range.end())
+        });
+        for (syntax_ptr, ty) in types {
             let node = syntax_ptr.resolve(&source_file);
             write!(
                 acc,
@@ -0,0 +1,473 @@
+//! A simple trait solver, and normalization of associated type projections
+//! using it. To check whether `T: Trait` holds, we look at the where clauses
+//! in scope and at the impls of `Trait`, recursively checking the where
+//! clauses of the impls. This is far from what rustc does (no coherence, no
+//! supertraits, no inference of trait arguments), but it covers the common
+//! cases of method resolution.
+
+use std::iter;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    HirDatabase, Cancelable, Def, DefId, Module, Path, Trait, Name, ImplBlock, ImplItem,
+    name::KnownName,
+    type_ref::TypeRef,
+    generics::GenericParams,
+};
+use super::{Ty, Substs, ProjectionTy, method_resolution::impl_substs};
+
+/// rustc's default recursion limit; stops us from looping forever on impls
+/// like `impl<T: Foo> Foo for Vec<T>`.
+const SOLVER_RECURSION_LIMIT: usize = 64;
+
+/// A trait applied to a self type and the trait's generic arguments, e.g.
+/// `Vec<u32>: IntoIterator`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TraitRef {
+    pub(crate) trait_def_id: DefId,
+    /// The self type, followed by the generic arguments of the trait.
+    pub(crate) substs: Substs,
+}
+
+impl TraitRef {
+    /// `ty: Trait<_, ..>`, with unknown trait arguments.
+    pub(crate) fn for_trait(db: &impl HirDatabase, trait_def: Trait, ty: Ty) -> TraitRef {
+        let param_count = trait_def.generic_params(db).params.len();
+        let substs: Vec<_> = iter::once(ty)
+            .chain(iter::repeat(Ty::Unknown).take(param_count))
+            .collect();
+        TraitRef {
+            trait_def_id: trait_def.def_id(),
+            substs: Substs(substs.into()),
+        }
+    }
+
+    /// Lowers `self_ty: trait_path`, resolving the trait in `module`.
+    pub(crate) fn from_hir(
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        self_ty: Ty,
+        trait_path: &Path,
+    ) -> Cancelable<Option<TraitRef>> {
+        let trait_def_id = ctry!(module.resolve_path(db, trait_path)?.take_types());
+        match trait_def_id.resolve(db)? {
+            Def::Trait(_) => {}
+            _ => return Ok(None),
+        }
+        let trait_substs = Ty::substs_from_path(db, module, generics, trait_path, trait_def_id)?;
+        let substs: Vec<_> = iter::once(self_ty)
+            .chain(trait_substs.0.iter().cloned())
+            .collect();
+        Ok(Some(TraitRef {
+            trait_def_id,
+            substs: Substs(substs.into()),
+        }))
+    }
+
+    pub fn self_ty(&self) -> &Ty {
+        &self.substs.0[0]
+    }
+
+    fn subst(&self, substs: &Substs) -> TraitRef {
+        let tys: Vec<_> = self.substs.0.iter().map(|ty| ty.clone().subst(substs)).collect();
+        TraitRef {
+            trait_def_id: self.trait_def_id,
+            substs: Substs(tys.into()),
+        }
+    }
+}
+
+/// How a trait is implemented for a type.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Implementation {
+    /// By an impl block; the substitutions are for its generic parameters.
+    Impl(ImplBlock, Substs),
+    /// By a where clause or a bound of a type parameter.
+    Bound,
+}
+
+/// Lowers the where clauses and type parameter bounds of `generics`.
+pub(crate) fn lower_predicates(
+    db: &impl HirDatabase,
+    module: &Module,
+    generics: &GenericParams,
+) -> Cancelable<Vec<TraitRef>> {
+    let mut res = Vec::new();
+    for pred in generics.predicates_including_parent() {
+        let self_ty = Ty::from_hir(db, module, generics, &pred.type_ref)?;
+        let trait_ref = TraitRef::from_hir(db, module, generics, self_ty, &pred.trait_path)?;
+        res.extend(trait_ref);
+    }
+    Ok(res)
+}
+
+/// Checks whether `trait_ref` holds without any where clauses in scope.
+pub(crate) fn implements(
+    db: &impl HirDatabase,
+    trait_ref: TraitRef,
+) -> Cancelable<Option<Implementation>> {
+    Solver::new(db, &[]).solve(&trait_ref)
+}
+
+/// Checks whether `trait_ref` holds, assuming the where clauses `env`.
+pub(crate) fn implements_in_env(
+    db: &impl HirDatabase,
+    env: &[TraitRef],
+    trait_ref: &TraitRef,
+) -> Cancelable<Option<Implementation>> {
+    if env.is_empty() {
+        return db.implements(trait_ref.clone());
+    }
+    Solver::new(db, env).solve(trait_ref)
+}
+
+struct Solver<'a, DB> {
+    db: &'a DB,
+    env: &'a [TraitRef],
+    /// The goals currently being solved. A goal which depends on itself, like
+    /// with `impl<T: Foo> Foo for T`, doesn't hold.
+    stack: Vec<TraitRef>,
+    /// The results of the goals solved so far. Without this, impls with
+    /// several where clauses which can't be proven would be explored an
+    /// exponential number of times. Note that a goal which failed because of
+    /// a cycle is remembered as failed, even if it might hold on its own.
+    cache: FxHashMap<TraitRef, Option<Implementation>>,
+}
+
+impl<'a, DB> Solver<'a, DB>
+where
+    DB: HirDatabase,
+{
+    fn new(db: &'a DB, env: &'a [TraitRef]) -> Solver<'a, DB> {
+        Solver {
+            db,
+            env,
+            stack: Vec::new(),
+            cache: FxHashMap::default(),
+        }
+    }
+
+    fn solve(&mut self, goal: &TraitRef) -> Cancelable<Option<Implementation>> {
+        if let Some(res) = self.cache.get(goal) {
+            return Ok(res.clone());
+        }
+        if self.stack.len() > SOLVER_RECURSION_LIMIT || self.stack.contains(goal) {
+            return Ok(None);
+        }
+        self.stack.push(goal.clone());
+        let res = self.solve_uncached(goal);
+        self.stack.pop();
+        let res = res?;
+        self.cache.insert(goal.clone(), res.clone());
+        Ok(res)
+    }
+
+    fn solve_uncached(&mut self, goal: &TraitRef) -> Cancelable<Option<Implementation>> {
+        let db = self.db;
+        let from_env = self.env.iter().any(|bound| {
+            bound.trait_def_id == goal.trait_def_id
+                && match_substs(&bound.substs, &goal.substs, &mut [])
+        });
+        if from_env {
+            return Ok(Some(Implementation::Bound));
+        }
+        match goal.self_ty() {
+            Ty::Unknown | Ty::Infer(_) => return Ok(None),
+            Ty::Dynamic(bounds) | Ty::Opaque(bounds) => {
+                let trait_substs = Substs(goal.substs.0[1..].into());
+                let from_bounds = bounds.iter().any(|bound| {
+                    bound.trait_def_id == goal.trait_def_id
+                        && match_substs(&bound.substs, &trait_substs, &mut [])
+                });
+                if from_bounds {
+                    return Ok(Some(Implementation::Bound));
+                }
+            }
+            _ => {}
+        }
+        // by the orphan rules, the impl is in the crate of the trait or of the type
+        let mut krates = vec![goal.trait_def_id.loc(db).krate];
+        if let Ty::Adt { def_id, .. } = goal.self_ty() {
+            let krate = def_id.loc(db).krate;
+            if !krates.contains(&krate) {
+                krates.push(krate);
+            }
+        }
+        for krate in krates {
+            let impls = db.impls_in_crate(krate)?;
+            for impl_block in impls.lookup_impl_blocks_for_trait(goal.trait_def_id) {
+                if let Some(substs) = self.match_impl(impl_block, goal)? {
+                    return Ok(Some(Implementation::Impl(impl_block.clone(), substs)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether `impl_block` applies to `goal`, including its where
+    /// clauses, and returns the substitutions for its generic parameters if so.
+    fn match_impl(
+        &mut self,
+        impl_block: &ImplBlock,
+        goal: &TraitRef,
+    ) -> Cancelable<Option<Substs>> {
+        let db = self.db;
+        let module = impl_block.module(db)?;
+        let generics = impl_block.generic_params();
+        let trait_path = match impl_block.target_trait() {
+            Some(TypeRef::Path(path)) => path,
+            _ => return Ok(None),
+        };
+        let self_ty = Ty::from_hir(db, &module, generics, impl_block.target_type())?;
+        let impl_trait_ref =
+            ctry!(TraitRef::from_hir(db, &module, generics, self_ty, trait_path)?);
+        let mut substs = vec![Ty::Unknown; generics.params.len()];
+        if !match_substs(&impl_trait_ref.substs, &goal.substs, &mut substs) {
+            return Ok(None);
+        }
+        let substs = Substs(substs.into());
+        for pred in lower_predicates(db, &module, generics)? {
+            let pred = pred.subst(&substs);
+            // we can't say anything about parameters we couldn't infer
+            if *pred.self_ty() == Ty::Unknown {
+                continue;
+            }
+            if self.solve(&pred)?.is_none() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(substs))
+    }
+}
+
+fn match_substs(patterns: &Substs, tys: &Substs, substs: &mut [Ty]) -> bool {
+    patterns.0.len() == tys.0.len()
+        && patterns
+            .0
+            .iter()
+            .zip(tys.0.iter())
+            .all(|(pattern, ty)| match_ty(pattern, ty, substs))
+}
+
+/// Matches `ty` against `pattern`, which may contain the type parameters
+/// `substs` is for; their values are recorded in `substs`. Unknown types and
+/// type variables match anything.
+fn match_ty(pattern: &Ty, ty: &Ty, substs: &mut [Ty]) -> bool {
+    match (pattern, ty) {
+        (Ty::Param { idx, .. }, _) if (*idx as usize) < substs.len() => {
+            let idx = *idx as usize;
+            if substs[idx] == Ty::Unknown {
+                substs[idx] = ty.clone();
+                true
+            } else {
+                let bound = substs[idx].clone();
+                match_ty(&bound, ty, &mut [])
+            }
+        }
+        (Ty::Unknown, _) | (_, Ty::Unknown) | (Ty::Infer(_), _) | (_, Ty::Infer(_)) => true,
+        (
+            Ty::Adt {
+                def_id: def_id1,
+                substs: substs1,
+                ..
+            },
+            Ty::Adt {
+                def_id: def_id2,
+                substs: substs2,
+                ..
+            },
+        ) => def_id1 == def_id2 && match_substs(substs1, substs2, substs),
+        (Ty::Ref(t1, m1), Ty::Ref(t2, m2)) | (Ty::RawPtr(t1, m1), Ty::RawPtr(t2, m2)) => {
+            m1 == m2 && match_ty(t1, t2, substs)
+        }
+        (Ty::Slice(t1), Ty::Slice(t2)) => match_ty(t1, t2, substs),
+        (Ty::Tuple(ts1), Ty::Tuple(ts2)) => {
+            ts1.len() == ts2.len()
+                && ts1
+                    .iter()
+                    .zip(ts2.iter())
+                    .all(|(t1, t2)| match_ty(t1, t2, substs))
+        }
+        _ => pattern == ty,
+    }
+}
+
+/// Returns the first trait impl for `ty` whose trait satisfies `is_trait`,
+/// together with the substitutions for the impl's generic parameters. Only
+/// impls for the type itself are found, not blanket impls.
+fn find_trait_impl(
+    db: &impl HirDatabase,
+    ty: &Ty,
+    mut is_trait: impl FnMut(Trait) -> Cancelable<bool>,
+) -> Cancelable<Option<(ImplBlock, Substs)>> {
+    let krate = match ty {
+        Ty::Adt { def_id, .. } => def_id.loc(db).krate,
+        _ => return Ok(None),
+    };
+    let impls = db.impls_in_crate(krate)?;
+    for impl_block in impls.lookup_trait_impl_blocks(ty) {
+        let module = impl_block.module(db)?;
+        let trait_path = match impl_block.target_trait() {
+            Some(TypeRef::Path(path)) => path,
+            _ => continue,
+        };
+        let trait_def = match module.resolve_path(db, trait_path)?.take_types() {
+            Some(def_id) => match def_id.resolve(db)? {
+                Def::Trait(t) => t,
+                _ => continue,
+            },
+            None => continue,
+        };
+        // TODO: check the generic arguments of the trait as well
+        if !is_trait(trait_def)? {
+            continue;
+        }
+        let generics = impl_block.generic_params();
+        let impl_ty = Ty::from_hir(db, &module, generics, impl_block.target_type())?;
+        let substs = impl_substs(&impl_ty, ty, generics);
+        return Ok(Some((impl_block.clone(), substs)));
+    }
+    Ok(None)
+}
+
+/// Returns the value of the associated type `name` in `impl_block`, with the
+/// impl's generic parameters substituted by `substs`.
+fn associated_type_in_impl(
+    db: &impl HirDatabase,
+    impl_block: &ImplBlock,
+    substs: &Substs,
+    name: &Name,
+) -> Cancelable<Option<Ty>> {
+    let type_ref = impl_block.items().iter().find_map(|item| match item {
+        ImplItem::Type {
+            name: item_name,
+            type_ref,
+        } if item_name == name => Some(type_ref),
+        _ => None,
+    });
+    let type_ref = ctry!(type_ref);
+    let module = impl_block.module(db)?;
+    let ty = Ty::from_hir(db, &module, impl_block.generic_params(), type_ref)?;
+    Ok(Some(ty.subst(substs)))
+}
+
+/// Normalizes `<T as Trait>::Name` to the type given in the impl of `Trait`
+/// for `T`, if we can find one.
+pub(crate) fn normalize_projection(
+    db: &impl HirDatabase,
+    proj: &ProjectionTy,
+) -> Cancelable<Option<Ty>> {
+    match proj.self_ty() {
+        Ty::Dynamic(bounds) | Ty::Opaque(bounds) => {
+            let assoc_ty = bounds
+                .iter()
+                .filter(|bound| bound.trait_def_id == proj.trait_def_id)
+                .find_map(|bound| bound.assoc_type(&proj.name));
+            return Ok(assoc_ty.cloned());
+        }
+        _ => {}
+    }
+    let trait_ref = TraitRef {
+        trait_def_id: proj.trait_def_id,
+        substs: proj.substs.clone(),
+    };
+    match ctry!(db.implements(trait_ref)?) {
+        Implementation::Impl(impl_block, substs) => {
+            associated_type_in_impl(db, &impl_block, &substs, &proj.name)
+        }
+        Implementation::Bound => Ok(None),
+    }
+}
+
+/// Returns `<T as Trait>::Item` for the first of the traits `traits`
+/// implemented by `ty`.
+// TODO: use lang items instead of matching on the trait's name
+fn known_associated_type(
+    db: &impl HirDatabase,
+    ty: &Ty,
+    traits: &[KnownName],
+    name: KnownName,
+) -> Cancelable<Option<Ty>> {
+    match ty {
+        Ty::Dynamic(bounds) | Ty::Opaque(bounds) => {
+            for &known_trait in traits {
+                let assoc_ty = bounds
+                    .iter()
+                    .filter(|bound| bound.trait_name.as_known_name() == Some(known_trait))
+                    .flat_map(|bound| bound.assoc_types.iter())
+                    .find(|(n, _)| n.as_known_name() == Some(name));
+                if let Some((_, assoc_ty)) = assoc_ty {
+                    return Ok(Some(assoc_ty.clone()));
+                }
+            }
+            return Ok(None);
+        }
+        _ => {}
+    }
+    for &known_trait in traits {
+        let impl_and_substs = find_trait_impl(db, ty, |t| {
+            Ok(t.name(db)?.and_then(|n| n.as_known_name()) == Some(known_trait))
+        })?;
+        if let Some((impl_block, substs)) = impl_and_substs {
+            let item = impl_block.items().iter().find_map(|item| match item {
+                ImplItem::Type { name: item_name, .. }
+                    if item_name.as_known_name() == Some(name) =>
+                {
+                    Some(item_name.clone())
+                }
+                _ => None,
+            });
+            if let Some(item) = item {
+                return associated_type_in_impl(db, &impl_block, &substs, &item);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The type of the elements produced by iterating over `ty` in a `for` loop,
+/// i.e. `<T as IntoIterator>::Item`. Since the blanket impl for iterators is
+/// not found by name, this falls back to `<T as Iterator>::Item`.
+pub(crate) fn iterator_item_ty(db: &impl HirDatabase, ty: &Ty) -> Cancelable<Option<Ty>> {
+    known_associated_type(
+        db,
+        ty,
+        &[KnownName::IntoIterator, KnownName::Iterator],
+        KnownName::Item,
+    )
+}
+
+impl Ty {
+    /// Whether `self` implements `Clone`, which is looked up among the traits
+    /// in scope in `module`.
+    pub fn is_clone(&self, db: &impl HirDatabase, module: &Module) -> Cancelable<bool> {
+        for trait_def in module.traits_in_scope(db)? {
+            if trait_def.name(db)?.and_then(|n| n.as_known_name()) == Some(KnownName::Clone) {
+                let trait_ref = TraitRef::for_trait(db, trait_def, self.clone());
+                return Ok(db.implements(trait_ref)?.is_some());
+            }
+        }
+        Ok(false)
+    }
+
+    /// Replaces all projections in `self` whose self type is known by the
+    /// types given in the corresponding impls.
+    pub(crate) fn normalize(self, db: &impl HirDatabase) -> Cancelable<Ty> {
+        let mut result = Ok(());
+        let ty = self.fold(&mut |ty| match ty {
+            Ty::Projection(proj) => match normalize_projection(db, &proj) {
+                Ok(Some(normalized)) => normalized,
+                Ok(None) => Ty::Projection(proj),
+                Err(e) => {
+                    result = Err(e);
+                    Ty::Unknown
+                }
+            },
+            ty => ty,
+        });
+        result?;
+        Ok(ty)
+    }
+}
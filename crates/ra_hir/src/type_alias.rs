@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use ra_syntax::ast::{self, NameOwner};
+
+use crate::{
+    DefId, Cancelable, Name, AsName,
+    db::HirDatabase,
+    type_ref::TypeRef,
+    generics::GenericParams,
+};
+
+/// A type alias, e.g. `type Result<T> = std::result::Result<T, Error>;`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type {
+    def_id: DefId,
+}
+
+impl Type {
+    pub(crate) fn new(def_id: DefId) -> Self {
+        Type { def_id }
+    }
+
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn type_alias_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<TypeAliasData>> {
+        db.type_alias_data(self.def_id)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.type_alias_data(self.def_id)?.name.clone())
+    }
+
+    pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
+        db.generic_params(self.def_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeAliasData {
+    name: Option<Name>,
+    type_ref: TypeRef,
+}
+
+impl TypeAliasData {
+    pub(crate) fn new(type_def: ast::TypeDef) -> TypeAliasData {
+        let name = type_def.name().map(|n| n.as_name());
+        let type_ref = TypeRef::from_ast_opt(type_def.type_ref());
+        TypeAliasData { name, type_ref }
+    }
+
+    pub fn name(&self) -> Option<&Name> {
+        self.name.as_ref()
+    }
+
+    /// The aliased type, i.e. the right hand side of the `=`.
+    pub fn type_ref(&self) -> &TypeRef {
+        &self.type_ref
+    }
+}
@@ -3,7 +3,7 @@
 
 use ra_syntax::ast;
 
-use crate::Path;
+use crate::{Path, Name, AsName, ConstExpr, path::GenericArg};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Mutability {
@@ -44,13 +44,21 @@ pub enum TypeRef {
     Path(Path),
     RawPtr(Box<TypeRef>, Mutability),
     Reference(Box<TypeRef>, Mutability),
-    Array(Box<TypeRef> /*, Expr*/),
+    Array(Box<TypeRef>, ConstExpr),
     Slice(Box<TypeRef>),
     /// A fn pointer. Last element of the vector is the return type.
     Fn(Vec<TypeRef>),
+    /// A qualified path to an associated type, e.g. `<T as Trait>::Item`.
+    Projection {
+        self_type: Box<TypeRef>,
+        trait_path: Path,
+        name: Name,
+    },
+    /// An `impl Trait` type, given by the paths of its trait bounds.
+    ImplTrait(Vec<Path>),
+    /// A trait object type `dyn Trait`, given by the paths of its trait bounds.
+    DynTrait(Vec<Path>),
     // For
-    // ImplTrait,
-    // DynTrait,
     Error,
 }
 
@@ -64,15 +72,17 @@ impl TypeRef {
             NeverType(..) => TypeRef::Never,
             PathType(inner) => inner
                 .path()
-                .and_then(Path::from_ast)
-                .map(TypeRef::Path)
+                .and_then(TypeRef::from_ast_path)
                 .unwrap_or(TypeRef::Error),
             PointerType(inner) => {
                 let inner_ty = TypeRef::from_ast_opt(inner.type_ref());
                 let mutability = Mutability::from_mutable(inner.is_mut());
                 TypeRef::RawPtr(Box::new(inner_ty), mutability)
             }
-            ArrayType(inner) => TypeRef::Array(Box::new(TypeRef::from_ast_opt(inner.type_ref()))),
+            ArrayType(inner) => TypeRef::Array(
+                Box::new(TypeRef::from_ast_opt(inner.type_ref())),
+                ConstExpr::from_ast_opt(inner.expr()),
+            ),
             SliceType(inner) => TypeRef::Slice(Box::new(TypeRef::from_ast_opt(inner.type_ref()))),
             ReferenceType(inner) => {
                 let inner_ty = TypeRef::from_ast_opt(inner.type_ref());
@@ -95,11 +105,116 @@ impl TypeRef {
             }
             // for types are close enough for our purposes to the inner type for now...
             ForType(inner) => TypeRef::from_ast_opt(inner.type_ref()),
-            ImplTraitType(_inner) => TypeRef::Error,
-            DynTraitType(_inner) => TypeRef::Error,
+            ImplTraitType(inner) => TypeRef::ImplTrait(type_bound_paths(inner.bounds())),
+            DynTraitType(inner) => TypeRef::DynTrait(type_bound_paths(inner.bounds())),
         }
     }
 
+    fn from_ast_path(path: ast::Path) -> Option<TypeRef> {
+        let qualified = path
+            .qualifier()
+            .and_then(|q| q.segment())
+            .and_then(|s| s.qualified_type());
+        match qualified {
+            Some((self_type, trait_type)) => {
+                let trait_path = Path::from_ast(trait_type?.path()?)?;
+                let name = path.segment()?.name_ref()?.as_name();
+                Some(TypeRef::Projection {
+                    self_type: Box::new(TypeRef::from_ast(self_type)),
+                    trait_path,
+                    name,
+                })
+            }
+            None => Path::from_ast(path).map(TypeRef::Path),
+        }
+    }
+
+    /// Replaces `Self` by `self_type`, e.g. in the signature of a method.
+    pub(crate) fn replace_self_type(&self, self_type: &TypeRef) -> TypeRef {
+        let replace = |type_ref: &TypeRef| type_ref.replace_self_type(self_type);
+        let replace_all = |type_refs: &[TypeRef]| type_refs.iter().map(replace).collect();
+        let replace_paths = |paths: &[Path]| {
+            paths
+                .iter()
+                .map(|path| path.replace_self_type_in_args(self_type))
+                .collect()
+        };
+        match self {
+            TypeRef::Path(path) if path.is_self_type() => self_type.clone(),
+            TypeRef::Path(path) => TypeRef::Path(path.replace_self_type_in_args(self_type)),
+            TypeRef::Tuple(inner) => TypeRef::Tuple(replace_all(inner)),
+            TypeRef::Fn(inner) => TypeRef::Fn(replace_all(inner)),
+            TypeRef::RawPtr(inner, mutability) => {
+                TypeRef::RawPtr(Box::new(replace(inner)), *mutability)
+            }
+            TypeRef::Reference(inner, mutability) => {
+                TypeRef::Reference(Box::new(replace(inner)), *mutability)
+            }
+            TypeRef::Array(inner, len) => TypeRef::Array(Box::new(replace(inner)), len.clone()),
+            TypeRef::Slice(inner) => TypeRef::Slice(Box::new(replace(inner))),
+            TypeRef::Projection {
+                self_type: inner,
+                trait_path,
+                name,
+            } => TypeRef::Projection {
+                self_type: Box::new(replace(inner)),
+                trait_path: trait_path.replace_self_type_in_args(self_type),
+                name: name.clone(),
+            },
+            TypeRef::ImplTrait(paths) => TypeRef::ImplTrait(replace_paths(paths)),
+            TypeRef::DynTrait(paths) => TypeRef::DynTrait(replace_paths(paths)),
+            TypeRef::Never | TypeRef::Placeholder | TypeRef::Error => self.clone(),
+        }
+    }
+
+    /// Calls `f` on all paths in `self`, including those in generic
+    /// arguments.
+    pub(crate) fn walk_paths(&self, f: &mut impl FnMut(&Path)) {
+        fn walk_path(path: &Path, f: &mut impl FnMut(&Path)) {
+            f(path);
+            let args = path
+                .segments
+                .iter()
+                .filter_map(|segment| segment.args_and_bindings.as_ref());
+            for args in args {
+                for arg in args.args.iter() {
+                    match arg {
+                        GenericArg::Type(type_ref) => type_ref.walk_paths(f),
+                    }
+                }
+                for (_, type_ref) in args.bindings.iter() {
+                    type_ref.walk_paths(f);
+                }
+            }
+        }
+        match self {
+            TypeRef::Path(path) => walk_path(path, f),
+            TypeRef::Tuple(inner) | TypeRef::Fn(inner) => {
+                inner.iter().for_each(|it| it.walk_paths(f))
+            }
+            TypeRef::RawPtr(inner, _)
+            | TypeRef::Reference(inner, _)
+            | TypeRef::Array(inner, _)
+            | TypeRef::Slice(inner) => inner.walk_paths(f),
+            TypeRef::Projection {
+                self_type,
+                trait_path,
+                ..
+            } => {
+                self_type.walk_paths(f);
+                walk_path(trait_path, f);
+            }
+            TypeRef::ImplTrait(paths) | TypeRef::DynTrait(paths) => {
+                paths.iter().for_each(|it| walk_path(it, f))
+            }
+            TypeRef::Never | TypeRef::Placeholder | TypeRef::Error => {}
+        }
+    }
+
+    pub(crate) fn unit() -> TypeRef {
+        TypeRef::Tuple(Vec::new())
+    }
+
     pub(crate) fn from_ast_opt(node: Option<ast::TypeRef>) -> Self {
         if let Some(node) = node {
             TypeRef::from_ast(node)
@@ -108,3 +223,11 @@ impl TypeRef {
         }
     }
 }
+
+fn type_bound_paths(bounds: Vec<ast::PathType>) -> Vec<Path> {
+    bounds
+        .into_iter()
+        .filter_map(|bound| bound.path())
+        .filter_map(Path::from_ast)
+        .collect()
+}
@@ -34,14 +34,12 @@ pub fn handle_extend_selection(
 ) -> Result<req::ExtendSelectionResult> {
     let file_id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(file_id);
-    let selections = params
-        .selections
-        .into_iter()
-        .map_conv_with(&line_index)
-        .map(|range| FileRange { file_id, range })
-        .map(|frange| world.analysis().extend_selection(frange))
-        .map_conv_with(&line_index)
-        .collect();
+    let mut selections = Vec::new();
+    for range in params.selections.into_iter().map_conv_with(&line_index) {
+        let frange = FileRange { file_id, range };
+        let range = world.analysis().extend_selection(frange)?;
+        selections.push(range.conv_with(&line_index));
+    }
     Ok(req::ExtendSelectionResult { selections })
 }
 
@@ -330,6 +328,7 @@ pub fn handle_runnables(
             let file_id = world.analysis().crate_root(crate_id)?;
             let path = world.vfs.read().file2path(ra_vfs::VfsFile(file_id.0));
             let res = world.workspaces.iter().find_map(|ws| {
+                let ws = &ws.cargo;
                 let tgt = ws.target_by_root(&path)?;
                 let res = CargoTargetSpec {
                     package: tgt.package(ws).name(ws).to_string(),
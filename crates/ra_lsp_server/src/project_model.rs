@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use cargo_metadata::{metadata_run, CargoOpt};
+use ra_analysis::CfgOptions;
 use ra_syntax::SmolStr;
 use rustc_hash::{FxHashMap, FxHashSet};
 use failure::{format_err, bail};
@@ -8,6 +9,44 @@ use thread_worker::{WorkerHandle, Worker};
 
 use crate::Result;
 
+pub use self::sysroot::Sysroot;
+
+mod sysroot;
+mod target_cfg;
+
+/// `ProjectWorkspace` is everything we need to know about a project to build
+/// a `CrateGraph` for it: the cargo packages and the standard library.
+#[derive(Debug, Clone)]
+pub struct ProjectWorkspace {
+    pub cargo: CargoWorkspace,
+    pub sysroot: Sysroot,
+    /// The cfg flags of the target the workspace is compiled for, like
+    /// `unix` or `target_os = "linux"`.
+    pub target_cfg: CfgOptions,
+}
+
+impl ProjectWorkspace {
+    pub fn discover(path: &Path) -> Result<ProjectWorkspace> {
+        let cargo_toml = find_cargo_toml(path)?;
+        let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml)?;
+        // A missing standard library degrades the analysis, but it shouldn't
+        // prevent us from loading the workspace.
+        let sysroot = Sysroot::discover(&cargo_toml).unwrap_or_else(|e| {
+            log::warn!("loading sysroot failed: {}", e);
+            Sysroot::default()
+        });
+        let target_cfg = target_cfg::discover(&cargo_toml).unwrap_or_else(|e| {
+            log::warn!("querying target cfg failed, assuming the host: {}", e);
+            target_cfg::host()
+        });
+        Ok(ProjectWorkspace {
+            cargo,
+            sysroot,
+            target_cfg,
+        })
+    }
+}
+
 /// `CargoWorksapce` represents the logical structure of, well, a Cargo
 /// workspace. It pretty closely mirrors `cargo metadata` output.
 ///
@@ -33,6 +72,7 @@ struct PackageData {
     targets: Vec<Target>,
     is_member: bool,
     dependencies: Vec<PackageDependency>,
+    features: Vec<SmolStr>,
 }
 
 #[derive(Debug, Clone)]
@@ -69,10 +109,14 @@ impl Package {
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.pkg(self).targets.iter().cloned()
     }
-    #[allow(unused)]
     pub fn is_member(self, ws: &CargoWorkspace) -> bool {
         ws.pkg(self).is_member
     }
+    /// Features enabled for this package. As we run `cargo metadata` with
+    /// `--all-features`, these are all of the declared features.
+    pub fn features(self, ws: &CargoWorkspace) -> &[SmolStr] {
+        &ws.pkg(self).features
+    }
     pub fn dependencies<'a>(
         self,
         ws: &'a CargoWorkspace,
@@ -125,6 +169,7 @@ impl CargoWorkspace {
                 targets: Vec::new(),
                 is_member,
                 dependencies: Vec::new(),
+                features: meta_pkg.features.keys().map(|it| it.as_str().into()).collect(),
             };
             for meta_tgt in meta_pkg.targets {
                 let tgt = Target(targets.len());
@@ -198,13 +243,13 @@ impl TargetKind {
     }
 }
 
-pub fn workspace_loader() -> (Worker<PathBuf, Result<CargoWorkspace>>, WorkerHandle) {
-    thread_worker::spawn::<PathBuf, Result<CargoWorkspace>, _>(
+pub fn workspace_loader() -> (Worker<PathBuf, Result<ProjectWorkspace>>, WorkerHandle) {
+    thread_worker::spawn::<PathBuf, Result<ProjectWorkspace>, _>(
         "workspace loader",
         1,
         |input_receiver, output_sender| {
             input_receiver
-                .map(|path| CargoWorkspace::from_cargo_metadata(path.as_path()))
+                .map(|path| ProjectWorkspace::discover(path.as_path()))
                 .for_each(|it| output_sender.send(it))
         },
     )
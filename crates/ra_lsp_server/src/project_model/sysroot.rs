@@ -0,0 +1,126 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ra_syntax::SmolStr;
+use failure::{format_err, bail};
+
+use crate::Result;
+
+/// `Sysroot` describes the crates of the standard library, as found in the
+/// `rust-src` component of the toolchain used for the workspace.
+#[derive(Debug, Clone, Default)]
+pub struct Sysroot {
+    crates: Vec<SysrootCrateData>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SysrootCrate(usize);
+
+#[derive(Debug, Clone)]
+struct SysrootCrateData {
+    name: SmolStr,
+    root: PathBuf,
+    deps: Vec<SysrootCrate>,
+}
+
+const SYSROOT_CRATES: &[&str] = &["core", "alloc", "std"];
+
+const SYSROOT_DEPS: &[(&str, &[&str])] = &[("alloc", &["core"]), ("std", &["alloc", "core"])];
+
+impl Sysroot {
+    pub fn std(&self) -> Option<SysrootCrate> {
+        self.by_name("std")
+    }
+
+    pub fn core(&self) -> Option<SysrootCrate> {
+        self.by_name("core")
+    }
+
+    pub fn crates<'a>(&'a self) -> impl Iterator<Item = SysrootCrate> + 'a {
+        (0..self.crates.len()).map(SysrootCrate)
+    }
+
+    pub fn discover(cargo_toml: &Path) -> Result<Sysroot> {
+        let src = find_src_dir(cargo_toml)?;
+
+        let mut sysroot = Sysroot { crates: Vec::new() };
+        for &name in SYSROOT_CRATES {
+            let candidates = [
+                src.join(format!("lib{}", name)).join("lib.rs"),
+                src.join(name).join("src").join("lib.rs"),
+            ];
+            if let Some(root) = candidates.iter().find(|it| it.exists()) {
+                sysroot.crates.push(SysrootCrateData {
+                    name: name.into(),
+                    root: root.clone(),
+                    deps: Vec::new(),
+                });
+            }
+        }
+        for &(from, deps) in SYSROOT_DEPS {
+            if let Some(from) = sysroot.by_name(from) {
+                for &dep in deps {
+                    if let Some(to) = sysroot.by_name(dep) {
+                        sysroot.crates[from.0].deps.push(to);
+                    }
+                }
+            }
+        }
+        if sysroot.core().is_none() {
+            bail!(
+                "can't load standard library from {}, try `rustup component add rust-src`",
+                src.display()
+            );
+        }
+        Ok(sysroot)
+    }
+
+    fn by_name(&self, name: &str) -> Option<SysrootCrate> {
+        self.crates().find(|it| it.name(self) == name)
+    }
+}
+
+impl SysrootCrate {
+    pub fn name(self, sysroot: &Sysroot) -> &SmolStr {
+        &sysroot.crates[self.0].name
+    }
+    pub fn root(self, sysroot: &Sysroot) -> &Path {
+        sysroot.crates[self.0].root.as_path()
+    }
+    pub fn root_dir(self, sysroot: &Sysroot) -> &Path {
+        self.root(sysroot).parent().unwrap()
+    }
+    pub fn deps<'a>(self, sysroot: &'a Sysroot) -> impl Iterator<Item = SysrootCrate> + 'a {
+        sysroot.crates[self.0].deps.iter().cloned()
+    }
+}
+
+/// Finds the sources of the standard library. `RUST_SRC_PATH` takes
+/// precedence over the `rust-src` component of the toolchain `rustc` resolves
+/// to in the workspace directory (which respects `rust-toolchain` overrides).
+fn find_src_dir(cargo_toml: &Path) -> Result<PathBuf> {
+    if let Ok(path) = env::var("RUST_SRC_PATH") {
+        return Ok(PathBuf::from(path));
+    }
+    let output = Command::new("rustc")
+        .current_dir(cargo_toml.parent().unwrap())
+        .args(&["--print", "sysroot"])
+        .output()?;
+    if !output.status.success() {
+        bail!("failed to locate sysroot");
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    let rust_src = Path::new(stdout.trim())
+        .join("lib")
+        .join("rustlib")
+        .join("src")
+        .join("rust");
+    ["library", "src"]
+        .iter()
+        .map(|it| rust_src.join(it))
+        .find(|it| it.exists())
+        .ok_or_else(|| format_err!("can't find rust-src in {}", rust_src.display()))
+}
@@ -0,0 +1,64 @@
+use std::{env::consts, path::Path, process::Command};
+
+use ra_analysis::CfgOptions;
+use failure::bail;
+
+use crate::Result;
+
+/// Asks the `rustc` used for the workspace (which respects `rust-toolchain`
+/// overrides) for the cfg flags of its default target.
+pub(super) fn discover(cargo_toml: &Path) -> Result<CfgOptions> {
+    let output = Command::new("rustc")
+        .current_dir(cargo_toml.parent().unwrap())
+        .args(&["--print", "cfg"])
+        .output()?;
+    if !output.status.success() {
+        bail!("rustc --print cfg failed");
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    let mut cfg = CfgOptions::default();
+    for line in stdout.lines() {
+        let line = line.trim();
+        match line.find('=') {
+            None => cfg.insert_atom(line.into()),
+            Some(idx) => {
+                let key = line[..idx].trim();
+                let value = line[idx + 1..].trim().trim_matches('"');
+                cfg.insert_key_value(key.into(), value.into());
+            }
+        }
+    }
+    add_profile_cfg(&mut cfg);
+    Ok(cfg)
+}
+
+/// The cfg flags of the host, for when `rustc` can't be queried.
+pub(super) fn host() -> CfgOptions {
+    let mut cfg = CfgOptions::default();
+    cfg.insert_key_value("target_os".into(), consts::OS.into());
+    cfg.insert_key_value("target_arch".into(), consts::ARCH.into());
+    if !consts::FAMILY.is_empty() {
+        cfg.insert_atom(consts::FAMILY.into());
+        cfg.insert_key_value("target_family".into(), consts::FAMILY.into());
+    }
+    let pointer_width = if cfg!(target_pointer_width = "64") {
+        "64"
+    } else {
+        "32"
+    };
+    cfg.insert_key_value("target_pointer_width".into(), pointer_width.into());
+    let endian = if cfg!(target_endian = "little") {
+        "little"
+    } else {
+        "big"
+    };
+    cfg.insert_key_value("target_endian".into(), endian.into());
+    add_profile_cfg(&mut cfg);
+    cfg
+}
+
+/// `rustc --print cfg` doesn't include the flags cargo passes for the `dev`
+/// profile, which is what the workspace is usually built with.
+fn add_profile_cfg(cfg: &mut CfgOptions) {
+    cfg.insert_atom("debug_assertions".into());
+}
@@ -15,7 +15,7 @@ use parking_lot::RwLock;
 use failure::{format_err};
 
 use crate::{
-    project_model::{CargoWorkspace, TargetKind},
+    project_model::{ProjectWorkspace, TargetKind},
     Result,
 };
 
@@ -23,26 +23,29 @@ use crate::{
 pub struct ServerWorldState {
     pub roots_to_scan: usize,
     pub root: PathBuf,
-    pub workspaces: Arc<Vec<CargoWorkspace>>,
+    pub workspaces: Arc<Vec<ProjectWorkspace>>,
     pub analysis_host: AnalysisHost,
     pub vfs: Arc<RwLock<Vfs>>,
 }
 
 pub struct ServerWorld {
-    pub workspaces: Arc<Vec<CargoWorkspace>>,
+    pub workspaces: Arc<Vec<ProjectWorkspace>>,
     pub analysis: Analysis,
     pub vfs: Arc<RwLock<Vfs>>,
 }
 
 impl ServerWorldState {
-    pub fn new(root: PathBuf, workspaces: Vec<CargoWorkspace>) -> ServerWorldState {
+    pub fn new(root: PathBuf, workspaces: Vec<ProjectWorkspace>) -> ServerWorldState {
         let mut change = AnalysisChange::new();
 
         let mut roots = Vec::new();
         roots.push(root.clone());
         for ws in workspaces.iter() {
-            for pkg in ws.packages() {
-                roots.push(pkg.root(&ws).to_path_buf());
+            for pkg in ws.cargo.packages() {
+                roots.push(pkg.root(&ws.cargo).to_path_buf());
+            }
+            for krate in ws.sysroot.crates() {
+                roots.push(krate.root_dir(&ws.sysroot).to_path_buf())
             }
         }
         let roots_to_scan = roots.len();
@@ -56,15 +59,53 @@ impl ServerWorldState {
         let mut pkg_to_lib_crate = FxHashMap::default();
         let mut pkg_crates = FxHashMap::default();
         for ws in workspaces.iter() {
+            let mut sysroot_crates = FxHashMap::default();
+            for krate in ws.sysroot.crates() {
+                if let Some(file_id) = vfs.load(krate.root(&ws.sysroot)) {
+                    let file_id = FileId(file_id.0);
+                    let crate_id = crate_graph.add_crate_root(file_id, ws.target_cfg.clone());
+                    sysroot_crates.insert(krate, crate_id);
+                }
+            }
+            for from in ws.sysroot.crates() {
+                for to in from.deps(&ws.sysroot) {
+                    let name = to.name(&ws.sysroot);
+                    if let (Some(&from), Some(&to)) =
+                        (sysroot_crates.get(&from), sysroot_crates.get(&to))
+                    {
+                        crate_graph.add_dep(from, name.clone(), to);
+                    }
+                }
+            }
+            let libstd = ws.sysroot.std().and_then(|it| sysroot_crates.get(&it).cloned());
+            let libcore = ws.sysroot.core().and_then(|it| sysroot_crates.get(&it).cloned());
+
+            let target_cfg = &ws.target_cfg;
+            let ws = &ws.cargo;
             for pkg in ws.packages() {
+                let mut cfg_options = target_cfg.clone();
+                // Analyze tests of the workspace members, but not of the
+                // dependencies.
+                if pkg.is_member(ws) {
+                    cfg_options.insert_atom("test".into());
+                }
+                for feature in pkg.features(ws) {
+                    cfg_options.insert_key_value("feature".into(), feature.clone());
+                }
                 for tgt in pkg.targets(ws) {
                     let root = tgt.root(ws);
                     if let Some(file_id) = vfs.load(root) {
                         let file_id = FileId(file_id.0);
-                        let crate_id = crate_graph.add_crate_root(file_id);
+                        let crate_id = crate_graph.add_crate_root(file_id, cfg_options.clone());
                         if tgt.kind(ws) == TargetKind::Lib {
                             pkg_to_lib_crate.insert(pkg, crate_id);
                         }
+                        if let Some(std) = libstd {
+                            crate_graph.add_dep(crate_id, "std".into(), std);
+                        }
+                        if let Some(core) = libcore {
+                            crate_graph.add_dep(crate_id, "core".into(), core);
+                        }
                         pkg_crates
                             .entry(pkg)
                             .or_insert_with(Vec::new)
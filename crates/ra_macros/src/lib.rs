@@ -0,0 +1,163 @@
+//! A `macro_rules!` by-example expander, working on token trees.
+//!
+//! The syntax of a macro definition and of a macro call is converted to
+//! `tt::Subtree`s. The definition is parsed into `MacroRules`, which can
+//! expand a call into a new token tree. Every token keeps the id it had in
+//! the input, so the expansion can be mapped back to the source.
+pub mod tt;
+mod mbe;
+mod mbe_parser;
+mod mbe_expander;
+mod tt_cursor;
+mod syntax_bridge;
+
+pub use crate::{
+    mbe::MacroRules,
+    syntax_bridge::{ast_to_token_tree, token_tree_to_text, TokenMap},
+};
+
+impl MacroRules {
+    /// Parses the body of a `macro_rules!` definition.
+    pub fn parse(tt: &tt::Subtree) -> Option<MacroRules> {
+        mbe_parser::parse(tt)
+    }
+
+    /// Expands the token tree of a macro call, using the first rule which
+    /// matches.
+    pub fn expand(&self, input: &tt::Subtree) -> Option<tt::Subtree> {
+        mbe_expander::expand(self, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::{ast, AstNode, SourceFileNode};
+
+    use super::*;
+
+    fn create_rules(macro_definition: &str) -> MacroRules {
+        let source_file = SourceFileNode::parse(macro_definition);
+        let macro_definition = source_file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (definition_tt, _) = ast_to_token_tree(macro_definition.token_tree().unwrap()).unwrap();
+        MacroRules::parse(&definition_tt).unwrap()
+    }
+
+    fn expand(rules: &MacroRules, invocation: &str) -> Option<tt::Subtree> {
+        let source_file = SourceFileNode::parse(invocation);
+        let macro_invocation = source_file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (invocation_tt, _) = ast_to_token_tree(macro_invocation.token_tree().unwrap()).unwrap();
+        rules.expand(&invocation_tt)
+    }
+
+    fn assert_expansion(rules: &MacroRules, invocation: &str, expansion: &str) {
+        let expanded = expand(rules, invocation).unwrap();
+        assert_eq!(expanded.to_string(), expansion);
+    }
+
+    #[test]
+    fn test_convert_tt() {
+        let source_file = SourceFileNode::parse("foo!(a => b::c, 'x 1.0 { /* c */ } [-]);");
+        let macro_call = source_file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (tt, token_map) = ast_to_token_tree(macro_call.token_tree().unwrap()).unwrap();
+        assert_eq!(tt.to_string(), "(a => b :: c , 'x 1.0 {} [-])");
+
+        let (text, ranges) = token_tree_to_text(&tt);
+        assert_eq!(text, tt.to_string());
+        for (id, range) in ranges {
+            let src_range = token_map.range_of(id).unwrap();
+            assert_eq!(
+                &text[range],
+                &source_file.syntax().text().to_string()[src_range]
+            );
+        }
+    }
+
+    #[test]
+    fn test_repetitions() {
+        let rules = create_rules(
+            r#"
+        macro_rules! impl_froms {
+            ($e:ident: $($v:ident),*) => {
+                $(
+                    impl From<$v> for $e {
+                        fn from(it: $v) -> $e {
+                            $e::$v(it)
+                        }
+                    }
+                )*
+            }
+        }
+        "#,
+        );
+        assert_expansion(
+            &rules,
+            "impl_froms!(TokenTree: Leaf, Subtree);",
+            "impl From < Leaf > for TokenTree {fn from (it : Leaf) -> TokenTree \
+             {TokenTree :: Leaf (it)}} \
+             impl From < Subtree > for TokenTree {fn from (it : Subtree) -> TokenTree \
+             {TokenTree :: Subtree (it)}}",
+        );
+        assert_expansion(&rules, "impl_froms!(TokenTree:);", "");
+    }
+
+    #[test]
+    fn test_nested_repetitions_and_separators() {
+        let rules = create_rules(
+            r#"
+        macro_rules! m {
+            ($($name:ident => [$($field:ident)+]);*) => {
+                $(struct $name { $($field: u32),* })*
+            };
+            ($($i:literal)?) => { $($i)? };
+        }
+        "#,
+        );
+        assert_expansion(
+            &rules,
+            "m!(A => [a b]; B => [c])",
+            "struct A {a : u32 , b : u32} struct B {c : u32}",
+        );
+        assert_expansion(&rules, "m!(92)", "92");
+        assert_expansion(&rules, "m!()", "");
+        assert!(expand(&rules, "m!(A => [])").is_none());
+    }
+
+    #[test]
+    fn test_fragments() {
+        let rules = create_rules(
+            r#"
+        macro_rules! m {
+            (expr $a:expr, $b:expr) => { $a * $b };
+            (ty $t:ty) => { let x: $t; };
+            (pat $p:pat = $e:expr) => { let $p = $e; };
+            (items $($i:item)*) => { $($i)* };
+            (tt $($t:tt)*) => { $($t)* };
+            ($v:vis $l:lifetime $b:block) => { $v fn f<$l>() $b };
+        }
+        "#,
+        );
+        assert_expansion(&rules, "m!(expr 1 + 1, foo(92))", "(1 + 1) * (foo (92))");
+        assert_expansion(&rules, "m!(ty Vec<u32>)", "let x : Vec < u32 > ;");
+        assert_expansion(&rules, "m!(pat (a, _) = (1, 2))", "let (a , _) = (1 , 2) ;");
+        assert_expansion(
+            &rules,
+            "m!(items fn foo() {} struct S;)",
+            "fn foo () {} struct S ;",
+        );
+        assert_expansion(&rules, "m!(tt a => b)", "a => b");
+        assert_expansion(&rules, "m!(pub(crate) 'a { 92 })", "pub (crate) fn f < 'a > () {92}");
+        assert_expansion(&rules, "m!('a {})", "fn f < 'a > () {}");
+    }
+}
@@ -0,0 +1,93 @@
+//! The representation of a parsed `macro_rules!` definition.
+//!
+//! It mirrors `tt`, but matchers and transcribers may also contain `$var`
+//! metavariables and `$(...) sep rep` repetitions.
+use ra_syntax::SmolStr;
+
+pub(crate) use crate::tt::{Delimiter, Literal, Punct, Ident};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MacroRules {
+    pub(crate) rules: Vec<Rule>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Rule {
+    pub(crate) lhs: Subtree,
+    pub(crate) rhs: Subtree,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TokenTree {
+    Leaf(Leaf),
+    Subtree(Subtree),
+    Repeat(Repeat),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Leaf {
+    Literal(Literal),
+    Punct(Punct),
+    Ident(Ident),
+    Var(Var),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Subtree {
+    pub(crate) delimiter: Delimiter,
+    pub(crate) token_trees: Vec<TokenTree>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Repeat {
+    pub(crate) subtree: Subtree,
+    pub(crate) kind: RepeatKind,
+    pub(crate) separator: Option<Separator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RepeatKind {
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+/// The separator of a repetition is a single token, which for operators like
+/// `=>` consists of several puncts.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Separator {
+    Ident(SmolStr),
+    Literal(SmolStr),
+    Puncts(Vec<char>),
+}
+
+/// A `$name` or, in matchers, `$name:kind` metavariable.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Var {
+    pub(crate) text: SmolStr,
+    pub(crate) kind: Option<SmolStr>,
+}
+
+impl From<Leaf> for TokenTree {
+    fn from(leaf: Leaf) -> TokenTree {
+        TokenTree::Leaf(leaf)
+    }
+}
+
+impl From<Var> for TokenTree {
+    fn from(var: Var) -> TokenTree {
+        TokenTree::Leaf(Leaf::Var(var))
+    }
+}
+
+impl From<Subtree> for TokenTree {
+    fn from(subtree: Subtree) -> TokenTree {
+        TokenTree::Subtree(subtree)
+    }
+}
+
+impl From<Repeat> for TokenTree {
+    fn from(repeat: Repeat) -> TokenTree {
+        TokenTree::Repeat(repeat)
+    }
+}
@@ -0,0 +1,476 @@
+use rustc_hash::FxHashMap;
+use ra_syntax::{ast, AstNode, SmolStr, SourceFileNode, SyntaxKind::*, TextUnit};
+
+use crate::{mbe, tt, tt_cursor::TtCursor};
+
+pub(crate) fn expand(rules: &mbe::MacroRules, input: &tt::Subtree) -> Option<tt::Subtree> {
+    rules.rules.iter().find_map(|it| expand_rule(it, input))
+}
+
+fn expand_rule(rule: &mbe::Rule, input: &tt::Subtree) -> Option<tt::Subtree> {
+    let mut input = TtCursor::new(input);
+    let bindings = match_lhs(&rule.lhs, &mut input)?;
+    if !input.is_eof() {
+        return None;
+    }
+    let mut res = expand_subtree(&rule.rhs, &bindings, &mut Vec::new())?;
+    // The delimiters of the transcriber are not part of the expansion.
+    res.delimiter = tt::Delimiter::None;
+    Some(res)
+}
+
+/// The fragments bound to metavariables by a successful match. Variables
+/// inside repetitions are bound to one fragment per iteration.
+#[derive(Debug, Default)]
+struct Bindings {
+    inner: FxHashMap<SmolStr, Binding>,
+}
+
+#[derive(Debug)]
+enum Binding {
+    Simple(tt::TokenTree),
+    Nested(Vec<Binding>),
+}
+
+impl Bindings {
+    fn get(&self, name: &SmolStr, nesting: &[usize]) -> Option<&tt::TokenTree> {
+        match self.walk(name, nesting)? {
+            Binding::Simple(it) => Some(it),
+            Binding::Nested(_) => None,
+        }
+    }
+
+    fn walk(&self, name: &SmolStr, nesting: &[usize]) -> Option<&Binding> {
+        let mut b = self.inner.get(name)?;
+        for &idx in nesting.iter() {
+            b = match b {
+                Binding::Simple(_) => break,
+                Binding::Nested(bs) => bs.get(idx)?,
+            };
+        }
+        Some(b)
+    }
+
+    fn push_nested(&mut self, nested: Bindings) {
+        for (key, value) in nested.inner {
+            let entry = self
+                .inner
+                .entry(key)
+                .or_insert_with(|| Binding::Nested(Vec::new()));
+            if let Binding::Nested(it) = entry {
+                it.push(value);
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Bindings) {
+        self.inner.extend(other.inner);
+    }
+}
+
+fn match_lhs(pattern: &mbe::Subtree, input: &mut TtCursor) -> Option<Bindings> {
+    let mut res = Bindings::default();
+    for pat in pattern.token_trees.iter() {
+        match pat {
+            mbe::TokenTree::Leaf(leaf) => match leaf {
+                mbe::Leaf::Var(mbe::Var { text, kind }) => {
+                    let kind = kind.as_ref()?;
+                    let fragment = match_fragment(kind, input)?;
+                    res.inner.insert(text.clone(), Binding::Simple(fragment));
+                }
+                mbe::Leaf::Punct(punct) => {
+                    if input.eat_punct()?.char != punct.char {
+                        return None;
+                    }
+                }
+                mbe::Leaf::Ident(ident) => {
+                    if input.eat_ident()?.text != ident.text {
+                        return None;
+                    }
+                }
+                mbe::Leaf::Literal(literal) => {
+                    if input.eat_literal()?.text != literal.text {
+                        return None;
+                    }
+                }
+            },
+            mbe::TokenTree::Subtree(subtree) => {
+                let input_subtree = input.eat_subtree()?;
+                if input_subtree.delimiter != subtree.delimiter {
+                    return None;
+                }
+                let mut input = TtCursor::new(input_subtree);
+                let bindings = match_lhs(subtree, &mut input)?;
+                if !input.is_eof() {
+                    return None;
+                }
+                res.merge(bindings);
+            }
+            mbe::TokenTree::Repeat(repeat) => {
+                let mut iterations = 0;
+                let mut nested = Bindings::default();
+                loop {
+                    let mut fork = input.clone();
+                    if iterations > 0 {
+                        if let Some(separator) = &repeat.separator {
+                            if !eat_separator(&mut fork, separator) {
+                                break;
+                            }
+                        }
+                    }
+                    let bindings = match match_lhs(&repeat.subtree, &mut fork) {
+                        Some(it) => it,
+                        None => break,
+                    };
+                    // Guard against repetitions which match nothing.
+                    if fork.pos() == input.pos() {
+                        break;
+                    }
+                    *input = fork;
+                    nested.push_nested(bindings);
+                    iterations += 1;
+                    if repeat.kind == mbe::RepeatKind::ZeroOrOne {
+                        break;
+                    }
+                }
+                if repeat.kind == mbe::RepeatKind::OneOrMore && iterations == 0 {
+                    return None;
+                }
+                // Variables of a repetition which matched zero times are still
+                // bound, to an empty sequence.
+                let mut vars = Vec::new();
+                collect_vars(&repeat.subtree, &mut vars);
+                for var in vars {
+                    nested
+                        .inner
+                        .entry(var)
+                        .or_insert_with(|| Binding::Nested(Vec::new()));
+                }
+                res.merge(nested);
+            }
+        }
+    }
+    Some(res)
+}
+
+fn eat_separator(input: &mut TtCursor, separator: &mbe::Separator) -> bool {
+    match separator {
+        mbe::Separator::Ident(text) => match input.eat_ident() {
+            Some(it) => it.text == *text,
+            None => false,
+        },
+        mbe::Separator::Literal(text) => match input.eat_literal() {
+            Some(it) => it.text == *text,
+            None => false,
+        },
+        mbe::Separator::Puncts(chars) => chars.iter().all(|&c| input.expect_char(c).is_some()),
+    }
+}
+
+fn match_fragment(kind: &str, input: &mut TtCursor) -> Option<tt::TokenTree> {
+    let res = match kind {
+        "ident" => {
+            let ident = input.at_ident()?;
+            if ident.text.starts_with('\'') {
+                return None;
+            }
+            input.eat()?.clone()
+        }
+        "lifetime" => {
+            let ident = input.at_ident()?;
+            if !ident.text.starts_with('\'') {
+                return None;
+            }
+            input.eat()?.clone()
+        }
+        "literal" => {
+            let mut len = 0;
+            let mut fork = input.clone();
+            if fork.at_char('-') {
+                fork.bump();
+                len += 1;
+            }
+            if fork.at_literal().is_none() {
+                let ident = fork.at_ident()?;
+                if ident.text != "true" && ident.text != "false" {
+                    return None;
+                }
+            }
+            take_tokens(input, len + 1)
+        }
+        "tt" => {
+            input.current()?;
+            // A multi-character operator is a single token tree.
+            let mut len = 1;
+            for tt in input.rest() {
+                match tt {
+                    tt::TokenTree::Leaf(tt::Leaf::Punct(p)) if p.spacing == tt::Spacing::Joint => {
+                        len += 1
+                    }
+                    _ => break,
+                }
+            }
+            take_tokens(input, len.min(input.rest().len()))
+        }
+        "block" => match input.current()? {
+            tt::TokenTree::Subtree(it) if it.delimiter == tt::Delimiter::Brace => {
+                input.eat()?.clone()
+            }
+            _ => return None,
+        },
+        "vis" => {
+            let mut len = 0;
+            if input.at_ident().filter(|it| it.text == "pub").is_some() {
+                len += 1;
+                if let Some(tt::TokenTree::Subtree(it)) = input.rest().get(1) {
+                    if it.delimiter == tt::Delimiter::Parenthesis {
+                        len += 1;
+                    }
+                }
+            }
+            take_tokens(input, len)
+        }
+        "expr" | "ty" | "path" | "pat" | "stmt" | "item" | "meta" => {
+            let len = longest_parsed_prefix(kind, input.rest())?;
+            let res = take_tokens(input, len);
+            // Like rustc, keep expressions atomic when they are substituted
+            // into a larger expression.
+            if kind == "expr" && len > 1 {
+                let token_trees = match res {
+                    tt::TokenTree::Subtree(it) => it.token_trees,
+                    _ => unreachable!(),
+                };
+                tt::Subtree {
+                    delimiter: tt::Delimiter::Parenthesis,
+                    token_trees,
+                }
+                .into()
+            } else {
+                res
+            }
+        }
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// Consumes `len` token trees. A single token tree is returned as is, any
+/// other number is wrapped into an invisible subtree.
+fn take_tokens(input: &mut TtCursor, len: usize) -> tt::TokenTree {
+    let token_trees = input.rest()[..len].to_vec();
+    input.bump_n(len);
+    if len == 1 {
+        return token_trees.into_iter().next().unwrap();
+    }
+    tt::Subtree {
+        delimiter: tt::Delimiter::None,
+        token_trees,
+    }
+    .into()
+}
+
+/// Finds the longest prefix of `tokens` which parses as a single fragment of
+/// the given kind. All of `tokens` is parsed once, and the fragment node the
+/// parser produced tells how many token trees it consumed.
+fn longest_parsed_prefix(kind: &str, tokens: &[tt::TokenTree]) -> Option<usize> {
+    let subtree = tt::Subtree {
+        delimiter: tt::Delimiter::None,
+        token_trees: tokens.to_vec(),
+    };
+    let fragment_end = parsed_fragment_end(kind, &subtree.to_string())?;
+    token_tree_ends(tokens)
+        .iter()
+        .position(|&end| end == fragment_end)
+        .map(|idx| idx + 1)
+}
+
+/// The offsets at which each of `tokens` ends in their rendering as a
+/// subtree without delimiters (see `Display` for `tt::Subtree`).
+fn token_tree_ends(tokens: &[tt::TokenTree]) -> Vec<TextUnit> {
+    let mut ends = Vec::with_capacity(tokens.len());
+    let mut offset = TextUnit::from(0);
+    let mut needs_space = false;
+    for tt in tokens {
+        if needs_space {
+            offset += TextUnit::of_char(' ');
+        }
+        needs_space = match tt {
+            tt::TokenTree::Leaf(tt::Leaf::Punct(p)) => p.spacing == tt::Spacing::Alone,
+            _ => true,
+        };
+        offset += TextUnit::of_str(&tt.to_string());
+        ends.push(offset);
+    }
+    ends
+}
+
+/// Parses `text` in a context where a fragment of the given kind is expected
+/// and returns where the parsed fragment ends, relative to `text`. There may
+/// be anything after the fragment, but no errors in it.
+fn parsed_fragment_end(kind: &str, text: &str) -> Option<TextUnit> {
+    let (prefix, suffix) = match kind {
+        "expr" => ("fn __() { let _ = ", "; }"),
+        "ty" | "path" => ("type __ = ", ";"),
+        "pat" => ("fn __() { let ", " = (); }"),
+        "stmt" => ("fn __() { ", "; }"),
+        "item" => ("", ""),
+        "meta" => ("#[", "] fn __() {}"),
+        _ => return None,
+    };
+    let file = SourceFileNode::parse(&format!("{}{}{}", prefix, text, suffix));
+    let start = TextUnit::of_str(prefix);
+    let first_error = file.errors().iter().map(|e| e.offset()).min();
+    let end = file
+        .syntax()
+        .descendants()
+        .filter(|node| node.range().start() == start)
+        .filter_map(|node| {
+            let is_fragment = match kind {
+                "expr" => ast::Expr::cast(node).is_some(),
+                "ty" => ast::TypeRef::cast(node).is_some(),
+                "path" => ast::Path::cast(node).is_some(),
+                "pat" => ast::Pat::cast(node).is_some(),
+                "stmt" => match node.kind() {
+                    LET_STMT | EXPR_STMT => true,
+                    _ => ast::ModuleItem::cast(node).is_some(),
+                },
+                "item" => ast::ModuleItem::cast(node).is_some(),
+                "meta" => node.kind() == META_ITEM,
+                _ => false,
+            };
+            if !is_fragment {
+                return None;
+            }
+            if kind != "stmt" {
+                return Some(node.range().end());
+            }
+            // Statements don't include their trailing semicolon.
+            let last = node
+                .children()
+                .filter(|it| !it.kind().is_trivia() && it.kind() != SEMI)
+                .last()?;
+            Some(last.range().end())
+        })
+        .filter(|&end| first_error.map_or(true, |offset| end <= offset))
+        .max()?;
+    if end <= start || end > start + TextUnit::of_str(text) {
+        return None;
+    }
+    Some(end - start)
+}
+
+fn expand_subtree(
+    template: &mbe::Subtree,
+    bindings: &Bindings,
+    nesting: &mut Vec<usize>,
+) -> Option<tt::Subtree> {
+    let mut token_trees = Vec::new();
+    for tt in template.token_trees.iter() {
+        expand_tt(tt, bindings, nesting, &mut token_trees)?;
+    }
+    Some(tt::Subtree {
+        delimiter: template.delimiter,
+        token_trees,
+    })
+}
+
+fn expand_tt(
+    template: &mbe::TokenTree,
+    bindings: &Bindings,
+    nesting: &mut Vec<usize>,
+    acc: &mut Vec<tt::TokenTree>,
+) -> Option<()> {
+    match template {
+        mbe::TokenTree::Subtree(subtree) => {
+            acc.push(expand_subtree(subtree, bindings, nesting)?.into())
+        }
+        mbe::TokenTree::Repeat(repeat) => {
+            for i in 0..repeat_count(repeat, bindings, nesting)? {
+                nesting.push(i);
+                let subtree = expand_subtree(&repeat.subtree, bindings, nesting);
+                nesting.pop();
+                if i > 0 {
+                    if let Some(separator) = &repeat.separator {
+                        push_separator(separator, acc);
+                    }
+                }
+                acc.extend(subtree?.token_trees);
+            }
+        }
+        mbe::TokenTree::Leaf(leaf) => {
+            let leaf = match leaf {
+                mbe::Leaf::Var(mbe::Var { text, .. }) => {
+                    match bindings.get(text, nesting) {
+                        Some(tt::TokenTree::Subtree(subtree))
+                            if subtree.delimiter == tt::Delimiter::None =>
+                        {
+                            acc.extend(subtree.token_trees.iter().cloned());
+                            return Some(());
+                        }
+                        Some(tt) => {
+                            acc.push(tt.clone());
+                            return Some(());
+                        }
+                        None => (),
+                    }
+                    if text != "crate" {
+                        return None;
+                    }
+                    tt::Leaf::Ident(tt::Ident {
+                        text: text.clone(),
+                        id: tt::TokenId::unspecified(),
+                    })
+                }
+                mbe::Leaf::Punct(punct) => tt::Leaf::Punct(punct.clone()),
+                mbe::Leaf::Ident(ident) => tt::Leaf::Ident(ident.clone()),
+                mbe::Leaf::Literal(literal) => tt::Leaf::Literal(literal.clone()),
+            };
+            acc.push(leaf.into())
+        }
+    }
+    Some(())
+}
+
+/// The number of iterations of a repetition is determined by the repeating
+/// metavariables it contains.
+fn repeat_count(repeat: &mbe::Repeat, bindings: &Bindings, nesting: &[usize]) -> Option<usize> {
+    let mut vars = Vec::new();
+    collect_vars(&repeat.subtree, &mut vars);
+    vars.iter().find_map(|var| match bindings.walk(var, nesting)? {
+        Binding::Nested(it) => Some(it.len()),
+        Binding::Simple(_) => None,
+    })
+}
+
+fn collect_vars(subtree: &mbe::Subtree, acc: &mut Vec<SmolStr>) {
+    for tt in subtree.token_trees.iter() {
+        match tt {
+            mbe::TokenTree::Leaf(mbe::Leaf::Var(var)) => acc.push(var.text.clone()),
+            mbe::TokenTree::Leaf(_) => (),
+            mbe::TokenTree::Subtree(it) => collect_vars(it, acc),
+            mbe::TokenTree::Repeat(it) => collect_vars(&it.subtree, acc),
+        }
+    }
+}
+
+fn push_separator(separator: &mbe::Separator, acc: &mut Vec<tt::TokenTree>) {
+    let id = tt::TokenId::unspecified();
+    match separator {
+        mbe::Separator::Ident(text) => {
+            acc.push(tt::Leaf::Ident(tt::Ident { text: text.clone(), id }).into())
+        }
+        mbe::Separator::Literal(text) => {
+            acc.push(tt::Leaf::Literal(tt::Literal { text: text.clone(), id }).into())
+        }
+        mbe::Separator::Puncts(chars) => {
+            for (i, &char) in chars.iter().enumerate() {
+                let spacing = if i + 1 == chars.len() {
+                    tt::Spacing::Alone
+                } else {
+                    tt::Spacing::Joint
+                };
+                acc.push(tt::Leaf::Punct(tt::Punct { char, spacing, id }).into());
+            }
+        }
+    }
+}
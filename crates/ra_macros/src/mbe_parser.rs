@@ -0,0 +1,140 @@
+use crate::tt::{self, Spacing};
+use crate::tt_cursor::TtCursor;
+use crate::mbe;
+
+/// Parses the body of a `macro_rules!` definition, that is, the
+/// `(lhs) => {rhs}; ...` sequence of rules.
+pub(crate) fn parse(tt: &tt::Subtree) -> Option<mbe::MacroRules> {
+    let mut parser = TtCursor::new(tt);
+    let mut rules = Vec::new();
+    while !parser.is_eof() {
+        rules.push(parse_rule(&mut parser)?);
+        if parser.expect_char(';').is_none() {
+            break;
+        }
+    }
+    if !parser.is_eof() || rules.is_empty() {
+        return None;
+    }
+    Some(mbe::MacroRules { rules })
+}
+
+fn parse_rule(p: &mut TtCursor) -> Option<mbe::Rule> {
+    let lhs = parse_subtree(p.eat_subtree()?, true)?;
+    p.expect_char('=')?;
+    p.expect_char('>')?;
+    let rhs = parse_subtree(p.eat_subtree()?, false)?;
+    Some(mbe::Rule { lhs, rhs })
+}
+
+fn parse_subtree(tt: &tt::Subtree, is_matcher: bool) -> Option<mbe::Subtree> {
+    let mut token_trees = Vec::new();
+    let mut p = TtCursor::new(tt);
+    while let Some(tt) = p.eat() {
+        let child: mbe::TokenTree = match tt {
+            tt::TokenTree::Leaf(leaf) => match leaf {
+                tt::Leaf::Punct(tt::Punct { char: '$', .. }) => {
+                    if p.at_ident().is_some() {
+                        parse_var(&mut p, is_matcher)?.into()
+                    } else if let Some(subtree) = p.eat_subtree() {
+                        parse_repeat(&mut p, subtree, is_matcher)?.into()
+                    } else {
+                        mbe::Leaf::Punct(tt::Punct {
+                            char: '$',
+                            spacing: Spacing::Alone,
+                            id: tt::TokenId::unspecified(),
+                        })
+                        .into()
+                    }
+                }
+                // Tokens of the definition do not point into the macro call,
+                // so their ids are dropped.
+                tt::Leaf::Punct(punct) => mbe::Leaf::Punct(tt::Punct {
+                    id: tt::TokenId::unspecified(),
+                    ..punct.clone()
+                })
+                .into(),
+                tt::Leaf::Ident(ident) => mbe::Leaf::Ident(tt::Ident {
+                    text: ident.text.clone(),
+                    id: tt::TokenId::unspecified(),
+                })
+                .into(),
+                tt::Leaf::Literal(literal) => mbe::Leaf::Literal(tt::Literal {
+                    text: literal.text.clone(),
+                    id: tt::TokenId::unspecified(),
+                })
+                .into(),
+            },
+            tt::TokenTree::Subtree(subtree) => parse_subtree(subtree, is_matcher)?.into(),
+        };
+        token_trees.push(child);
+    }
+    Some(mbe::Subtree {
+        delimiter: tt.delimiter,
+        token_trees,
+    })
+}
+
+fn parse_var(p: &mut TtCursor, is_matcher: bool) -> Option<mbe::Var> {
+    let ident = p.eat_ident()?;
+    let text = ident.text.clone();
+    let kind = if is_matcher && p.at_char(':') {
+        p.bump();
+        Some(p.eat_ident()?.text.clone())
+    } else {
+        None
+    };
+    Some(mbe::Var { text, kind })
+}
+
+fn parse_repeat(
+    p: &mut TtCursor,
+    subtree: &tt::Subtree,
+    is_matcher: bool,
+) -> Option<mbe::Repeat> {
+    let subtree = parse_subtree(subtree, is_matcher)?;
+    let separator = if repeat_kind(p).is_some() {
+        None
+    } else {
+        Some(parse_separator(p)?)
+    };
+    let kind = repeat_kind(p)?;
+    p.bump();
+    Some(mbe::Repeat {
+        subtree,
+        kind,
+        separator,
+    })
+}
+
+fn repeat_kind(p: &TtCursor) -> Option<mbe::RepeatKind> {
+    let punct = p.at_punct()?;
+    if punct.spacing != Spacing::Alone {
+        return None;
+    }
+    let kind = match punct.char {
+        '*' => mbe::RepeatKind::ZeroOrMore,
+        '+' => mbe::RepeatKind::OneOrMore,
+        '?' => mbe::RepeatKind::ZeroOrOne,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn parse_separator(p: &mut TtCursor) -> Option<mbe::Separator> {
+    if let Some(ident) = p.eat_ident() {
+        return Some(mbe::Separator::Ident(ident.text.clone()));
+    }
+    if let Some(literal) = p.eat_literal() {
+        return Some(mbe::Separator::Literal(literal.text.clone()));
+    }
+    let mut chars = Vec::new();
+    loop {
+        let punct = p.eat_punct()?;
+        chars.push(punct.char);
+        if punct.spacing == Spacing::Alone {
+            break;
+        }
+    }
+    Some(mbe::Separator::Puncts(chars))
+}
@@ -0,0 +1,136 @@
+use ra_syntax::{ast, AstNode, SyntaxKind::*, SyntaxNodeRef, TextRange, TextUnit};
+
+use crate::tt::{self, TokenId};
+
+/// Maps the ids of tokens produced by `ast_to_token_tree` back to their
+/// ranges in the source file.
+#[derive(Debug, Default)]
+pub struct TokenMap {
+    tokens: Vec<TextRange>,
+}
+
+impl TokenMap {
+    pub fn range_of(&self, id: TokenId) -> Option<TextRange> {
+        self.tokens.get(id.0 as usize).cloned()
+    }
+
+    fn alloc(&mut self, range: TextRange) -> TokenId {
+        let id = TokenId(self.tokens.len() as u32);
+        self.tokens.push(range);
+        id
+    }
+}
+
+/// Converts the syntax tree of a token tree to a `tt::Subtree`. Whitespace
+/// and comments are dropped, multi-character operators are split into
+/// `Joint` puncts.
+pub fn ast_to_token_tree(ast: ast::TokenTree) -> Option<(tt::Subtree, TokenMap)> {
+    let mut token_map = TokenMap::default();
+    let tt = convert_tt(&mut token_map, ast.syntax())?;
+    Some((tt, token_map))
+}
+
+fn convert_tt(token_map: &mut TokenMap, tt: SyntaxNodeRef) -> Option<tt::Subtree> {
+    let first_child = tt.first_child()?;
+    let last_child = tt.last_child()?;
+    let (delimiter, close) = match first_child.kind() {
+        L_PAREN => (tt::Delimiter::Parenthesis, R_PAREN),
+        L_CURLY => (tt::Delimiter::Brace, R_CURLY),
+        L_BRACK => (tt::Delimiter::Bracket, R_BRACK),
+        _ => return None,
+    };
+    let mut token_trees = Vec::new();
+    for child in tt.children().skip(1) {
+        if child == last_child && child.kind() == close {
+            break;
+        }
+        match child.kind() {
+            WHITESPACE | COMMENT => (),
+            TOKEN_TREE => token_trees.push(convert_tt(token_map, child)?.into()),
+            INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE | STRING | RAW_STRING | BYTE_STRING
+            | RAW_BYTE_STRING => {
+                let id = token_map.alloc(child.range());
+                let text = child.leaf_text()?.clone();
+                token_trees.push(tt::Leaf::Literal(tt::Literal { text, id }).into());
+            }
+            kind if kind == IDENT || kind == LIFETIME || kind.is_keyword() => {
+                let id = token_map.alloc(child.range());
+                let text = child.leaf_text()?.clone();
+                token_trees.push(tt::Leaf::Ident(tt::Ident { text, id }).into());
+            }
+            _ => {
+                let text = child.leaf_text()?;
+                let mut offset = child.range().start();
+                let mut chars = text.chars().peekable();
+                while let Some(char) = chars.next() {
+                    let len = TextUnit::of_char(char);
+                    let id = token_map.alloc(TextRange::offset_len(offset, len));
+                    offset += len;
+                    let spacing = if chars.peek().is_some() {
+                        tt::Spacing::Joint
+                    } else {
+                        tt::Spacing::Alone
+                    };
+                    token_trees.push(tt::Leaf::Punct(tt::Punct { char, spacing, id }).into());
+                }
+            }
+        }
+    }
+    Some(tt::Subtree {
+        delimiter,
+        token_trees,
+    })
+}
+
+/// Renders a token tree to source text, like its `Display` impl does, and
+/// records the range each token with a known id ends up at.
+pub fn token_tree_to_text(tt: &tt::Subtree) -> (String, Vec<(TokenId, TextRange)>) {
+    let mut text = String::new();
+    let mut ranges = Vec::new();
+    render_subtree(tt, &mut text, &mut ranges);
+    (text, ranges)
+}
+
+fn render_subtree(
+    subtree: &tt::Subtree,
+    buf: &mut String,
+    ranges: &mut Vec<(TokenId, TextRange)>,
+) {
+    if let Some(c) = subtree.delimiter.open_char() {
+        buf.push(c);
+    }
+    let mut needs_space = false;
+    for tt in subtree.token_trees.iter() {
+        if needs_space {
+            buf.push(' ');
+        }
+        needs_space = true;
+        match tt {
+            tt::TokenTree::Subtree(it) => render_subtree(it, buf, ranges),
+            tt::TokenTree::Leaf(leaf) => {
+                let start = TextUnit::of_str(buf);
+                let id = match leaf {
+                    tt::Leaf::Ident(it) => {
+                        buf.push_str(&it.text);
+                        it.id
+                    }
+                    tt::Leaf::Literal(it) => {
+                        buf.push_str(&it.text);
+                        it.id
+                    }
+                    tt::Leaf::Punct(it) => {
+                        buf.push(it.char);
+                        needs_space = it.spacing == tt::Spacing::Alone;
+                        it.id
+                    }
+                };
+                if id != TokenId::unspecified() {
+                    ranges.push((id, TextRange::from_to(start, TextUnit::of_str(buf))));
+                }
+            }
+        }
+    }
+    if let Some(c) = subtree.delimiter.close_char() {
+        buf.push(c);
+    }
+}
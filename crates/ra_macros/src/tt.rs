@@ -0,0 +1,147 @@
+//! A simple token tree, the input and output of macro expansion.
+//!
+//! Each token carries a `TokenId`, so that tokens which survive expansion
+//! can be traced back to the source they came from.
+use std::fmt;
+
+use ra_syntax::SmolStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenId(pub u32);
+
+impl TokenId {
+    /// Id for tokens which do not originate from the source, like the ones
+    /// a transcriber introduces.
+    pub const fn unspecified() -> TokenId {
+        TokenId(!0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenTree {
+    Leaf(Leaf),
+    Subtree(Subtree),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Leaf {
+    Literal(Literal),
+    Punct(Punct),
+    Ident(Ident),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subtree {
+    pub delimiter: Delimiter,
+    pub token_trees: Vec<TokenTree>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Parenthesis,
+    Brace,
+    Bracket,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Literal {
+    pub text: SmolStr,
+    pub id: TokenId,
+}
+
+/// A single punctuation character. Multi-character operators like `=>` are
+/// represented as several `Joint` puncts followed by an `Alone` one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Punct {
+    pub char: char,
+    pub spacing: Spacing,
+    pub id: TokenId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spacing {
+    Alone,
+    Joint,
+}
+
+/// Identifiers, keywords and lifetimes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident {
+    pub text: SmolStr,
+    pub id: TokenId,
+}
+
+impl From<Leaf> for TokenTree {
+    fn from(leaf: Leaf) -> TokenTree {
+        TokenTree::Leaf(leaf)
+    }
+}
+
+impl From<Subtree> for TokenTree {
+    fn from(subtree: Subtree) -> TokenTree {
+        TokenTree::Subtree(subtree)
+    }
+}
+
+impl Delimiter {
+    pub fn open_char(self) -> Option<char> {
+        match self {
+            Delimiter::Parenthesis => Some('('),
+            Delimiter::Brace => Some('{'),
+            Delimiter::Bracket => Some('['),
+            Delimiter::None => None,
+        }
+    }
+
+    pub fn close_char(self) -> Option<char> {
+        match self {
+            Delimiter::Parenthesis => Some(')'),
+            Delimiter::Brace => Some('}'),
+            Delimiter::Bracket => Some(']'),
+            Delimiter::None => None,
+        }
+    }
+}
+
+impl fmt::Display for TokenTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenTree::Leaf(it) => fmt::Display::fmt(it, f),
+            TokenTree::Subtree(it) => fmt::Display::fmt(it, f),
+        }
+    }
+}
+
+impl fmt::Display for Subtree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(c) = self.delimiter.open_char() {
+            write!(f, "{}", c)?;
+        }
+        let mut needs_space = false;
+        for tt in self.token_trees.iter() {
+            if needs_space {
+                f.write_str(" ")?;
+            }
+            needs_space = true;
+            if let TokenTree::Leaf(Leaf::Punct(p)) = tt {
+                needs_space = p.spacing == Spacing::Alone;
+            }
+            fmt::Display::fmt(tt, f)?;
+        }
+        if let Some(c) = self.delimiter.close_char() {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Leaf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Leaf::Ident(it) => fmt::Display::fmt(&it.text, f),
+            Leaf::Literal(it) => fmt::Display::fmt(&it.text, f),
+            Leaf::Punct(it) => fmt::Display::fmt(&it.char, f),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::tt;
+
+/// A cursor over the token trees of a single subtree. It is `Clone`, so that
+/// the matcher can fork it to backtrack.
+#[derive(Clone)]
+pub(crate) struct TtCursor<'a> {
+    subtree: &'a tt::Subtree,
+    pos: usize,
+}
+
+impl<'a> TtCursor<'a> {
+    pub(crate) fn new(subtree: &'a tt::Subtree) -> TtCursor<'a> {
+        TtCursor { subtree, pos: 0 }
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_eof(&self) -> bool {
+        self.pos == self.subtree.token_trees.len()
+    }
+
+    pub(crate) fn rest(&self) -> &'a [tt::TokenTree] {
+        &self.subtree.token_trees[self.pos..]
+    }
+
+    pub(crate) fn current(&self) -> Option<&'a tt::TokenTree> {
+        self.subtree.token_trees.get(self.pos)
+    }
+
+    pub(crate) fn at_punct(&self) -> Option<&'a tt::Punct> {
+        match self.current() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Punct(it))) => Some(it),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn at_char(&self, char: char) -> bool {
+        match self.at_punct() {
+            Some(it) => it.char == char,
+            None => false,
+        }
+    }
+
+    pub(crate) fn at_ident(&self) -> Option<&'a tt::Ident> {
+        match self.current() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Ident(it))) => Some(it),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn at_literal(&self) -> Option<&'a tt::Literal> {
+        match self.current() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Literal(it))) => Some(it),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    pub(crate) fn bump_n(&mut self, n: usize) {
+        self.pos += n;
+    }
+
+    pub(crate) fn eat(&mut self) -> Option<&'a tt::TokenTree> {
+        let res = self.current()?;
+        self.bump();
+        Some(res)
+    }
+
+    pub(crate) fn eat_subtree(&mut self) -> Option<&'a tt::Subtree> {
+        match self.current()? {
+            tt::TokenTree::Subtree(it) => {
+                self.bump();
+                Some(it)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn eat_punct(&mut self) -> Option<&'a tt::Punct> {
+        let res = self.at_punct()?;
+        self.bump();
+        Some(res)
+    }
+
+    pub(crate) fn eat_ident(&mut self) -> Option<&'a tt::Ident> {
+        let res = self.at_ident()?;
+        self.bump();
+        Some(res)
+    }
+
+    pub(crate) fn eat_literal(&mut self) -> Option<&'a tt::Literal> {
+        let res = self.at_literal()?;
+        self.bump();
+        Some(res)
+    }
+
+    pub(crate) fn expect_char(&mut self, char: char) -> Option<()> {
+        if self.at_char(char) {
+            self.bump();
+            Some(())
+        } else {
+            None
+        }
+    }
+}
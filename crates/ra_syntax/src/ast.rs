@@ -8,6 +8,7 @@ use itertools::Itertools;
 pub use self::generated::*;
 use crate::{
     yellow::{RefRoot, SyntaxNodeChildren},
+    string_lexing::{self, StringComponentKind},
     SmolStr,
     SyntaxKind::*,
     SyntaxNodeRef,
@@ -24,6 +25,12 @@ pub trait AstNode<'a>: Clone + Copy + 'a {
     fn syntax(self) -> SyntaxNodeRef<'a>;
 }
 
+pub trait VisibilityOwner<'a>: AstNode<'a> {
+    fn visibility(self) -> Option<Visibility<'a>> {
+        child_opt(self)
+    }
+}
+
 pub trait NameOwner<'a>: AstNode<'a> {
     fn name(self) -> Option<Name<'a>> {
         child_opt(self)
@@ -34,6 +41,9 @@ pub trait LoopBodyOwner<'a>: AstNode<'a> {
     fn loop_body(self) -> Option<Block<'a>> {
         child_opt(self)
     }
+    fn label(self) -> Option<Label<'a>> {
+        child_opt(self)
+    }
 }
 
 pub trait ArgListOwner<'a>: AstNode<'a> {
@@ -68,6 +78,10 @@ pub trait AttrsOwner<'a>: AstNode<'a> {
     fn attrs(self) -> AstChildren<'a, Attr<'a>> {
         children(self)
     }
+    /// `true` if the node has a `#[atom]` attribute, like `#[macro_use]`.
+    fn has_atom_attr(self, atom: &str) -> bool {
+        self.attrs().filter_map(|it| it.as_atom()).any(|it| it == atom)
+    }
 }
 
 pub trait DocCommentsOwner<'a>: AstNode<'a> {
@@ -97,6 +111,10 @@ impl<'a> FnDef<'a> {
     pub fn has_atom_attr(&self, atom: &str) -> bool {
         self.attrs().filter_map(|x| x.as_atom()).any(|x| x == atom)
     }
+
+    pub fn is_unsafe(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNSAFE_KW)
+    }
 }
 
 impl<'a> Attr<'a> {
@@ -110,6 +128,19 @@ impl<'a> Attr<'a> {
         }
     }
 
+    /// For `#[key = "value"]`, returns the key and the unquoted value.
+    pub fn as_key_value(&self) -> Option<(SmolStr, SmolStr)> {
+        let tt = self.value()?;
+        let mut children = tt.syntax().children().filter(|it| !it.kind().is_trivia());
+        let _bra = children.next()?;
+        let (key, eq, value, _ket) = children.collect_tuple()?;
+        if key.kind() != IDENT || eq.kind() != EQ || value.kind() != STRING {
+            return None;
+        }
+        let value = value.leaf_text()?.trim_matches('"').into();
+        Some((key.leaf_text()?.clone(), value))
+    }
+
     pub fn as_call(&self) -> Option<(SmolStr, TokenTree<'a>)> {
         let tt = self.value()?;
         let (_bra, attr, args, _ket) = tt.syntax().children().collect_tuple()?;
@@ -128,6 +159,19 @@ impl<'a> Lifetime<'a> {
     }
 }
 
+impl<'a> Label<'a> {
+    pub fn lifetime(self) -> Option<Lifetime<'a>> {
+        child_opt(self)
+    }
+}
+
+impl<'a> BreakExpr<'a> {
+    /// The label of `break 'a`, which names the loop to break out of.
+    pub fn lifetime(self) -> Option<Lifetime<'a>> {
+        child_opt(self)
+    }
+}
+
 impl<'a> Char<'a> {
     pub fn text(&self) -> &SmolStr {
         &self.syntax().leaf_text().unwrap()
@@ -152,6 +196,100 @@ impl<'a> String<'a> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiteralFlavor {
+    String,
+    /// A byte string, with its length in bytes if it is well-formed.
+    ByteString { len: Option<u64> },
+    Char,
+    Byte,
+    IntNumber {
+        suffix: Option<SmolStr>,
+    },
+    FloatNumber {
+        suffix: Option<SmolStr>,
+    },
+    Bool,
+}
+
+impl<'a> Literal<'a> {
+    /// The literal's token. Error-recovered literal nodes may not have one.
+    pub fn token(&self) -> Option<SyntaxNodeRef<'a>> {
+        self.syntax().first_child()
+    }
+
+    /// The kind of the literal, or `None` if its token isn't a literal.
+    pub fn flavor(&self) -> Option<LiteralFlavor> {
+        const INT_SUFFIXES: [&str; 12] = [
+            "u64", "u32", "u16", "u8", "usize", "u128", "i64", "i32", "i16", "i8", "isize", "i128",
+        ];
+        const FLOAT_SUFFIXES: [&str; 2] = ["f32", "f64"];
+
+        let token = self.token()?;
+        let text = token.leaf_text().map(|it| it.as_str()).unwrap_or("");
+        let find_suffix = |suffixes: &[&str]| {
+            suffixes
+                .iter()
+                .find(|&s| text.ends_with(s))
+                .map(|&s| SmolStr::new(s))
+        };
+
+        let flavor = match token.kind() {
+            INT_NUMBER => {
+                let is_hex = text.starts_with("0x");
+                if let Some(suffix) = find_suffix(&INT_SUFFIXES) {
+                    LiteralFlavor::IntNumber {
+                        suffix: Some(suffix),
+                    }
+                } else if let (false, Some(suffix)) = (is_hex, find_suffix(&FLOAT_SUFFIXES)) {
+                    // `1f32` is a float literal
+                    LiteralFlavor::FloatNumber {
+                        suffix: Some(suffix),
+                    }
+                } else {
+                    LiteralFlavor::IntNumber { suffix: None }
+                }
+            }
+            FLOAT_NUMBER => LiteralFlavor::FloatNumber {
+                suffix: find_suffix(&FLOAT_SUFFIXES),
+            },
+            STRING | RAW_STRING => LiteralFlavor::String,
+            TRUE_KW | FALSE_KW => LiteralFlavor::Bool,
+            BYTE_STRING => LiteralFlavor::ByteString {
+                len: byte_string_len(text),
+            },
+            RAW_BYTE_STRING => LiteralFlavor::ByteString {
+                len: raw_byte_string_len(text),
+            },
+            CHAR => LiteralFlavor::Char,
+            BYTE => LiteralFlavor::Byte,
+            _ => return None,
+        };
+        Some(flavor)
+    }
+}
+
+/// The number of bytes of `b"..."`, after unescaping.
+fn byte_string_len(text: &str) -> Option<u64> {
+    let mut components = string_lexing::parse_byte_string_literal(text);
+    let len = (&mut components)
+        .filter(|it| it.kind != StringComponentKind::IgnoreNewline)
+        .count();
+    if !components.has_closing_quote {
+        return None;
+    }
+    Some(len as u64)
+}
+
+/// The number of bytes of `br#"..."#`.
+fn raw_byte_string_len(text: &str) -> Option<u64> {
+    let text = text.get(2..)?.trim_matches('#');
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') {
+        return None;
+    }
+    Some(text.len() as u64 - 2)
+}
+
 impl<'a> Comment<'a> {
     pub fn text(&self) -> &SmolStr {
         self.syntax().leaf_text().unwrap()
@@ -272,12 +410,25 @@ impl<'a> LetStmt<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElseBranchFlavor<'a> {
+    Block(Block<'a>),
+    IfExpr(IfExpr<'a>),
+}
+
 impl<'a> IfExpr<'a> {
     pub fn then_branch(self) -> Option<Block<'a>> {
         self.blocks().nth(0)
     }
-    pub fn else_branch(self) -> Option<Block<'a>> {
-        self.blocks().nth(1)
+    pub fn else_branch(self) -> Option<ElseBranchFlavor<'a>> {
+        let res = match self.blocks().nth(1) {
+            Some(block) => ElseBranchFlavor::Block(block),
+            None => {
+                let elif: IfExpr = child_opt(self)?;
+                ElseBranchFlavor::IfExpr(elif)
+            }
+        };
+        Some(res)
     }
     fn blocks(self) -> AstChildren<'a, Block<'a>> {
         children(self)
@@ -313,6 +464,103 @@ impl<'a> PathSegment<'a> {
         };
         Some(res)
     }
+
+    /// `true` for the first segment of a path rooted in the extern prelude,
+    /// like `::foo::Bar`.
+    pub fn has_leading_coloncolon(self) -> bool {
+        self.syntax().first_child().map(|it| it.kind()) == Some(COLONCOLON)
+    }
+
+    /// For a qualified segment like `<Foo as Trait>`, returns the self type
+    /// and the trait.
+    pub fn qualified_type(self) -> Option<(TypeRef<'a>, Option<PathType<'a>>)> {
+        if self.syntax().first_child().map(|it| it.kind()) != Some(L_ANGLE) {
+            return None;
+        }
+        let mut types = self.syntax().children().filter_map(TypeRef::cast);
+        let self_type = types.next()?;
+        let trait_type = types.next().and_then(|it| match it {
+            TypeRef::PathType(it) => Some(it),
+            _ => None,
+        });
+        Some((self_type, trait_type))
+    }
+}
+
+impl<'a> TypeParam<'a> {
+    /// The trait bounds of this parameter, e.g. `Clone` and `Debug` in
+    /// `T: Clone + Debug`.
+    pub fn bounds(self) -> Vec<PathType<'a>> {
+        type_bounds(bounds_after_colon(self.syntax()))
+    }
+}
+
+impl<'a> WherePred<'a> {
+    /// The trait bounds of this predicate, i.e. everything after the `:`.
+    pub fn bounds(self) -> Vec<PathType<'a>> {
+        type_bounds(bounds_after_colon(self.syntax()))
+    }
+}
+
+impl<'a> ImplTraitType<'a> {
+    /// The trait bounds of this type, e.g. `Iterator<Item = u32>` and `Clone`
+    /// in `impl Iterator<Item = u32> + Clone`.
+    pub fn bounds(self) -> Vec<PathType<'a>> {
+        type_bounds(child_opt(self))
+    }
+}
+
+impl<'a> DynTraitType<'a> {
+    /// The trait bounds of this type, e.g. `Handler` and `Send` in
+    /// `dyn Handler + Send`.
+    pub fn bounds(self) -> Vec<PathType<'a>> {
+        type_bounds(child_opt(self))
+    }
+}
+
+fn bounds_after_colon<'a>(node: SyntaxNodeRef<'a>) -> Option<PathType<'a>> {
+    node.children()
+        .skip_while(|it| it.kind() != COLON)
+        .find_map(PathType::cast)
+}
+
+/// `A + B` bounds are parsed as nested path types, starting with `first`; this
+/// flattens them.
+fn type_bounds<'a>(first: Option<PathType<'a>>) -> Vec<PathType<'a>> {
+    let mut res = Vec::new();
+    let mut next = first;
+    while let Some(path_type) = next {
+        res.push(path_type);
+        next = path_type.syntax().children().find_map(PathType::cast);
+    }
+    res
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfParamFlavor {
+    /// self
+    Owned,
+    /// &self
+    Ref,
+    /// &mut self
+    MutRef,
+}
+
+impl<'a> SelfParam<'a> {
+    /// How `self` is taken. For `self: Type`, this is `Owned`; the type is
+    /// given by `type_ref`.
+    pub fn flavor(self) -> SelfParamFlavor {
+        let mut children = self.syntax().children().map(|it| it.kind());
+        if children.next() != Some(AMP) {
+            return SelfParamFlavor::Owned;
+        }
+        // `&mut self`, as opposed to `mut self`
+        if children.any(|kind| kind == MUT_KW) {
+            SelfParamFlavor::MutRef
+        } else {
+            SelfParamFlavor::Ref
+        }
+    }
 }
 
 impl<'a> UseTree<'a> {
@@ -330,6 +578,38 @@ impl<'a> UseTreeList<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityKind<'a> {
+    /// `pub`
+    Pub,
+    /// `pub(crate)` or `crate`
+    PubCrate,
+    /// `pub(super)`
+    PubSuper,
+    /// `pub(self)`
+    PubSelf,
+    /// `pub(in path)`
+    PubIn(Path<'a>),
+}
+
+impl<'a> Visibility<'a> {
+    pub fn kind(self) -> VisibilityKind<'a> {
+        if let Some(path) = self.path() {
+            return VisibilityKind::PubIn(path);
+        }
+        let mut tokens = self.syntax().children().map(|it| it.kind());
+        if tokens.next() == Some(CRATE_KW) {
+            return VisibilityKind::PubCrate;
+        }
+        match tokens.find(|&it| it != L_PAREN && !it.is_trivia()) {
+            Some(CRATE_KW) => VisibilityKind::PubCrate,
+            Some(SUPER_KW) => VisibilityKind::PubSuper,
+            Some(SELF_KW) => VisibilityKind::PubSelf,
+            _ => VisibilityKind::Pub,
+        }
+    }
+}
+
 fn child_opt<'a, P: AstNode<'a>, C: AstNode<'a>>(parent: P) -> Option<C> {
     children(parent).next()
 }
@@ -407,6 +687,12 @@ impl<'a> ReferenceType<'a> {
     }
 }
 
+impl<'a> BlockExpr<'a> {
+    pub fn is_unsafe(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNSAFE_KW)
+    }
+}
+
 impl<'a> RefExpr<'a> {
     pub fn is_mut(&self) -> bool {
         self.syntax().children().any(|n| n.kind() == MUT_KW)
@@ -433,3 +719,169 @@ impl<'a> PrefixExpr<'a> {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    /// The `||` operator for boolean OR
+    BooleanOr,
+    /// The `&&` operator for boolean AND
+    BooleanAnd,
+    /// The `==` operator for equality testing
+    EqualityTest,
+    /// The `!=` operator for equality testing
+    NegatedEqualityTest,
+    /// The `<=` operator for lesser-equal testing
+    LesserEqualTest,
+    /// The `>=` operator for greater-equal testing
+    GreaterEqualTest,
+    /// The `<` operator for comparison
+    LesserTest,
+    /// The `>` operator for comparison
+    GreaterTest,
+    /// The `+` operator for addition
+    Addition,
+    /// The `-` operator for subtraction
+    Subtraction,
+    /// The `*` operator for multiplication
+    Multiplication,
+    /// The `/` operator for division
+    Division,
+    /// The `%` operator for remainder after division
+    Remainder,
+    /// The `<<` operator for left shift
+    LeftShift,
+    /// The `>>` operator for right shift
+    RightShift,
+    /// The `^` operator for bitwise XOR
+    BitwiseXor,
+    /// The `|` operator for bitwise OR
+    BitwiseOr,
+    /// The `&` operator for bitwise AND
+    BitwiseAnd,
+    /// The `=` operator for assignment
+    Assignment,
+}
+
+impl<'a> BinExpr<'a> {
+    pub fn op(&self) -> Option<BinOp> {
+        self.syntax()
+            .children()
+            .filter_map(|c| match c.kind() {
+                PIPEPIPE => Some(BinOp::BooleanOr),
+                AMPAMP => Some(BinOp::BooleanAnd),
+                EQEQ => Some(BinOp::EqualityTest),
+                NEQ => Some(BinOp::NegatedEqualityTest),
+                LTEQ => Some(BinOp::LesserEqualTest),
+                GTEQ => Some(BinOp::GreaterEqualTest),
+                L_ANGLE => Some(BinOp::LesserTest),
+                R_ANGLE => Some(BinOp::GreaterTest),
+                PLUS => Some(BinOp::Addition),
+                MINUS => Some(BinOp::Subtraction),
+                STAR => Some(BinOp::Multiplication),
+                SLASH => Some(BinOp::Division),
+                PERCENT => Some(BinOp::Remainder),
+                SHL => Some(BinOp::LeftShift),
+                SHR => Some(BinOp::RightShift),
+                CARET => Some(BinOp::BitwiseXor),
+                PIPE => Some(BinOp::BitwiseOr),
+                AMP => Some(BinOp::BitwiseAnd),
+                EQ => Some(BinOp::Assignment),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn lhs(self) -> Option<Expr<'a>> {
+        children(self).nth(0)
+    }
+
+    pub fn rhs(self) -> Option<Expr<'a>> {
+        children(self).nth(1)
+    }
+}
+
+#[derive(Debug)]
+pub enum ArrayExprKind<'a> {
+    /// `[initializer; repeat]`
+    Repeat {
+        initializer: Option<Expr<'a>>,
+        repeat: Option<Expr<'a>>,
+    },
+    /// `[a, b, c]`
+    ElementList(AstChildren<'a, Expr<'a>>),
+}
+
+impl<'a> ArrayExpr<'a> {
+    pub fn kind(self) -> ArrayExprKind<'a> {
+        if self.syntax().children().any(|c| c.kind() == SEMI) {
+            ArrayExprKind::Repeat {
+                initializer: children(self).nth(0),
+                repeat: children(self).nth(1),
+            }
+        } else {
+            ArrayExprKind::ElementList(children(self))
+        }
+    }
+}
+
+impl<'a> IndexExpr<'a> {
+    pub fn base(self) -> Option<Expr<'a>> {
+        children(self).nth(0)
+    }
+
+    pub fn index(self) -> Option<Expr<'a>> {
+        children(self).nth(1)
+    }
+}
+
+impl<'a> NamedFieldList<'a> {
+    /// The base expression of a functional record update, i.e. `base` in
+    /// `S { a: 1, ..base }`.
+    pub fn spread(self) -> Option<Expr<'a>> {
+        self.syntax()
+            .children()
+            .skip_while(|n| n.kind() != DOTDOT)
+            .find_map(Expr::cast)
+    }
+}
+
+impl<'a> TuplePat<'a> {
+    /// For `(a, .., z)`, returns the number of sub-patterns before the `..`.
+    pub fn ellipsis_pos(self) -> Option<usize> {
+        self.syntax()
+            .children()
+            .filter(|n| n.kind() == DOTDOT || Pat::cast(*n).is_some())
+            .position(|n| n.kind() == DOTDOT)
+    }
+}
+
+impl<'a> RefPat<'a> {
+    pub fn is_mut(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == MUT_KW)
+    }
+}
+
+impl<'a> BindPat<'a> {
+    pub fn is_mutable(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == MUT_KW)
+    }
+
+    pub fn is_ref(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == REF_KW)
+    }
+}
+
+impl<'a> MacroCall<'a> {
+    /// For a `macro_rules! foo { ... }` definition, returns the `foo`
+    /// identifier token.
+    pub fn macro_rules_name(self) -> Option<SyntaxNodeRef<'a>> {
+        let path = self.path()?;
+        if path.qualifier().is_some() {
+            return None;
+        }
+        if path.segment()?.name_ref()?.text() != "macro_rules" {
+            return None;
+        }
+        self.syntax().children().find(|n| n.kind() == IDENT)
+    }
+}
@@ -367,8 +367,8 @@ fn match_arm(p: &mut Parser) -> BlockLike {
     while p.eat(PIPE) {
         patterns::pattern(p);
     }
-    if p.eat(IF_KW) {
-        expr(p);
+    if p.at(IF_KW) {
+        match_guard(p);
     }
     p.expect(FAT_ARROW);
     let ret = expr_stmt(p);
@@ -376,6 +376,20 @@ fn match_arm(p: &mut Parser) -> BlockLike {
     ret
 }
 
+// test match_guard
+// fn foo() {
+//     match () {
+//         _ if foo => (),
+//     }
+// }
+fn match_guard(p: &mut Parser) -> CompletedMarker {
+    assert!(p.at(IF_KW));
+    let m = p.start();
+    p.bump();
+    expr(p);
+    m.complete(p, MATCH_GUARD)
+}
+
 // test block_expr
 // fn foo() {
 //     {};
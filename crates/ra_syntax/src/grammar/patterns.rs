@@ -53,7 +53,7 @@ fn atom_pat(p: &mut Parser, recovery_set: TokenSet) -> Option<CompletedMarker> {
     //         "hello" => (),
     //     }
     // }
-    if let Some(m) = expressions::literal(p) {
+    if let Some(m) = literal_pat(p) {
         return Some(m);
     }
 
@@ -70,6 +70,12 @@ fn atom_pat(p: &mut Parser, recovery_set: TokenSet) -> Option<CompletedMarker> {
     Some(m)
 }
 
+fn literal_pat(p: &mut Parser) -> Option<CompletedMarker> {
+    let literal = expressions::literal(p)?;
+    let m = literal.precede(p);
+    Some(m.complete(p, LITERAL_PAT))
+}
+
 // test path_part
 // fn foo() {
 //     let foo::Bar = ();
@@ -123,11 +129,7 @@ fn field_pat_list(p: &mut Parser) {
     while !p.at(EOF) && !p.at(R_CURLY) {
         match p.current() {
             DOTDOT => p.bump(),
-            IDENT if p.nth(1) == COLON => {
-                p.bump();
-                p.bump();
-                pattern(p);
-            }
+            IDENT if p.nth(1) == COLON => field_pat(p),
             L_CURLY => error_block(p, "expected ident"),
             _ => {
                 bind_pat(p, false);
@@ -141,6 +143,17 @@ fn field_pat_list(p: &mut Parser) {
     m.complete(p, FIELD_PAT_LIST);
 }
 
+fn field_pat(p: &mut Parser) {
+    assert!(p.at(IDENT));
+    assert!(p.nth(1) == COLON);
+
+    let m = p.start();
+    name(p);
+    p.bump();
+    pattern(p);
+    m.complete(p, FIELD_PAT);
+}
+
 // test placeholder_pat
 // fn main() { let _ = (); }
 fn placeholder_pat(p: &mut Parser) -> CompletedMarker {
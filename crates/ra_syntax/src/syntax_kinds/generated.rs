@@ -151,10 +151,12 @@ pub enum SyntaxKind {
     PATH_PAT,
     STRUCT_PAT,
     FIELD_PAT_LIST,
+    FIELD_PAT,
     TUPLE_STRUCT_PAT,
     TUPLE_PAT,
     SLICE_PAT,
     RANGE_PAT,
+    LITERAL_PAT,
     TUPLE_EXPR,
     ARRAY_EXPR,
     PAREN_EXPR,
@@ -412,10 +414,12 @@ impl SyntaxKind {
             PATH_PAT => &SyntaxInfo { name: "PATH_PAT" },
             STRUCT_PAT => &SyntaxInfo { name: "STRUCT_PAT" },
             FIELD_PAT_LIST => &SyntaxInfo { name: "FIELD_PAT_LIST" },
+            FIELD_PAT => &SyntaxInfo { name: "FIELD_PAT" },
             TUPLE_STRUCT_PAT => &SyntaxInfo { name: "TUPLE_STRUCT_PAT" },
             TUPLE_PAT => &SyntaxInfo { name: "TUPLE_PAT" },
             SLICE_PAT => &SyntaxInfo { name: "SLICE_PAT" },
             RANGE_PAT => &SyntaxInfo { name: "RANGE_PAT" },
+            LITERAL_PAT => &SyntaxInfo { name: "LITERAL_PAT" },
             TUPLE_EXPR => &SyntaxInfo { name: "TUPLE_EXPR" },
             ARRAY_EXPR => &SyntaxInfo { name: "ARRAY_EXPR" },
             PAREN_EXPR => &SyntaxInfo { name: "PAREN_EXPR" },
@@ -0,0 +1,5 @@
+fn foo() {
+    match () {
+        _ if foo => (),
+    }
+}